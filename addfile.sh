@@ -0,0 +1,6 @@
+#!/bin/bash
+set -euo pipefail
+
+content_type="${3:-application/octet-stream}"
+
+curl -X POST "http://localhost:8000/api/$1" --data-binary "@$2" -H "Content-Type: ${content_type}"
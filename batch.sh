@@ -0,0 +1,11 @@
+#!/bin/bash
+set -euo pipefail
+
+queue="$1"
+shift
+items=""
+for content in "$@" ; do
+  items="${items:+${items},}"'{ "content":"'"${content}"'"}'
+done
+
+curl -X POST "http://localhost:8000/api/${queue}/batch" -d "[${items}]" -H "Content-Type: application/json"
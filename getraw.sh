@@ -0,0 +1,4 @@
+#!/bin/bash
+set -euo pipefail
+
+curl -D - "http://localhost:8000/api/$1/raw?action=get"
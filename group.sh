@@ -0,0 +1,6 @@
+#!/bin/bash
+set -euo pipefail
+
+curl "http://localhost:8000/api/$1?action=browse&group=$2&max_messages=10"
+echo
+curl -X POST http://localhost:8000/api/$1/groups/$2/commit -d '{}' -H "Content-Type: application/json"
@@ -0,0 +1,4 @@
+#!/bin/bash
+set -euo pipefail
+
+curl -X PUT http://localhost:8000/api/$1/retention -d '{ "max_age_seconds":"'"$2"'"}' -H "Content-Type: application/json"
@@ -1,7 +1,10 @@
-use msg_q::config::Config;
+use msg_q::config::{Config,Storage};
+use msg_q::domain::messages::ports::MessageRepository;
 use msg_q::domain::messages::service::Service;
 use msg_q::inbound::http::{HttpServer,HttpServerConfig};
+use msg_q::outbound::log::Log;
 use msg_q::outbound::memory::Memory;
+use msg_q::outbound::sqlite::Sqlite;
 
 
 #[tokio::main]
@@ -10,8 +13,24 @@ async fn main() -> anyhow::Result<()> {
   
   tracing_subscriber::fmt::init();
 
-  let repo = Memory::new().await?;
-  let service = Service::new(repo);
+  let auto_create = config.auto_create_queues;
+  match &config.storage {
+    Storage::Memory(None) => serve(Memory::new().await?.with_auto_create(auto_create), &config).await,
+    Storage::Memory(Some(snapshot)) => {
+      let repo = Memory::from_snapshot(&snapshot.path).await?.with_auto_create(auto_create);
+      let saver = repo.save_snapshot_every(snapshot.path.clone(), snapshot.interval);
+      let result = serve(repo.clone(), &config).await;
+      saver.abort();
+      repo.save_snapshot(&snapshot.path)?;
+      result
+      },
+    Storage::Log(dir) => serve(Log::new(dir).await?.with_auto_create(auto_create), &config).await,
+    Storage::Sqlite(path) => serve(Sqlite::new(path).await?.with_auto_create(auto_create), &config).await,
+    }
+  }
+
+async fn serve(repo: impl MessageRepository, config: &Config) -> anyhow::Result<()> {
+  let service = Service::new(repo).with_dedup_window(config.dedup_window);
   
   let server_config = HttpServerConfig {
                        port: &config.server_port,
@@ -1,19 +1,64 @@
 use std::env;
-use anyhow::Context;
+use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::{bail, Context};
+
+use crate::domain::messages::models::message::DEFAULT_DEDUP_WINDOW;
 
 const SERVER_PORT_KEY: &str = "SERVER_PORT";
+const STORAGE_KEY: &str = "STORAGE";
+const LOG_DIR_KEY: &str = "LOG_DIR";
+const SQLITE_PATH_KEY: &str = "SQLITE_PATH";
+const SNAPSHOT_PATH_KEY: &str = "SNAPSHOT_PATH";
+const SNAPSHOT_INTERVAL_KEY: &str = "SNAPSHOT_INTERVAL_SECONDS";
+const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+const DEDUP_WINDOW_KEY: &str = "DEDUP_WINDOW_SECONDS";
+const AUTO_CREATE_QUEUES_KEY: &str = "AUTO_CREATE_QUEUES";
 
 #[derive(Debug,Clone,PartialEq,Eq)]
 pub struct Config {
   pub server_port: String,
+  pub storage: Storage,
+  pub dedup_window: Duration,
+  pub auto_create_queues: bool,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum Storage {
+  Memory(Option<SnapshotConfig>),
+  Log(PathBuf),
+  Sqlite(PathBuf),
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct SnapshotConfig {
+  pub path: PathBuf,
+  pub interval: Duration,
 }
 
 impl Config {
   pub fn from_env() -> anyhow::Result<Config> {
     let server_port = load_env(SERVER_PORT_KEY)?;
+    let storage = match env::var(STORAGE_KEY).ok().as_deref() {
+      None | Some("memory") => Storage::Memory(load_snapshot_config()?),
+      Some("log") => Storage::Log(load_env(LOG_DIR_KEY)?.into()),
+      Some("sqlite") => Storage::Sqlite(load_env(SQLITE_PATH_KEY)?.into()),
+      Some(other) => bail!("{} is not a valid value for {}", other, STORAGE_KEY),
+      };
+    let dedup_window = match env::var(DEDUP_WINDOW_KEY) {
+      Err(_) => DEFAULT_DEDUP_WINDOW,
+      Ok(s) => Duration::from_secs(s.parse().with_context(|| format!("{} is not a valid value for {}", s, DEDUP_WINDOW_KEY))?),
+      };
+    let auto_create_queues = match env::var(AUTO_CREATE_QUEUES_KEY) {
+      Err(_) => true,
+      Ok(s) => s.parse().with_context(|| format!("{} is not a valid value for {}", s, AUTO_CREATE_QUEUES_KEY))?,
+      };
 
     Ok(Config {
         server_port,
+        storage,
+        dedup_window,
+        auto_create_queues,
         })
     }
   }
@@ -21,3 +66,17 @@ impl Config {
 fn load_env(key: &str) -> anyhow::Result<String> {
   env::var(key).with_context(|| format!("failed to load environment variable {}", key))
   }
+
+fn load_snapshot_config() -> anyhow::Result<Option<SnapshotConfig>> {
+  let Ok(path) = env::var(SNAPSHOT_PATH_KEY) else {
+    return Ok(None);
+    };
+  let interval = match env::var(SNAPSHOT_INTERVAL_KEY) {
+    Err(_) => DEFAULT_SNAPSHOT_INTERVAL,
+    Ok(s) => Duration::from_secs(s.parse().with_context(|| format!("{} is not a valid value for {}", s, SNAPSHOT_INTERVAL_KEY))?),
+    };
+  Ok(Some(SnapshotConfig {
+      path: path.into(),
+      interval,
+      }))
+  }
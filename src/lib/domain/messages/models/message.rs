@@ -1,8 +1,9 @@
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use uuid::Uuid;
 
 #[cfg(test)]
@@ -12,6 +13,8 @@ use mock_instant::global::Instant;
 use std::time::Instant;
 
 use derive_more::From;
+
+use crate::domain::messages::models::selector::Selector;
 use thiserror::Error;
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -26,9 +29,16 @@ pub struct GetMessageOptions {
     action: GetMessageAction,
     mid: Option<Uuid>,
     cid: Option<Uuid>,
+    receipt: Option<Uuid>,
     reservation: Option<Instant>,
     expiry: Option<Instant>,
     cursor: Option<usize>,
+    after_priority: Option<i32>,
+    max_messages: Option<usize>,
+    order: Option<MessageOrder>,
+    selector: Option<Selector>,
+    group: Option<QueueName>,
+    wait: Option<Duration>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -38,6 +48,7 @@ pub enum GetMessageAction {
     Reserve,
     Confirm,
     Return,
+    Extend,
     Query,
 }
 
@@ -50,6 +61,7 @@ impl TryFrom<&str> for GetMessageAction {
             "reserve" => Ok(Self::Reserve),
             "confirm" => Ok(Self::Confirm),
             "return" => Ok(Self::Return),
+            "extend" => Ok(Self::Extend),
             "query" => Ok(Self::Query),
             _ => Err(GetMessageError::InvalidParameter(format!(
                 "{} is not valid for action",
@@ -59,14 +71,39 @@ impl TryFrom<&str> for GetMessageAction {
     }
 }
 
+/// The order in which messages are handed out.  `Priority` takes the highest
+/// priority first and the lowest cursor within a priority.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MessageOrder {
+    Cursor,
+    Priority,
+}
+
+impl TryFrom<&str> for MessageOrder {
+    type Error = GetMessageError;
+    fn try_from(value: &str) -> Result<Self, GetMessageError> {
+        match value {
+            "cursor" => Ok(Self::Cursor),
+            "priority" => Ok(Self::Priority),
+            _ => Err(GetMessageError::InvalidParameter(format!(
+                "{} is not valid for order",
+                value
+            ))),
+        }
+    }
+}
+
 impl GetMessageAction {
     pub fn validate(&self, gmo: &GetMessageOptions) -> Result<(), GetMessageError> {
         match self {
-            Self::Reserve => gmo.needs_reservation()?,
-            Self::Confirm => gmo.needs_mid()?,
-            Self::Return => gmo.needs_mid()?,
-            Self::Query => gmo.no_reservation()?,
-            Self::Browse => gmo.no_reservation()?,
+            Self::Confirm => gmo.needs_receipt().and(gmo.no_wait())?,
+            Self::Return => gmo.needs_receipt().and(gmo.no_wait())?,
+            Self::Extend => gmo
+                .needs_receipt()
+                .and(gmo.needs_reservation())
+                .and(gmo.no_wait())?,
+            Self::Query => gmo.no_reservation().and(gmo.no_wait())?,
+            Self::Browse => gmo.no_reservation().and(gmo.no_wait())?,
             _ => {}
         }
         Ok(())
@@ -74,6 +111,25 @@ impl GetMessageAction {
 }
 
 impl GetMessageOptions {
+    fn new(queue_name: QueueName, action: GetMessageAction) -> Self {
+        Self {
+            queue_name,
+            action,
+            mid: None,
+            cid: None,
+            receipt: None,
+            reservation: None,
+            expiry: None,
+            cursor: None,
+            after_priority: None,
+            max_messages: None,
+            order: None,
+            selector: None,
+            group: None,
+            wait: None,
+        }
+    }
+
     pub fn queue_name(&self) -> &QueueName {
         &self.queue_name
     }
@@ -86,6 +142,11 @@ impl GetMessageOptions {
     pub fn cid(&self) -> Option<Uuid> {
         self.cid
     }
+    /// The receipt handed out when the message was reserved, which confirm,
+    /// return and extend must present.
+    pub fn receipt(&self) -> Option<Uuid> {
+        self.receipt
+    }
     pub fn reservation(&self) -> &Option<Instant> {
         &self.reservation
     }
@@ -95,6 +156,69 @@ impl GetMessageOptions {
     pub fn cursor(&self) -> &Option<usize> {
         &self.cursor
     }
+    /// With `cursor`, the priority of the last message seen when listing in
+    /// priority order.
+    pub fn after_priority(&self) -> Option<i32> {
+        self.after_priority
+    }
+    /// Browsing defaults to cursor order and everything else to priority
+    /// order.
+    pub fn order(&self) -> MessageOrder {
+        self.order.unwrap_or(match self.action {
+            GetMessageAction::Browse => MessageOrder::Cursor,
+            _ => MessageOrder::Priority,
+        })
+    }
+    /// How many messages a get, reserve or browse may return at once, if a
+    /// list was asked for.
+    pub fn max_messages(&self) -> Option<usize> {
+        self.max_messages
+    }
+    /// Narrows a get, reserve or browse to the messages it selects.
+    pub fn selector(&self) -> Option<&Selector> {
+        self.selector.as_ref()
+    }
+    /// The consumer group a browse reads through, starting after the
+    /// group's position rather than at `after`.
+    pub fn group(&self) -> Option<&QueueName> {
+        self.group.as_ref()
+    }
+    /// How long a get or reserve may wait for a matching message to arrive.
+    pub fn wait(&self) -> Option<Duration> {
+        self.wait
+    }
+
+    /// Replaces the cursor, so that only messages after `cursor` match.
+    pub fn with_cursor(self, cursor: Option<usize>) -> Self {
+        Self { cursor, ..self }
+    }
+
+    /// Reserves for the queue's `default` when no `reservation_seconds` were
+    /// given; a reserve with neither is refused.
+    pub fn with_default_reservation(
+        self,
+        default: Option<Duration>,
+    ) -> Result<Self, GetMessageError> {
+        if self.action != GetMessageAction::Reserve || self.reservation.is_some() {
+            return Ok(self);
+        }
+        let reservation = default.map(|d| Instant::now() + d).ok_or_else(|| {
+            GetMessageError::NoReservation(format!(
+                "{} has no default reservation, so reservation_seconds must be given",
+                self.queue_name
+            ))
+        })?;
+        Ok(Self {
+            reservation: Some(reservation),
+            ..self
+        })
+    }
+
+    /// Sets how long to wait for a message without the checks made on the
+    /// `wait_seconds` parameter, for callers that poll a queue themselves.
+    pub fn with_wait(self, wait: Option<Duration>) -> Self {
+        Self { wait, ..self }
+    }
 
     pub fn needs_mid(&self) -> Result<(), GetMessageError> {
         self.mid
@@ -102,6 +226,12 @@ impl GetMessageOptions {
             .map(|_| ())
     }
 
+    pub fn needs_receipt(&self) -> Result<(), GetMessageError> {
+        self.receipt
+            .ok_or(GetMessageError::MissingParameter("receipt".to_string()))
+            .map(|_| ())
+    }
+
     pub fn no_reservation(&self) -> Result<(), GetMessageError> {
         if self.reservation.is_some() {
             Err(GetMessageError::InvalidParameter(
@@ -112,6 +242,16 @@ impl GetMessageOptions {
         }
     }
 
+    pub fn no_wait(&self) -> Result<(), GetMessageError> {
+        if self.wait.is_some() {
+            Err(GetMessageError::InvalidParameter(
+                "wait_seconds".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn needs_reservation(&self) -> Result<(), GetMessageError> {
         self.reservation
             .ok_or(GetMessageError::MissingParameter(
@@ -120,32 +260,104 @@ impl GetMessageOptions {
             .map(|_| ())
     }
 
+    /// True if `msg` comes after the position given by `cursor` and
+    /// `after_priority`.
+    fn is_after(&self, msg: &Message) -> bool {
+        match (self.cursor, self.after_priority) {
+            (None, _) => true,
+            (Some(cursor), None) => msg.cursor > cursor,
+            (Some(cursor), Some(priority)) => msg.order_key() > (Reverse(priority), cursor),
+        }
+    }
+
+    /// True if there is no selector or it selects `msg`.
+    pub fn selects(&self, msg: &Message) -> bool {
+        self.selector.as_ref().is_none_or(|s| s.selects(msg))
+    }
+
+    /// True if the receipt is for the current reservation of `msg`.
+    fn holds(&self, msg: &Message) -> bool {
+        msg.is_reserved()
+            && msg.receipt.is_some()
+            && msg.receipt == self.receipt
+            && (self.mid.is_none() || msg.mid == self.mid.unwrap())
+    }
+
+    /// Explains why nothing in `messages` matched: a confirm, return or
+    /// extend whose message is still there has a receipt for a reservation
+    /// which has lapsed or been taken over.
+    pub fn no_match<'a>(&self, mut messages: impl Iterator<Item = &'a Message>) -> GetMessageError {
+        let stale = match self.action {
+            GetMessageAction::Confirm | GetMessageAction::Return | GetMessageAction::Extend => {
+                messages.any(|m| m.receipt == self.receipt || Some(m.mid) == self.mid)
+            }
+            _ => false,
+        };
+        if stale {
+            GetMessageError::NotReserved(format!(
+                "receipt {} does not hold a current reservation",
+                self.receipt.unwrap_or_default()
+            ))
+        } else {
+            GetMessageError::NoMessage(format!("{}", self.queue_name))
+        }
+    }
+
     pub fn matches(&self, msg: &Message) -> bool {
         match self.action() {
-            GetMessageAction::Browse => {
+            GetMessageAction::Browse | GetMessageAction::Query => {
                 !msg.is_reserved()
                     && !msg.is_expired()
+                    && !msg.is_delayed()
                     && (self.mid.is_none() || msg.mid == self.mid.unwrap())
                     && (self.cid.is_none() || msg.cid == self.cid)
-                    && (self.cursor.is_none() || msg.cursor > self.cursor.unwrap())
+                    && self.is_after(msg)
+                    && self.selects(msg)
             }
             GetMessageAction::Get => {
                 !msg.is_reserved()
+                    && !msg.consumed
                     && !msg.is_expired()
+                    && !msg.is_delayed()
                     && (self.mid.is_none() || msg.mid == self.mid.unwrap())
                     && (self.cid.is_none() || msg.cid == self.cid)
-                    && (self.cursor.is_none() || msg.cursor > self.cursor.unwrap())
+                    && self.is_after(msg)
+                    && self.selects(msg)
             }
-            GetMessageAction::Confirm => msg.is_reserved() && msg.mid == self.mid.unwrap(),
+            GetMessageAction::Confirm => self.holds(msg),
             GetMessageAction::Reserve => {
                 !msg.is_reserved()
+                    && !msg.consumed
                     && !msg.is_expired()
+                    && !msg.is_delayed()
                     && (self.mid.is_none() || msg.mid == self.mid.unwrap())
                     && (self.cid.is_none() || msg.cid == self.cid)
-                    && (self.cursor.is_none() || msg.cursor > self.cursor.unwrap())
+                    && self.is_after(msg)
+                    && self.selects(msg)
+            }
+            GetMessageAction::Return | GetMessageAction::Extend => self.holds(msg),
+        }
+    }
+
+    /// The first `limit` of `messages`, which are in cursor order, that match,
+    /// in the order they are to be handed out.
+    pub fn select<'a>(
+        &self,
+        messages: impl IntoIterator<Item = &'a Message>,
+        limit: usize,
+    ) -> Vec<(usize, &'a Message)> {
+        let matching = messages
+            .into_iter()
+            .enumerate()
+            .filter(|(_, m)| self.matches(m));
+        match self.order() {
+            MessageOrder::Cursor => matching.take(limit).collect(),
+            MessageOrder::Priority => {
+                let mut matching = matching.collect::<Vec<_>>();
+                matching.sort_by_key(|(_, m)| m.order_key());
+                matching.truncate(limit);
+                matching
             }
-            GetMessageAction::Return => msg.is_reserved() && msg.mid == self.mid.unwrap(),
-            GetMessageAction::Query => unreachable!(),
         }
     }
 }
@@ -171,6 +383,13 @@ impl TryFrom<HashMap<String, String>> for GetMessageOptions {
                     .map_err(|_| GetMessageError::InvalidParameter("mid".to_string()))?,
             ),
         };
+        let receipt = match m.get("receipt") {
+            None => None,
+            Some(s) => Some(
+                Uuid::try_parse(s)
+                    .map_err(|_| GetMessageError::InvalidParameter("receipt".to_string()))?,
+            ),
+        };
         let cid = match m.get("cid") {
             None => None,
             Some(s) => Some(
@@ -205,16 +424,91 @@ impl TryFrom<HashMap<String, String>> for GetMessageOptions {
                     .map_err(|_| GetMessageError::InvalidParameter("after".to_string()))?,
             ),
         };
+        let after_priority = match m.get("after_priority") {
+            None => None,
+            Some(s) => Some(
+                s.parse()
+                    .map_err(|_| GetMessageError::InvalidParameter("after_priority".to_string()))?,
+            ),
+        };
+        let max_messages = match m.get("max_messages") {
+            None => None,
+            Some(s) => Some(s.parse::<usize>().ok().filter(|n| *n > 0).ok_or(
+                GetMessageError::InvalidParameter("max_messages".to_string()),
+            )?),
+        };
+        let order = match m.get("order") {
+            None => None,
+            Some(s) => Some(s.as_str().try_into()?),
+        };
+        let selector = match m.get("selector") {
+            None => None,
+            Some(s) => Some(
+                Selector::parse(s)
+                    .map_err(|e| GetMessageError::InvalidParameter(format!("selector: {}", e)))?,
+            ),
+        };
+        let group = match m.get("group") {
+            None => None,
+            Some(s) => Some(
+                s.try_into()
+                    .map_err(|_| GetMessageError::InvalidParameter("group".to_string()))?,
+            ),
+        };
+        let wait = match m.get("wait_seconds") {
+            None => None,
+            Some(s) => Some(
+                s.parse::<u64>()
+                    .map(Duration::from_secs)
+                    .map_err(|_| GetMessageError::InvalidParameter("wait_seconds".to_string()))?,
+            ),
+        };
         let gmo = Self {
             queue_name,
             action,
             mid,
             cid,
+            receipt,
             reservation,
             expiry,
             cursor,
+            after_priority,
+            max_messages,
+            order,
+            selector,
+            group,
+            wait,
         };
         action.validate(&gmo)?;
+        let searches = matches!(
+            action,
+            GetMessageAction::Get | GetMessageAction::Reserve | GetMessageAction::Browse
+        );
+        if gmo.max_messages.is_some() && !searches {
+            return Err(GetMessageError::InvalidParameter(
+                "max_messages".to_string(),
+            ));
+        }
+        if gmo.selector.is_some() && !searches {
+            return Err(GetMessageError::InvalidParameter("selector".to_string()));
+        }
+        if gmo.group.is_some() {
+            if action != GetMessageAction::Browse {
+                return Err(GetMessageError::InvalidParameter("group".to_string()));
+            }
+            if gmo.cursor.is_some() {
+                return Err(GetMessageError::InvalidParameter("after".to_string()));
+            }
+        }
+        if gmo.after_priority.is_some() {
+            if gmo.order() != MessageOrder::Priority {
+                return Err(GetMessageError::InvalidParameter(
+                    "after_priority".to_string(),
+                ));
+            }
+            gmo.cursor
+                .ok_or(GetMessageError::MissingParameter("after".to_string()))?;
+        }
         Ok(gmo)
     }
 }
@@ -254,26 +548,167 @@ impl Display for QueueSummaryError {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Message {
     mid: uuid::Uuid,
     cid: Option<uuid::Uuid>,
     cursor: usize,
-    content: String,
+    content: Content,
     reservation: Reservation,
     expiry: Expiry,
+    #[serde(default)]
+    visibility: Visibility,
+    enqueued_at: SystemTime,
+    first_reserved_at: Option<SystemTime>,
+    #[serde(default)]
+    deliveries: u32,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    receipt: Option<Uuid>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Set instead of removing the message when it is got or confirmed from
+    /// a queue which retains its messages.
+    #[serde(default)]
+    consumed: bool,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Reservation {
     Unreserved,
-    Until(Instant),
+    Until(#[serde(with = "wall_clock")] Instant),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Expiry {
     Permanent,
-    Expire(Instant),
+    Expire(#[serde(with = "wall_clock")] Instant),
+}
+
+/// Whether a message can be handed out yet.  A delayed message is stored but
+/// hidden until its time comes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    NotBefore(#[serde(with = "wall_clock")] Instant),
+}
+
+/// The payload of a message.  Text arrives as a JSON string; anything else is
+/// kept as the bytes that were sent, along with their content type.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Content {
+    Text(String),
+    Binary {
+        content_type: String,
+        #[serde(with = "base64_bytes")]
+        bytes: Vec<u8>,
+    },
+}
+
+impl Content {
+    pub const TEXT_CONTENT_TYPE: &'static str = "text/plain; charset=utf-8";
+
+    pub fn binary(content_type: String, bytes: Vec<u8>) -> Self {
+        Self::Binary {
+            content_type,
+            bytes,
+        }
+    }
+
+    /// The content if it was sent as text.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Content::Text(s) => Some(s),
+            Content::Binary { .. } => None,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            Content::Text(s) => s.as_bytes(),
+            Content::Binary { bytes, .. } => bytes,
+        }
+    }
+
+    pub fn content_type(&self) -> &str {
+        match self {
+            Content::Text(_) => Self::TEXT_CONTENT_TYPE,
+            Content::Binary { content_type, .. } => content_type,
+        }
+    }
+}
+
+impl From<String> for Content {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<&str> for Content {
+    fn from(s: &str) -> Self {
+        Self::Text(s.to_string())
+    }
+}
+
+/// Writes bytes as base64 so that binary content stays compact in JSON.
+pub mod base64_bytes {
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&BASE64_STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        BASE64_STANDARD.decode(s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Converts between monotonic `Instant`s and wall-clock `SystemTime`s so that
+/// reservations and expiries can be persisted and survive a restart.
+pub mod wall_clock {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::sync::OnceLock;
+    use std::time::SystemTime;
+
+    #[cfg(test)]
+    use mock_instant::global::Instant;
+
+    #[cfg(not(test))]
+    use std::time::Instant;
+
+    fn anchor() -> (SystemTime, Instant) {
+        static ANCHOR: OnceLock<(SystemTime, Instant)> = OnceLock::new();
+        *ANCHOR.get_or_init(|| (SystemTime::now(), Instant::now()))
+    }
+
+    pub fn to_system_time(instant: Instant) -> SystemTime {
+        let (system, monotonic) = anchor();
+        match instant.checked_duration_since(monotonic) {
+            Some(d) => system + d,
+            None => system - monotonic.duration_since(instant),
+        }
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Instant {
+        let (system, monotonic) = anchor();
+        match time.duration_since(system) {
+            Ok(d) => monotonic + d,
+            Err(e) => monotonic.checked_sub(e.duration()).unwrap_or(monotonic),
+        }
+    }
+
+    pub fn serialize<S: Serializer>(instant: &Instant, s: S) -> Result<S::Ok, S::Error> {
+        to_system_time(*instant).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Instant, D::Error> {
+        SystemTime::deserialize(d).map(from_system_time)
+    }
 }
 
 impl From<Option<Instant>> for Reservation {
@@ -285,6 +720,15 @@ impl From<Option<Instant>> for Reservation {
     }
 }
 
+impl From<Option<Instant>> for Visibility {
+    fn from(i: Option<Instant>) -> Visibility {
+        match i {
+            None => Self::Visible,
+            Some(i) => Self::NotBefore(i),
+        }
+    }
+}
+
 impl From<Option<Instant>> for Expiry {
     fn from(i: Option<Instant>) -> Expiry {
         match i {
@@ -298,16 +742,24 @@ impl Message {
     pub fn new(
         mid: uuid::Uuid,
         cid: Option<uuid::Uuid>,
-        content: String,
+        content: impl Into<Content>,
         expiry: Option<Instant>,
     ) -> Self {
         Self {
             mid,
             cid,
-            content,
+            content: content.into(),
             cursor: 0,
             reservation: Reservation::Unreserved,
             expiry: expiry.into(),
+            visibility: Visibility::Visible,
+            enqueued_at: wall_clock::to_system_time(Instant::now()),
+            first_reserved_at: None,
+            deliveries: 0,
+            priority: 0,
+            receipt: None,
+            headers: BTreeMap::new(),
+            consumed: false,
         }
     }
 
@@ -318,16 +770,85 @@ impl Message {
         self.cid.as_ref()
     }
 
-    pub fn content(&self) -> &String {
+    pub fn content(&self) -> &Content {
         &self.content
     }
 
     pub fn cursor(&self) -> usize {
         self.cursor
     }
+
+    /// Free-form metadata set by the producer, such as trace ids or content
+    /// types, which is handed back unchanged with the message.
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
+
+    /// Messages with a higher priority are handed out first.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Sorts messages highest priority first, then by cursor.
+    pub fn order_key(&self) -> (Reverse<i32>, usize) {
+        (Reverse(self.priority), self.cursor)
+    }
+
+    pub fn enqueued_at(&self) -> SystemTime {
+        self.enqueued_at
+    }
+    pub fn first_reserved_at(&self) -> Option<SystemTime> {
+        self.first_reserved_at
+    }
+    pub fn reserved_until(&self) -> Option<SystemTime> {
+        match self.reservation {
+            Reservation::Unreserved => None,
+            Reservation::Until(inst) => Some(wall_clock::to_system_time(inst)),
+        }
+    }
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        match self.expiry {
+            Expiry::Permanent => None,
+            Expiry::Expire(inst) => Some(wall_clock::to_system_time(inst)),
+        }
+    }
+    pub fn deliver_at(&self) -> Option<SystemTime> {
+        match self.visibility {
+            Visibility::Visible => None,
+            Visibility::NotBefore(inst) => Some(wall_clock::to_system_time(inst)),
+        }
+    }
+    /// The number of times the message has been reserved.
+    pub fn deliveries(&self) -> u32 {
+        self.deliveries
+    }
+    /// Sets the history of a message being loaded from storage.
+    pub fn restore_history(
+        &mut self,
+        enqueued_at: SystemTime,
+        first_reserved_at: Option<SystemTime>,
+        deliveries: u32,
+    ) {
+        self.enqueued_at = enqueued_at;
+        self.first_reserved_at = first_reserved_at;
+        self.deliveries = deliveries;
+    }
     pub fn set_cursor(&mut self, cursor: usize) {
         self.cursor = cursor
     }
+    pub fn set_priority(&mut self, priority: i32) {
+        self.priority = priority
+    }
+    pub fn set_headers(&mut self, headers: BTreeMap<String, String>) {
+        self.headers = headers
+    }
+    /// The receipt for the current reservation, if there is one.
+    pub fn receipt(&self) -> Option<&Uuid> {
+        self.receipt.as_ref().filter(|_| self.is_reserved())
+    }
+    pub fn set_receipt(&mut self, receipt: Option<Uuid>) {
+        self.receipt = receipt
+    }
 
     pub fn is_reserved(&self) -> bool {
         match self.reservation {
@@ -343,11 +864,60 @@ impl Message {
     pub fn set_reservation(&mut self, inst: &Option<Instant>) {
         if let Some(i) = *inst {
             let new_inst = i;
-            self.reservation = Reservation::Until(new_inst)
+            self.reservation = Reservation::Until(new_inst);
+            self.first_reserved_at
+                .get_or_insert_with(|| wall_clock::to_system_time(Instant::now()));
+            self.deliveries += 1;
+        }
+    }
+    /// Moves the end of the current reservation to `inst` without counting
+    /// another delivery.
+    pub fn extend_reservation(&mut self, inst: &Option<Instant>) {
+        if let Some(i) = *inst {
+            self.reservation = Reservation::Until(i);
         }
     }
     pub fn remove_reservation(&mut self) {
-        self.reservation = Reservation::Unreserved
+        self.reservation = Reservation::Unreserved;
+        self.receipt = None;
+    }
+    /// True if the message has been got or confirmed but kept, so that it is
+    /// only seen by browsing.
+    pub fn is_consumed(&self) -> bool {
+        self.consumed
+    }
+    pub fn set_consumed(&mut self, consumed: bool) {
+        self.consumed = consumed;
+    }
+    /// Marks the message as handled where a get or confirm would otherwise
+    /// remove it.
+    pub fn consume(&mut self) {
+        self.remove_reservation();
+        self.consumed = true;
+    }
+    /// Clears the reservation and delivery count so that a redriven message
+    /// is delivered as if it were new.
+    pub fn redrive(&mut self) {
+        self.remove_reservation();
+        self.deliveries = 0;
+    }
+    /// True if the message was reserved and the reservation ran out without
+    /// being confirmed or returned.
+    pub fn reservation_lapsed(&self) -> bool {
+        match self.reservation {
+            Reservation::Unreserved => false,
+            Reservation::Until(inst) => Instant::now() >= inst,
+        }
+    }
+    /// True if the message is not to be delivered yet.
+    pub fn is_delayed(&self) -> bool {
+        match self.visibility {
+            Visibility::Visible => false,
+            Visibility::NotBefore(inst) => Instant::now() < inst,
+        }
+    }
+    pub fn set_deliver_at(&mut self, inst: &Option<Instant>) {
+        self.visibility = (*inst).into()
     }
     pub fn is_expired(&self) -> bool {
         match self.expiry {
@@ -355,6 +925,16 @@ impl Message {
             Expiry::Expire(inst) => Instant::now() >= inst,
         }
     }
+    /// Pushes any reservation and expiry back by `d`, so that time spent with
+    /// the server stopped does not count against them.
+    pub fn postpone(&mut self, d: Duration) {
+        if let Reservation::Until(inst) = &mut self.reservation {
+            *inst += d
+        }
+        if let Expiry::Expire(inst) = &mut self.expiry {
+            *inst += d
+        }
+    }
     pub fn set_expiry(&mut self, inst: &Option<Instant>) {
         if let Some(i) = *inst {
             let new_inst = i;
@@ -363,7 +943,8 @@ impl Message {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")]
 pub struct QueueName(String);
 impl TryFrom<String> for QueueName {
     type Error = QueueNameEmptyError;
@@ -403,38 +984,603 @@ impl Display for QueueName {
 #[derive(Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, From)]
 pub struct QueueList(pub Vec<String>);
 
+/// Moves a message to `queue_name` once it has been reserved
+/// `max_deliveries` times and its last reservation was returned or lapsed.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DeadLetterPolicy {
+    max_deliveries: u32,
+    queue_name: QueueName,
+}
+
+impl DeadLetterPolicy {
+    pub fn new(max_deliveries: u32, queue_name: QueueName) -> Self {
+        Self {
+            max_deliveries,
+            queue_name,
+        }
+    }
+
+    pub fn max_deliveries(&self) -> u32 {
+        self.max_deliveries
+    }
+
+    pub fn queue_name(&self) -> &QueueName {
+        &self.queue_name
+    }
+
+    /// True if a message whose reservation has ended should be dead-lettered.
+    pub fn exhausted(&self, message: &Message) -> bool {
+        message.deliveries() >= self.max_deliveries
+    }
+}
+
+/// Keeps the messages of a queue when they are got or confirmed, so that the
+/// queue can be browsed again from any cursor.  Messages are trimmed from the
+/// front once they are older than `max_age` or there are more than
+/// `max_messages` of them; a policy with neither keeps everything.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    max_age: Option<Duration>,
+    max_messages: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn new(max_age: Option<Duration>, max_messages: Option<usize>) -> Self {
+        Self {
+            max_age,
+            max_messages,
+        }
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    pub fn max_messages(&self) -> Option<usize> {
+        self.max_messages
+    }
+
+    /// True if `message` has been kept longer than `max_age`.
+    pub fn outlived(&self, message: &Message) -> bool {
+        self.max_age.is_some_and(|max_age| {
+            wall_clock::to_system_time(Instant::now())
+                .duration_since(message.enqueued_at())
+                .is_ok_and(|age| age >= max_age)
+        })
+    }
+}
+
+/// Defaults and limits a queue applies to every message, so that they need
+/// not be trusted to each client.  `expiry` and `reservation` stand in for
+/// `expiry_seconds` and `reservation_seconds` when a request leaves them out.
+///
+/// Whether a queue may be auto-created is not here but a store setting
+/// (`AUTO_CREATE_QUEUES`), since a queue which does not exist yet has no
+/// config to consult.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueConfig {
+    expiry: Option<Duration>,
+    reservation: Option<Duration>,
+    max_depth: Option<usize>,
+    max_content_size: Option<usize>,
+}
+
+impl QueueConfig {
+    pub fn with_expiry(self, expiry: Option<Duration>) -> Self {
+        Self { expiry, ..self }
+    }
+    pub fn with_reservation(self, reservation: Option<Duration>) -> Self {
+        Self {
+            reservation,
+            ..self
+        }
+    }
+    pub fn with_max_depth(self, max_depth: Option<usize>) -> Self {
+        Self { max_depth, ..self }
+    }
+    pub fn with_max_content_size(self, max_content_size: Option<usize>) -> Self {
+        Self {
+            max_content_size,
+            ..self
+        }
+    }
+
+    pub fn expiry(&self) -> Option<Duration> {
+        self.expiry
+    }
+    pub fn reservation(&self) -> Option<Duration> {
+        self.reservation
+    }
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+    pub fn max_content_size(&self) -> Option<usize> {
+        self.max_content_size
+    }
+
+    /// `req` as `queue_name` takes it: refused if its content is too large,
+    /// and given the default expiry if it has none.
+    pub fn prepare(
+        &self,
+        queue_name: &QueueName,
+        req: &CreateMessageRequest,
+    ) -> Result<CreateMessageRequest, CreateMessageError> {
+        let size = req.content().bytes().len();
+        if let Some(max) = self.max_content_size.filter(|max| size > *max) {
+            return Err(CreateMessageError::TooLarge(format!(
+                "{} takes messages of at most {} bytes, not {}",
+                queue_name, max, size
+            )));
+        }
+        let mut req = req.clone();
+        if req.expiry.is_none() {
+            req.expiry = self.expiry.map(|d| Instant::now() + d);
+        }
+        Ok(req)
+    }
+
+    /// Refuses `count` more messages for `queue_name` if they would take it
+    /// past `max_depth` from `depth`.
+    pub fn admit(
+        &self,
+        queue_name: &QueueName,
+        depth: usize,
+        count: usize,
+    ) -> Result<(), CreateMessageError> {
+        match self.max_depth {
+            Some(max) if depth + count > max => Err(CreateMessageError::QueueFull(format!(
+                "{} holds at most {} messages",
+                queue_name, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The settings a queue is created or reconfigured with.  A queue made by
+/// its first message has none of them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct QueueSettings {
+    dead_letter: Option<DeadLetterPolicy>,
+    retention: Option<RetentionPolicy>,
+    #[serde(default)]
+    config: QueueConfig,
+}
+
+impl QueueSettings {
+    pub fn with_dead_letter(self, dead_letter: Option<DeadLetterPolicy>) -> Self {
+        Self {
+            dead_letter,
+            ..self
+        }
+    }
+    pub fn with_retention(self, retention: Option<RetentionPolicy>) -> Self {
+        Self { retention, ..self }
+    }
+    pub fn with_config(self, config: QueueConfig) -> Self {
+        Self { config, ..self }
+    }
+
+    pub fn dead_letter(&self) -> Option<&DeadLetterPolicy> {
+        self.dead_letter.as_ref()
+    }
+    pub fn retention(&self) -> Option<&RetentionPolicy> {
+        self.retention.as_ref()
+    }
+    pub fn config(&self) -> &QueueConfig {
+        &self.config
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum QueueError {
+    NoQueue(String),
+    InvalidSettings(String),
+    #[error(transparent)]
+    Unknown(Arc<anyhow::Error>),
+}
+impl From<anyhow::Error> for QueueError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Unknown(Arc::new(value))
+    }
+}
+
+impl Display for QueueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueError::NoQueue(e) => f.write_str(e),
+            QueueError::InvalidSettings(e) => f.write_str(e),
+            QueueError::Unknown(_) => f.write_str("Unknown"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum RetentionPolicyError {
+    NoQueue(String),
+    InvalidPolicy(String),
+    #[error(transparent)]
+    Unknown(Arc<anyhow::Error>),
+}
+impl From<anyhow::Error> for RetentionPolicyError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Unknown(Arc::new(value))
+    }
+}
+
+impl Display for RetentionPolicyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RetentionPolicyError::NoQueue(e) => f.write_str(e),
+            RetentionPolicyError::InvalidPolicy(e) => f.write_str(e),
+            RetentionPolicyError::Unknown(_) => f.write_str("Unknown"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum DeadLetterPolicyError {
+    NoQueue(String),
+    InvalidPolicy(String),
+    #[error(transparent)]
+    Unknown(Arc<anyhow::Error>),
+}
+impl From<anyhow::Error> for DeadLetterPolicyError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Unknown(Arc::new(value))
+    }
+}
+
+impl Display for DeadLetterPolicyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeadLetterPolicyError::NoQueue(e) => f.write_str(e),
+            DeadLetterPolicyError::InvalidPolicy(e) => f.write_str(e),
+            DeadLetterPolicyError::Unknown(_) => f.write_str("Unknown"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum SubscriptionError {
+    NoQueue(String),
+    NoSubscription(String),
+    #[error(transparent)]
+    Unknown(Arc<anyhow::Error>),
+}
+impl From<anyhow::Error> for SubscriptionError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Unknown(Arc::new(value))
+    }
+}
+
+impl Display for SubscriptionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubscriptionError::NoQueue(e) => f.write_str(e),
+            SubscriptionError::NoSubscription(e) => f.write_str(e),
+            SubscriptionError::Unknown(_) => f.write_str("Unknown"),
+        }
+    }
+}
+
+/// Where a consumer group has read up to in a queue.  A browse through the
+/// group moves `position` to the last message it returned, while `committed`
+/// only moves when the group commits, and is where the group picks up again
+/// after a restart.  Groups are named like queues.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConsumerGroup {
+    queue_name: QueueName,
+    group: QueueName,
+    position: usize,
+    committed: usize,
+}
+
+impl ConsumerGroup {
+    pub fn new(queue_name: QueueName, group: QueueName, position: usize, committed: usize) -> Self {
+        Self {
+            queue_name,
+            group,
+            position,
+            committed,
+        }
+    }
+
+    pub fn queue_name(&self) -> &QueueName {
+        &self.queue_name
+    }
+    pub fn group(&self) -> &QueueName {
+        &self.group
+    }
+    /// The cursor of the last message read through the group.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+    pub fn committed(&self) -> usize {
+        self.committed
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum ConsumerGroupError {
+    NoGroup(String),
+    InvalidCursor(String),
+    #[error(transparent)]
+    Unknown(Arc<anyhow::Error>),
+}
+impl From<anyhow::Error> for ConsumerGroupError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Unknown(Arc::new(value))
+    }
+}
+
+impl Display for ConsumerGroupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConsumerGroupError::NoGroup(e) => f.write_str(e),
+            ConsumerGroupError::InvalidCursor(e) => f.write_str(e),
+            ConsumerGroupError::Unknown(_) => f.write_str("Unknown"),
+        }
+    }
+}
+
+/// Selects the messages in a queue to move to `target`.  With no filters
+/// every available message is moved; otherwise a message must match all of
+/// the filters given.  Reserved and expired messages are never moved.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RedriveRequest {
+    target: QueueName,
+    mid: Option<Uuid>,
+    cid: Option<Uuid>,
+    after: Option<usize>,
+    until: Option<usize>,
+}
+
+impl RedriveRequest {
+    pub fn new(target: QueueName) -> Self {
+        Self {
+            target,
+            mid: None,
+            cid: None,
+            after: None,
+            until: None,
+        }
+    }
+
+    pub fn with_mid(self, mid: Option<Uuid>) -> Self {
+        Self { mid, ..self }
+    }
+
+    pub fn with_cid(self, cid: Option<Uuid>) -> Self {
+        Self { cid, ..self }
+    }
+
+    /// Restricts the move to cursors greater than `after` and no greater than
+    /// `until`.
+    pub fn with_cursor_range(self, after: Option<usize>, until: Option<usize>) -> Self {
+        Self {
+            after,
+            until,
+            ..self
+        }
+    }
+
+    pub fn target(&self) -> &QueueName {
+        &self.target
+    }
+    pub fn mid(&self) -> Option<Uuid> {
+        self.mid
+    }
+    pub fn cid(&self) -> Option<Uuid> {
+        self.cid
+    }
+    pub fn after(&self) -> Option<usize> {
+        self.after
+    }
+    pub fn until(&self) -> Option<usize> {
+        self.until
+    }
+
+    pub fn matches(&self, msg: &Message) -> bool {
+        !msg.is_reserved()
+            && !msg.is_expired()
+            && (self.mid.is_none() || self.mid == Some(msg.mid))
+            && (self.cid.is_none() || msg.cid == self.cid)
+            && (self.after.is_none() || Some(msg.cursor) > self.after)
+            && (self.until.is_none() || Some(msg.cursor) <= self.until)
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum RedriveError {
+    NoQueue(String),
+    InvalidRequest(String),
+    #[error(transparent)]
+    Unknown(Arc<anyhow::Error>),
+}
+impl From<anyhow::Error> for RedriveError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Unknown(Arc::new(value))
+    }
+}
+
+impl Display for RedriveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedriveError::NoQueue(e) => f.write_str(e),
+            RedriveError::InvalidRequest(e) => f.write_str(e),
+            RedriveError::Unknown(_) => f.write_str("Unknown"),
+        }
+    }
+}
+
+/// Confirms or returns several reservations in a queue at once, each named
+/// by its receipt.  Every receipt succeeds or fails on its own.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AcknowledgeRequest {
+    queue_name: QueueName,
+    action: GetMessageAction,
+    receipts: Vec<Uuid>,
+}
+
+impl AcknowledgeRequest {
+    pub fn new(
+        queue_name: QueueName,
+        action: GetMessageAction,
+        receipts: Vec<Uuid>,
+    ) -> Result<Self, GetMessageError> {
+        match action {
+            GetMessageAction::Confirm | GetMessageAction::Return => Ok(Self {
+                queue_name,
+                action,
+                receipts,
+            }),
+            _ => Err(GetMessageError::InvalidParameter("action".to_string())),
+        }
+    }
+
+    pub fn queue_name(&self) -> &QueueName {
+        &self.queue_name
+    }
+    pub fn action(&self) -> GetMessageAction {
+        self.action
+    }
+    pub fn receipts(&self) -> &[Uuid] {
+        &self.receipts
+    }
+
+    /// The options which confirm or return each receipt by itself, in order.
+    pub fn options(&self) -> impl Iterator<Item = GetMessageOptions> + '_ {
+        self.receipts.iter().map(|receipt| GetMessageOptions {
+            receipt: Some(*receipt),
+            ..GetMessageOptions::new(self.queue_name.clone(), self.action)
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, From)]
 pub struct CreateMessageRequest {
-    content: String,
+    content: Content,
     cid: Option<uuid::Uuid>,
     expiry: Option<Instant>,
+    deliver_at: Option<Instant>,
+    priority: i32,
+    headers: BTreeMap<String, String>,
+    dedup_key: Option<String>,
+    dedup_window: Duration,
 }
 
+/// How long a deduplication key is remembered unless the service is told
+/// otherwise.
+pub const DEFAULT_DEDUP_WINDOW: Duration = Duration::from_secs(5 * 60);
+
 impl CreateMessageRequest {
-    pub fn new(content: String, cid: Option<uuid::Uuid>, expiry: Option<Instant>) -> Self {
+    pub fn new(
+        content: impl Into<Content>,
+        cid: Option<uuid::Uuid>,
+        expiry: Option<Instant>,
+    ) -> Self {
         Self {
             cid,
-            content,
+            content: content.into(),
             expiry,
+            deliver_at: None,
+            priority: 0,
+            headers: BTreeMap::new(),
+            dedup_key: None,
+            dedup_window: DEFAULT_DEDUP_WINDOW,
         }
     }
 
+    /// A request with the same `dedup_key` as one made in the same queue
+    /// within the dedup window creates nothing and gets the original message.
+    pub fn with_dedup_key(self, dedup_key: Option<String>) -> Self {
+        Self { dedup_key, ..self }
+    }
+
+    pub fn with_dedup_window(self, dedup_window: Duration) -> Self {
+        Self {
+            dedup_window,
+            ..self
+        }
+    }
+
+    pub fn with_priority(self, priority: i32) -> Self {
+        Self { priority, ..self }
+    }
+
+    pub fn with_headers(self, headers: BTreeMap<String, String>) -> Self {
+        Self { headers, ..self }
+    }
+
+    /// Hides the message from browse, get and reserve until `deliver_at`.
+    pub fn with_deliver_at(self, deliver_at: Option<Instant>) -> Self {
+        Self { deliver_at, ..self }
+    }
+
     pub fn cid(&self) -> Option<&uuid::Uuid> {
         self.cid.as_ref()
     }
 
-    pub fn content(&self) -> &String {
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn content(&self) -> &Content {
         &self.content
     }
 
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
+
     pub fn expiry(&self) -> Option<&Instant> {
         self.expiry.as_ref()
     }
+
+    pub fn deliver_at(&self) -> Option<&Instant> {
+        self.deliver_at.as_ref()
+    }
+
+    pub fn dedup_key(&self) -> Option<&String> {
+        self.dedup_key.as_ref()
+    }
+
+    pub fn dedup_window(&self) -> Duration {
+        self.dedup_window
+    }
+
+    /// True if a message created with the same key at `created_at` is
+    /// recent enough to make this request a duplicate.
+    pub fn within_dedup_window(&self, created_at: SystemTime) -> bool {
+        wall_clock::to_system_time(Instant::now()) < created_at + self.dedup_window
+    }
+
+    /// The new message this request describes.
+    pub fn message(&self, mid: Uuid) -> Message {
+        let mut message = Message::new(mid, self.cid, self.content.clone(), self.expiry);
+        message.set_priority(self.priority);
+        message.set_headers(self.headers.clone());
+        message.set_deliver_at(&self.deliver_at);
+        message
+    }
+
+    /// What a duplicate request hands back when the original message created
+    /// at `created_at` has already left the queue.
+    pub fn duplicate_of(&self, mid: Uuid, created_at: SystemTime) -> Message {
+        let mut message = self.message(mid);
+        message.restore_history(created_at, None, 0);
+        message
+    }
 }
 
 #[derive(Clone, Debug, Error)]
 pub enum CreateMessageError {
     BadQueue(String),
+    NoQueue(String),
+    QueueFull(String),
+    TooLarge(String),
     #[error(transparent)]
     Unknown(Arc<anyhow::Error>),
 }
@@ -465,8 +1611,10 @@ impl From<anyhow::Error> for QueueListError {
 pub enum GetMessageError {
     BadUuid(String),
     NoMessage(String),
+    NotReserved(String),
     MissingParameter(String),
     InvalidParameter(String),
+    NoReservation(String),
     Unknown(Arc<anyhow::Error>),
 }
 
@@ -481,8 +1629,10 @@ impl Display for GetMessageError {
         match self {
             GetMessageError::BadUuid(e) => f.write_str(e),
             GetMessageError::NoMessage(e) => f.write_str(e),
+            GetMessageError::NotReserved(e) => f.write_str(e),
             GetMessageError::MissingParameter(e) => f.write_str(e),
             GetMessageError::InvalidParameter(e) => f.write_str(e),
+            GetMessageError::NoReservation(e) => f.write_str(e),
             GetMessageError::Unknown(_) => f.write_str("Unknown"),
         }
     }
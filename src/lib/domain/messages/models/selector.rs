@@ -0,0 +1,405 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::Peekable;
+use std::str::Chars;
+
+use thiserror::Error;
+
+use crate::domain::messages::models::message::Message;
+
+/// A condition on a message's headers and metadata, such as
+/// `region = 'eu' AND priority > 3`.
+///
+/// A name is looked up first among `mid`, `cid`, `cursor`, `priority` and
+/// `deliveries`, and otherwise among the headers.  Strings are quoted with
+/// `'`, doubled to escape it; anything compared with a number is compared
+/// numerically.  A comparison on a name the message lacks, or on a value
+/// which is not a number when a number is given, is false.  `AND` binds
+/// tighter than `OR`, and `NOT` and parentheses are allowed.
+#[derive(Clone, Debug)]
+pub struct Selector {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(String, Op, Literal),
+    In(String, Vec<Literal>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Literal {
+    Str(String),
+    Num(f64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Literal(Literal),
+    Op(Op),
+    Open,
+    Close,
+    Comma,
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub struct SelectorError(String);
+
+impl Display for SelectorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Selector {
+    pub fn parse(source: &str) -> Result<Self, SelectorError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?.into_iter().peekable(),
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.next() {
+            return Err(SelectorError(format!("unexpected {:?}", token)));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn selects(&self, msg: &Message) -> bool {
+        self.expr.eval(msg)
+    }
+}
+
+// Selectors are compared by their text so that `GetMessageOptions` can keep
+// its derives.
+impl PartialEq for Selector {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+impl Eq for Selector {}
+impl PartialOrd for Selector {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Selector {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.source.cmp(&other.source)
+    }
+}
+impl Hash for Selector {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state)
+    }
+}
+
+impl Expr {
+    fn eval(&self, msg: &Message) -> bool {
+        match self {
+            Expr::Or(a, b) => a.eval(msg) || b.eval(msg),
+            Expr::And(a, b) => a.eval(msg) && b.eval(msg),
+            Expr::Not(a) => !a.eval(msg),
+            Expr::Compare(name, op, literal) => {
+                match field(msg, name).and_then(|value| literal.compare(&value)) {
+                    None => false,
+                    Some(ordering) => match op {
+                        Op::Eq => ordering.is_eq(),
+                        Op::Ne => ordering.is_ne(),
+                        Op::Lt => ordering.is_lt(),
+                        Op::Le => ordering.is_le(),
+                        Op::Gt => ordering.is_gt(),
+                        Op::Ge => ordering.is_ge(),
+                    },
+                }
+            }
+            Expr::In(name, literals) => field(msg, name).is_some_and(|value| {
+                literals
+                    .iter()
+                    .any(|literal| literal.compare(&value) == Some(Ordering::Equal))
+            }),
+        }
+    }
+}
+
+impl Literal {
+    /// How `value` compares with the literal, if they can be compared.
+    fn compare(&self, value: &str) -> Option<Ordering> {
+        match self {
+            Literal::Str(s) => Some(value.cmp(s)),
+            Literal::Num(n) => value.trim().parse::<f64>().ok()?.partial_cmp(n),
+        }
+    }
+}
+
+fn field(msg: &Message, name: &str) -> Option<String> {
+    match name {
+        "mid" => Some(msg.mid().to_string()),
+        "cid" => msg.cid().map(|u| u.to_string()),
+        "cursor" => Some(msg.cursor().to_string()),
+        "priority" => Some(msg.priority().to_string()),
+        "deliveries" => Some(msg.deliveries().to_string()),
+        _ => msg.headers().get(name).cloned(),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, SelectorError> {
+    let mut chars = source.chars().peekable();
+    let mut tokens = vec![];
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' | ')' | ',' | '=' => {
+                chars.next();
+                match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    ',' => Token::Comma,
+                    _ => Token::Op(Op::Eq),
+                }
+            }
+            '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                match (c, eq) {
+                    ('!', true) => Token::Op(Op::Ne),
+                    ('<', true) => Token::Op(Op::Le),
+                    ('<', false) if chars.next_if_eq(&'>').is_some() => Token::Op(Op::Ne),
+                    ('<', false) => Token::Op(Op::Lt),
+                    ('>', true) => Token::Op(Op::Ge),
+                    ('>', false) => Token::Op(Op::Gt),
+                    _ => return Err(SelectorError("expected = after !".to_string())),
+                }
+            }
+            '\'' => {
+                chars.next();
+                Token::Literal(Literal::Str(string(&mut chars)?))
+            }
+            '-' | '0'..='9' => {
+                let text = take_while(&mut chars, |c| c.is_ascii_digit() || "-.".contains(c));
+                let n = text
+                    .parse()
+                    .map_err(|_| SelectorError(format!("{} is not a number", text)))?;
+                Token::Literal(Literal::Num(n))
+            }
+            _ if c.is_alphabetic() || c == '_' => Token::Name(take_while(&mut chars, |c| {
+                c.is_alphanumeric() || "_-.".contains(c)
+            })),
+            _ => return Err(SelectorError(format!("unexpected {}", c))),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn take_while(chars: &mut Peekable<Chars>, f: impl Fn(char) -> bool) -> String {
+    let mut s = String::new();
+    while let Some(c) = chars.next_if(|c| f(*c)) {
+        s.push(c);
+    }
+    s
+}
+
+/// Reads the rest of a quoted string whose opening quote has been read.
+fn string(chars: &mut Peekable<Chars>) -> Result<String, SelectorError> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            None => return Err(SelectorError("unterminated string".to_string())),
+            Some('\'') if chars.next_if_eq(&'\'').is_some() => s.push('\''),
+            Some('\'') => return Ok(s),
+            Some(c) => s.push(c),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    /// Consumes the next token if it is the keyword `word`.
+    fn keyword(&mut self, word: &str) -> bool {
+        self.tokens
+            .next_if(|t| matches!(t, Token::Name(n) if n.eq_ignore_ascii_case(word)))
+            .is_some()
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), SelectorError> {
+        match self.tokens.next() {
+            Some(t) if t == token => Ok(()),
+            Some(t) => Err(SelectorError(format!(
+                "expected {:?}, found {:?}",
+                token, t
+            ))),
+            None => Err(SelectorError(format!("expected {:?}", token))),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, SelectorError> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, SelectorError> {
+        let mut expr = self.unary()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, SelectorError> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.tokens.next_if_eq(&Token::Open).is_some() {
+            let expr = self.or()?;
+            self.expect(Token::Close)?;
+            return Ok(expr);
+        }
+        self.predicate()
+    }
+
+    fn predicate(&mut self) -> Result<Expr, SelectorError> {
+        let name = match self.tokens.next() {
+            Some(Token::Name(name)) => name,
+            Some(t) => return Err(SelectorError(format!("expected a name, found {:?}", t))),
+            None => return Err(SelectorError("expected a name".to_string())),
+        };
+        let negated = self.keyword("not");
+        if self.keyword("in") {
+            self.expect(Token::Open)?;
+            let mut literals = vec![self.literal()?];
+            while self.tokens.next_if_eq(&Token::Comma).is_some() {
+                literals.push(self.literal()?);
+            }
+            self.expect(Token::Close)?;
+            let expr = Expr::In(name, literals);
+            return Ok(if negated {
+                Expr::Not(Box::new(expr))
+            } else {
+                expr
+            });
+        }
+        if negated {
+            return Err(SelectorError("expected IN after NOT".to_string()));
+        }
+        match self.tokens.next() {
+            Some(Token::Op(op)) => Ok(Expr::Compare(name, op, self.literal()?)),
+            Some(t) => Err(SelectorError(format!(
+                "expected a comparison after {}, found {:?}",
+                name, t
+            ))),
+            None => Err(SelectorError(format!(
+                "expected a comparison after {}",
+                name
+            ))),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Literal, SelectorError> {
+        match self.tokens.next() {
+            Some(Token::Literal(literal)) => Ok(literal),
+            Some(t) => Err(SelectorError(format!("expected a value, found {:?}", t))),
+            None => Err(SelectorError("expected a value".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn message(priority: i32, headers: &[(&str, &str)]) -> Message {
+        let mut message = Message::new(Uuid::new_v4(), None, "msg".to_string(), None);
+        message.set_priority(priority);
+        message.set_headers(
+            headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+        );
+        message
+    }
+
+    #[test]
+    fn test_selects() {
+        let eu = message(
+            5,
+            &[("region", "eu"), ("version", "2"), ("trace-id", "it's")],
+        );
+        let us = message(1, &[("region", "us")]);
+        let cases = [
+            ("region = 'eu'", true, false),
+            ("region = 'eu' AND priority > 3", true, false),
+            ("region = 'eu' and priority < 3", false, false),
+            ("region <> 'eu'", false, true),
+            ("region != 'eu' OR priority >= 5", true, true),
+            ("region IN ('us', 'ap')", false, true),
+            ("region NOT IN ('us', 'ap')", true, false),
+            ("NOT (region = 'us' OR version = 2)", false, false),
+            ("version = 2.0", true, false),
+            ("version <= -1", false, false),
+            ("trace-id = 'it''s'", true, false),
+            ("missing = 'x' OR priority=1", false, true),
+        ];
+        for (source, selects_eu, selects_us) in cases {
+            let selector = Selector::parse(source).unwrap();
+            assert_eq!(selector.selects(&eu), selects_eu, "{}", source);
+            assert_eq!(selector.selects(&us), selects_us, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for source in [
+            "",
+            "region",
+            "region = ",
+            "region = 'eu",
+            "region = eu",
+            "region == 'eu'",
+            "(region = 'eu'",
+            "region = 'eu' AND",
+            "region NOT = 'eu'",
+            "region IN ()",
+            "priority > 1-2",
+            "region ! 'eu'",
+            "region = 'eu' priority = 1",
+        ] {
+            assert!(Selector::parse(source).is_err(), "{}", source);
+        }
+    }
+}
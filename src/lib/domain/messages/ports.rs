@@ -1,13 +1,15 @@
 use std::future::Future;
 
 use crate::domain::messages::models::message::{
-    CreateMessageRequest, GetMessageOptions, Message, QueueList, QueueSummary,
+    AcknowledgeRequest, ConsumerGroup, CreateMessageRequest, DeadLetterPolicy, GetMessageOptions,
+    Message, QueueList, QueueSettings, QueueSummary, RedriveRequest, RetentionPolicy,
 };
 
 #[allow(unused_imports)]
 use crate::domain::messages::models::message::QueueName;
 use crate::domain::messages::models::message::{
-    CreateMessageError, GetMessageError, QueueListError, QueueSummaryError,
+    ConsumerGroupError, CreateMessageError, DeadLetterPolicyError, GetMessageError, QueueError,
+    QueueListError, QueueSummaryError, RedriveError, RetentionPolicyError, SubscriptionError,
 };
 
 pub trait MessageService: Clone + Send + Sync + 'static {
@@ -16,15 +18,116 @@ pub trait MessageService: Clone + Send + Sync + 'static {
         queue_name: QueueName,
         req: &CreateMessageRequest,
     ) -> impl Future<Output = Result<Message, CreateMessageError>> + Send;
+    /// Adds all of `reqs` to the queue, in order, or none of them.
+    fn create_messages(
+        &self,
+        queue_name: QueueName,
+        reqs: &[CreateMessageRequest],
+    ) -> impl Future<Output = Result<Vec<Message>, CreateMessageError>> + Send;
     fn get_message(
         &self,
         gmo: GetMessageOptions,
     ) -> impl Future<Output = Result<Message, GetMessageError>> + Send;
+    /// Like `get_message`, but hands out up to `max_messages` at once.
+    fn get_messages(
+        &self,
+        gmo: GetMessageOptions,
+    ) -> impl Future<Output = Result<Vec<Message>, GetMessageError>> + Send;
+    /// Confirms or returns each receipt of `req` in turn, with the message
+    /// or the reason it failed for each.
+    fn acknowledge_messages(
+        &self,
+        req: &AcknowledgeRequest,
+    ) -> impl Future<Output = Result<Vec<Result<Message, GetMessageError>>, GetMessageError>> + Send;
     fn get_info(
         &self,
         gmo: GetMessageOptions,
     ) -> impl Future<Output = Result<QueueSummary, QueueSummaryError>> + Send;
     fn queue_list(&self) -> impl Future<Output = Result<QueueList, QueueListError>> + Send;
+    /// Creates `queue_name` with `settings`, or replaces the settings of an
+    /// existing queue, keeping its messages.  True if the queue is new.
+    fn create_queue(
+        &self,
+        queue_name: QueueName,
+        settings: &QueueSettings,
+    ) -> impl Future<Output = Result<bool, QueueError>> + Send;
+    /// Drops `queue_name` with its messages, consumer groups and
+    /// subscriptions.  Sending to it again makes a new, empty queue if the
+    /// store auto-creates queues, and is refused with `NoQueue` if not.
+    fn delete_queue(
+        &self,
+        queue_name: QueueName,
+    ) -> impl Future<Output = Result<(), QueueError>> + Send;
+    /// Removes every message from `queue_name`, reserved or not, and gives
+    /// how many there were.  Its settings and cursors are kept.
+    fn purge_queue(
+        &self,
+        queue_name: QueueName,
+    ) -> impl Future<Output = Result<usize, QueueError>> + Send;
+    fn set_dead_letter_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<DeadLetterPolicy>,
+    ) -> impl Future<Output = Result<(), DeadLetterPolicyError>> + Send;
+    /// Turns retention on for `queue_name` with `policy`, or off with `None`.
+    /// The queue must already exist, or `NoQueue` is returned.  Turning it off
+    /// removes the messages which have already been consumed.
+    fn set_retention_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<RetentionPolicy>,
+    ) -> impl Future<Output = Result<(), RetentionPolicyError>> + Send;
+    fn redrive(
+        &self,
+        queue_name: QueueName,
+        req: &RedriveRequest,
+    ) -> impl Future<Output = Result<Vec<Message>, RedriveError>> + Send;
+    /// Copies `req` into every queue subscribed to `topic`, all at once, and
+    /// gives the message created in each.  Topics are named like queues but
+    /// hold no messages of their own.
+    fn publish(
+        &self,
+        topic: QueueName,
+        req: &CreateMessageRequest,
+    ) -> impl Future<Output = Result<Vec<(QueueName, Message)>, CreateMessageError>> + Send;
+    /// Subscribes `queue_name` to `topic`.  The queue must already exist, or
+    /// `NoQueue` is returned.
+    fn subscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> impl Future<Output = Result<(), SubscriptionError>> + Send;
+    fn unsubscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> impl Future<Output = Result<(), SubscriptionError>> + Send;
+    /// The queues subscribed to `topic`, in name order.
+    fn subscriptions(
+        &self,
+        topic: QueueName,
+    ) -> impl Future<Output = Result<Vec<QueueName>, SubscriptionError>> + Send;
+    /// A group is made by the first browse through it, starting before the
+    /// first message of the queue.
+    fn consumer_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> impl Future<Output = Result<ConsumerGroup, ConsumerGroupError>> + Send;
+    /// Commits `cursor`, or the group's position if it is `None`, and moves
+    /// the position back to it.  Committing a cursor makes the group if need
+    /// be, so that a new group can start part way through a queue.
+    fn commit_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+        cursor: Option<usize>,
+    ) -> impl Future<Output = Result<ConsumerGroup, ConsumerGroupError>> + Send;
+    fn delete_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> impl Future<Output = Result<(), ConsumerGroupError>> + Send;
 }
 
 pub trait MessageRepository: Send + Sync + Clone + 'static {
@@ -33,13 +136,114 @@ pub trait MessageRepository: Send + Sync + Clone + 'static {
         queue_name: QueueName,
         req: &CreateMessageRequest,
     ) -> impl Future<Output = Result<Message, CreateMessageError>> + Send;
+    /// Adds all of `reqs` to the queue, in order, or none of them.
+    fn create_messages(
+        &self,
+        queue_name: QueueName,
+        reqs: &[CreateMessageRequest],
+    ) -> impl Future<Output = Result<Vec<Message>, CreateMessageError>> + Send;
     fn get_message(
         &self,
         gmo: GetMessageOptions,
     ) -> impl Future<Output = Result<Message, GetMessageError>> + Send;
+    /// Like `get_message`, but hands out up to `max_messages` at once.
+    fn get_messages(
+        &self,
+        gmo: GetMessageOptions,
+    ) -> impl Future<Output = Result<Vec<Message>, GetMessageError>> + Send;
+    /// Confirms or returns each receipt of `req` in turn, with the message
+    /// or the reason it failed for each.
+    fn acknowledge_messages(
+        &self,
+        req: &AcknowledgeRequest,
+    ) -> impl Future<Output = Result<Vec<Result<Message, GetMessageError>>, GetMessageError>> + Send;
     fn get_info(
         &self,
         gmo: GetMessageOptions,
     ) -> impl Future<Output = Result<QueueSummary, QueueSummaryError>> + Send;
     fn queue_list(&self) -> impl Future<Output = Result<QueueList, QueueListError>> + Send;
+    /// Creates `queue_name` with `settings`, or replaces the settings of an
+    /// existing queue, keeping its messages.  True if the queue is new.
+    fn create_queue(
+        &self,
+        queue_name: QueueName,
+        settings: &QueueSettings,
+    ) -> impl Future<Output = Result<bool, QueueError>> + Send;
+    /// Drops `queue_name` with its messages, consumer groups and
+    /// subscriptions.  Sending to it again makes a new, empty queue if the
+    /// store auto-creates queues, and is refused with `NoQueue` if not.
+    fn delete_queue(
+        &self,
+        queue_name: QueueName,
+    ) -> impl Future<Output = Result<(), QueueError>> + Send;
+    /// Removes every message from `queue_name`, reserved or not, and gives
+    /// how many there were.  Its settings and cursors are kept.
+    fn purge_queue(
+        &self,
+        queue_name: QueueName,
+    ) -> impl Future<Output = Result<usize, QueueError>> + Send;
+    fn set_dead_letter_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<DeadLetterPolicy>,
+    ) -> impl Future<Output = Result<(), DeadLetterPolicyError>> + Send;
+    /// Turns retention on for `queue_name` with `policy`, or off with `None`.
+    /// The queue must already exist, or `NoQueue` is returned.  Turning it off
+    /// removes the messages which have already been consumed.
+    fn set_retention_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<RetentionPolicy>,
+    ) -> impl Future<Output = Result<(), RetentionPolicyError>> + Send;
+    fn redrive(
+        &self,
+        queue_name: QueueName,
+        req: &RedriveRequest,
+    ) -> impl Future<Output = Result<Vec<Message>, RedriveError>> + Send;
+    /// Copies `req` into every queue subscribed to `topic`, all at once, and
+    /// gives the message created in each.  Topics are named like queues but
+    /// hold no messages of their own.
+    fn publish(
+        &self,
+        topic: QueueName,
+        req: &CreateMessageRequest,
+    ) -> impl Future<Output = Result<Vec<(QueueName, Message)>, CreateMessageError>> + Send;
+    /// Subscribes `queue_name` to `topic`.  The queue must already exist, or
+    /// `NoQueue` is returned.
+    fn subscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> impl Future<Output = Result<(), SubscriptionError>> + Send;
+    fn unsubscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> impl Future<Output = Result<(), SubscriptionError>> + Send;
+    /// The queues subscribed to `topic`, in name order.
+    fn subscriptions(
+        &self,
+        topic: QueueName,
+    ) -> impl Future<Output = Result<Vec<QueueName>, SubscriptionError>> + Send;
+    /// A group is made by the first browse through it, starting before the
+    /// first message of the queue.
+    fn consumer_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> impl Future<Output = Result<ConsumerGroup, ConsumerGroupError>> + Send;
+    /// Commits `cursor`, or the group's position if it is `None`, and moves
+    /// the position back to it.  Committing a cursor makes the group if need
+    /// be, so that a new group can start part way through a queue.
+    fn commit_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+        cursor: Option<usize>,
+    ) -> impl Future<Output = Result<ConsumerGroup, ConsumerGroupError>> + Send;
+    fn delete_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> impl Future<Output = Result<(), ConsumerGroupError>> + Send;
 }
@@ -1,11 +1,15 @@
+use crate::domain::messages::models::message::DEFAULT_DEDUP_WINDOW;
 use crate::domain::messages::models::message::{
-    CreateMessageError, GetMessageError, QueueListError,
+    AcknowledgeRequest, ConsumerGroup, CreateMessageRequest, DeadLetterPolicy, GetMessageOptions,
+    Message, QueueList, QueueName, QueueSettings, QueueSummary, QueueSummaryError, RedriveRequest,
+    RetentionPolicy,
 };
 use crate::domain::messages::models::message::{
-    CreateMessageRequest, GetMessageOptions, Message, QueueList, QueueName, QueueSummary,
-    QueueSummaryError,
+    ConsumerGroupError, CreateMessageError, DeadLetterPolicyError, GetMessageError, QueueError,
+    QueueListError, RedriveError, RetentionPolicyError, SubscriptionError,
 };
 use crate::domain::messages::ports::{MessageRepository, MessageService};
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct Service<R>
@@ -13,6 +17,7 @@ where
     R: MessageRepository,
 {
     repo: R,
+    dedup_window: Duration,
 }
 
 impl<R> Service<R>
@@ -20,7 +25,23 @@ where
     R: MessageRepository,
 {
     pub fn new(repo: R) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            dedup_window: DEFAULT_DEDUP_WINDOW,
+        }
+    }
+
+    /// Sets how long a deduplication key given when creating a message is
+    /// remembered.
+    pub fn with_dedup_window(self, dedup_window: Duration) -> Self {
+        Self {
+            dedup_window,
+            ..self
+        }
+    }
+
+    fn prepare(&self, req: &CreateMessageRequest) -> CreateMessageRequest {
+        req.clone().with_dedup_window(self.dedup_window)
     }
 }
 
@@ -33,12 +54,32 @@ where
         queue_name: QueueName,
         req: &CreateMessageRequest,
     ) -> Result<Message, CreateMessageError> {
-        self.repo.create_message(queue_name, req).await
+        self.repo
+            .create_message(queue_name, &self.prepare(req))
+            .await
+    }
+
+    async fn create_messages(
+        &self,
+        queue_name: QueueName,
+        reqs: &[CreateMessageRequest],
+    ) -> Result<Vec<Message>, CreateMessageError> {
+        let reqs = reqs.iter().map(|req| self.prepare(req)).collect::<Vec<_>>();
+        self.repo.create_messages(queue_name, &reqs).await
     }
 
     async fn get_message(&self, gmo: GetMessageOptions) -> Result<Message, GetMessageError> {
         self.repo.get_message(gmo).await
     }
+    async fn get_messages(&self, gmo: GetMessageOptions) -> Result<Vec<Message>, GetMessageError> {
+        self.repo.get_messages(gmo).await
+    }
+    async fn acknowledge_messages(
+        &self,
+        req: &AcknowledgeRequest,
+    ) -> Result<Vec<Result<Message, GetMessageError>>, GetMessageError> {
+        self.repo.acknowledge_messages(req).await
+    }
     async fn get_info(&self, gmo: GetMessageOptions) -> Result<QueueSummary, QueueSummaryError> {
         self.repo.get_info(gmo).await
     }
@@ -46,4 +87,145 @@ where
     async fn queue_list(&self) -> Result<QueueList, QueueListError> {
         self.repo.queue_list().await
     }
+
+    async fn create_queue(
+        &self,
+        queue_name: QueueName,
+        settings: &QueueSettings,
+    ) -> Result<bool, QueueError> {
+        check_dead_letter_policy(&queue_name, settings.dead_letter())
+            .map_err(|e| QueueError::InvalidSettings(e.to_string()))?;
+        check_retention_policy(settings.retention())
+            .map_err(|e| QueueError::InvalidSettings(e.to_string()))?;
+        self.repo.create_queue(queue_name, settings).await
+    }
+
+    async fn delete_queue(&self, queue_name: QueueName) -> Result<(), QueueError> {
+        self.repo.delete_queue(queue_name).await
+    }
+
+    async fn purge_queue(&self, queue_name: QueueName) -> Result<usize, QueueError> {
+        self.repo.purge_queue(queue_name).await
+    }
+
+    async fn set_dead_letter_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<DeadLetterPolicy>,
+    ) -> Result<(), DeadLetterPolicyError> {
+        check_dead_letter_policy(&queue_name, policy.as_ref())?;
+        self.repo.set_dead_letter_policy(queue_name, policy).await
+    }
+
+    async fn set_retention_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<RetentionPolicy>,
+    ) -> Result<(), RetentionPolicyError> {
+        check_retention_policy(policy.as_ref())?;
+        self.repo.set_retention_policy(queue_name, policy).await
+    }
+
+    async fn redrive(
+        &self,
+        queue_name: QueueName,
+        req: &RedriveRequest,
+    ) -> Result<Vec<Message>, RedriveError> {
+        if req.target() == &queue_name {
+            return Err(RedriveError::InvalidRequest(format!(
+                "cannot redrive {} into itself",
+                queue_name
+            )));
+        }
+        self.repo.redrive(queue_name, req).await
+    }
+
+    async fn publish(
+        &self,
+        topic: QueueName,
+        req: &CreateMessageRequest,
+    ) -> Result<Vec<(QueueName, Message)>, CreateMessageError> {
+        self.repo.publish(topic, &self.prepare(req)).await
+    }
+
+    async fn subscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> Result<(), SubscriptionError> {
+        self.repo.subscribe(topic, queue_name).await
+    }
+
+    async fn unsubscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> Result<(), SubscriptionError> {
+        self.repo.unsubscribe(topic, queue_name).await
+    }
+
+    async fn subscriptions(&self, topic: QueueName) -> Result<Vec<QueueName>, SubscriptionError> {
+        self.repo.subscriptions(topic).await
+    }
+
+    async fn consumer_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> Result<ConsumerGroup, ConsumerGroupError> {
+        self.repo.consumer_group(queue_name, group).await
+    }
+
+    async fn commit_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+        cursor: Option<usize>,
+    ) -> Result<ConsumerGroup, ConsumerGroupError> {
+        self.repo.commit_group(queue_name, group, cursor).await
+    }
+
+    async fn delete_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> Result<(), ConsumerGroupError> {
+        self.repo.delete_group(queue_name, group).await
+    }
+}
+
+fn check_dead_letter_policy(
+    queue_name: &QueueName,
+    policy: Option<&DeadLetterPolicy>,
+) -> Result<(), DeadLetterPolicyError> {
+    if let Some(policy) = policy {
+        if policy.queue_name() == queue_name {
+            return Err(DeadLetterPolicyError::InvalidPolicy(format!(
+                "{} cannot be its own dead letter queue",
+                queue_name
+            )));
+        }
+        if policy.max_deliveries() == 0 {
+            return Err(DeadLetterPolicyError::InvalidPolicy(
+                "max_deliveries must be at least 1".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_retention_policy(policy: Option<&RetentionPolicy>) -> Result<(), RetentionPolicyError> {
+    if let Some(policy) = policy {
+        if policy.max_messages() == Some(0) {
+            return Err(RetentionPolicyError::InvalidPolicy(
+                "max_messages must be at least 1".to_string(),
+            ));
+        }
+        if policy.max_age() == Some(Duration::ZERO) {
+            return Err(RetentionPolicyError::InvalidPolicy(
+                "max_age_seconds must be at least 1".to_string(),
+            ));
+        }
+    }
+    Ok(())
 }
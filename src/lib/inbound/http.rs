@@ -1,18 +1,27 @@
 use std::sync::Arc;
 
 use anyhow::Context;
-use axum::routing::{get, post};
+use axum::routing::{get, post, put};
 use axum::Router;
 use tokio::net;
+use tokio::sync::watch;
 
 use crate::domain::messages::ports::MessageService;
-use crate::inbound::http::handlers::create_message::create_message;
-use crate::inbound::http::handlers::get_message::{get_message, get_message_mid};
+use crate::inbound::http::handlers::acknowledge::acknowledge;
+use crate::inbound::http::handlers::consume::consume;
+use crate::inbound::http::handlers::consumer_group::{commit_group, consumer_group, delete_group};
+use crate::inbound::http::handlers::create_message::{create_message, create_messages};
+use crate::inbound::http::handlers::dead_letter::{remove_dead_letter, set_dead_letter};
+use crate::inbound::http::handlers::get_message::{get_message, get_message_mid, get_raw_message};
+use crate::inbound::http::handlers::queue::{create_queue, delete_queue, purge_queue};
 use crate::inbound::http::handlers::queue_list::queue_list;
+use crate::inbound::http::handlers::redrive::redrive;
+use crate::inbound::http::handlers::retention::{remove_retention, set_retention};
+use crate::inbound::http::handlers::stream::stream_messages;
+use crate::inbound::http::handlers::topic::{publish, subscribe, subscriptions, unsubscribe};
 
 mod errors;
 mod handlers;
-mod responses;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HttpServerConfig<'a> {
@@ -22,11 +31,15 @@ pub struct HttpServerConfig<'a> {
 #[derive(Debug, Clone)]
 struct AppState<MS: MessageService> {
     message_service: Arc<MS>,
+    /// Becomes `true` once the server starts shutting down, so that
+    /// long-lived responses such as event streams can finish.
+    shutdown: watch::Receiver<bool>,
 }
 
 pub struct HttpServer {
     router: axum::Router,
     listener: net::TcpListener,
+    shutdown: watch::Sender<bool>,
 }
 
 impl HttpServer {
@@ -41,8 +54,10 @@ impl HttpServer {
             },
         );
 
+        let (shutdown, shutdown_rx) = watch::channel(false);
         let state = AppState {
             message_service: Arc::new(service),
+            shutdown: shutdown_rx,
         };
 
         let router = axum::Router::new()
@@ -53,22 +68,87 @@ impl HttpServer {
             .await
             .with_context(|| format!("failed to listen on {}", config.port))?;
 
-        Ok(Self { router, listener })
+        Ok(Self {
+            router,
+            listener,
+            shutdown,
+        })
     }
 
     pub async fn run(self) -> anyhow::Result<()> {
         tracing::debug!("listening on {}", self.listener.local_addr().unwrap());
+        let shutdown = self.shutdown;
         axum::serve(self.listener, self.router)
+            .with_graceful_shutdown(async move {
+                shutdown_signal().await;
+                shutdown.send_replace(true);
+            })
             .await
             .context("received error from running server")?;
         Ok(())
     }
 }
 
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler")
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("shutting down");
+}
+
 fn api_routes<MS: MessageService>() -> Router<AppState<MS>> {
     Router::new()
         .route("/", get(queue_list::<MS>))
         .route("/:queue_name", post(create_message::<MS>))
         .route("/:queue_name", get(get_message::<MS>))
+        .route(
+            "/:queue_name",
+            put(create_queue::<MS>).delete(delete_queue::<MS>),
+        )
+        .route(
+            "/:queue_name/dead_letter",
+            put(set_dead_letter::<MS>).delete(remove_dead_letter::<MS>),
+        )
+        .route("/:queue_name/acknowledge", post(acknowledge::<MS>))
+        .route("/:queue_name/batch", post(create_messages::<MS>))
+        .route("/:queue_name/consume", get(consume::<MS>))
+        .route(
+            "/:queue_name/groups/:group",
+            get(consumer_group::<MS>).delete(delete_group::<MS>),
+        )
+        .route(
+            "/:queue_name/groups/:group/commit",
+            post(commit_group::<MS>),
+        )
+        .route("/:queue_name/publish", post(publish::<MS>))
+        .route("/:queue_name/purge", post(purge_queue::<MS>))
+        .route("/:queue_name/raw", get(get_raw_message::<MS>))
+        .route("/:queue_name/redrive", post(redrive::<MS>))
+        .route(
+            "/:queue_name/retention",
+            put(set_retention::<MS>).delete(remove_retention::<MS>),
+        )
+        .route("/:queue_name/stream", get(stream_messages::<MS>))
+        .route("/:queue_name/subscriptions", get(subscriptions::<MS>))
+        .route(
+            "/:queue_name/subscriptions/:subscriber",
+            put(subscribe::<MS>).delete(unsubscribe::<MS>),
+        )
         .route("/:queue_name/:uid", get(get_message_mid::<MS>))
 }
@@ -36,6 +36,7 @@ where
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ApiError {
     NotFound(String),
+    Conflict(String),
     InternalServerError(String),
     UnprocessableEntity(String),
 }
@@ -85,6 +86,11 @@ impl IntoResponse for ApiError {
                 )
                     .into_response()
             }
+            Conflict(message) => (
+                StatusCode::CONFLICT,
+                Json(ApiResponseBody::new_error(StatusCode::CONFLICT, message)),
+            )
+                .into_response(),
             InternalServerError(e) => {
                 tracing::error!("{}", e);
                 (
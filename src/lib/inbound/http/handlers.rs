@@ -1,3 +1,13 @@
+pub mod acknowledge;
+pub mod consume;
+pub mod consumer_group;
 pub mod create_message;
+pub mod dead_letter;
 pub mod get_message;
+mod params;
+pub mod queue;
 pub mod queue_list;
+pub mod redrive;
+pub mod retention;
+pub mod stream;
+pub mod topic;
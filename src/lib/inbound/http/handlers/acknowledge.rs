@@ -0,0 +1,171 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::messages::models::message::{
+    AcknowledgeRequest, GetMessageAction, GetMessageError, Message,
+};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::{ApiError, ApiSuccess};
+use crate::inbound::http::AppState;
+
+/// `action` is `confirm` or `return`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AcknowledgeRequestBody {
+    action: String,
+    receipts: Vec<String>,
+}
+
+/// Confirms or returns every receipt in the body which parses, and reports
+/// for each receipt in turn either the id of its message or why it failed.
+pub async fn acknowledge<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Json(body): Json<AcknowledgeRequestBody>,
+) -> Result<ApiSuccess<AcknowledgeResponseData>, ApiError> {
+    let queue_name = queue_name
+        .try_into()
+        .map_err(|_| GetMessageError::InvalidParameter("queue_name".to_string()))?;
+    let action = GetMessageAction::try_from(body.action.as_str())?;
+    let parsed = body
+        .receipts
+        .iter()
+        .map(|s| Uuid::try_parse(s).map_err(|_| format!("{} cannot be parsed to a Uuid", s)))
+        .collect::<Vec<_>>();
+    let receipts = parsed.iter().filter_map(|p| p.clone().ok()).collect();
+    let req = AcknowledgeRequest::new(queue_name, action, receipts)?;
+    let mut results = state
+        .message_service
+        .acknowledge_messages(&req)
+        .await?
+        .into_iter();
+    let items = body
+        .receipts
+        .into_iter()
+        .zip(parsed)
+        .map(|(receipt, p)| {
+            let result = p.and_then(|_| {
+                results.next().unwrap().map_err(|e| match e {
+                    GetMessageError::NoMessage(_) => {
+                        format!("receipt {} does not hold a reservation", receipt)
+                    }
+                    e => e.to_string(),
+                })
+            });
+            AcknowledgeItemResponseData::new(receipt, result)
+        })
+        .collect();
+    Ok(ApiSuccess::new(
+        StatusCode::OK,
+        AcknowledgeResponseData { items },
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AcknowledgeResponseData {
+    items: Vec<AcknowledgeItemResponseData>,
+}
+
+/// Exactly one of `id` and `error` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AcknowledgeItemResponseData {
+    receipt: String,
+    id: Option<String>,
+    error: Option<String>,
+}
+
+impl AcknowledgeItemResponseData {
+    fn new(receipt: String, result: Result<Message, String>) -> Self {
+        match result {
+            Ok(message) => Self {
+                receipt,
+                id: Some(message.mid().to_string()),
+                error: None,
+            },
+            Err(error) => Self {
+                receipt,
+                id: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::domain::messages::models::message::{CreateMessageRequest, GetMessageOptions};
+    use crate::domain::messages::service::Service;
+    use crate::outbound::memory::Memory;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_acknowledge() {
+        let service = Service::new(Memory::new().await.unwrap());
+        for content in ["msg1", "msg2"] {
+            let req = CreateMessageRequest::new(content.to_string(), None, None);
+            service
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+        }
+        let gmo: GetMessageOptions = [
+            ("queue_name", "queue1"),
+            ("action", "reserve"),
+            ("reservation_seconds", "10"),
+            ("max_messages", "2"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<_, _>>()
+        .try_into()
+        .unwrap();
+        let reserved = service.get_messages(gmo).await.unwrap();
+        let stale = Uuid::new_v4().to_string();
+        let receipts = vec![
+            reserved[0].receipt().unwrap().to_string(),
+            "xxx".to_string(),
+            stale.clone(),
+            reserved[1].receipt().unwrap().to_string(),
+        ];
+
+        let call = |action: &str| {
+            let state = State(AppState {
+                message_service: Arc::new(service.clone()),
+                shutdown: watch::channel(false).1,
+            });
+            let body = AcknowledgeRequestBody {
+                action: action.to_string(),
+                receipts: receipts.clone(),
+            };
+            acknowledge(state, Path("queue1".to_string()), Json(body))
+        };
+        let expected = ApiError::UnprocessableEntity("Bad parameter action".to_string());
+        assert_eq!(call("reserve").await, Err(expected));
+
+        let expected = ApiSuccess::new(
+            StatusCode::OK,
+            AcknowledgeResponseData {
+                items: vec![
+                    AcknowledgeItemResponseData::new(receipts[0].clone(), Ok(reserved[0].clone())),
+                    AcknowledgeItemResponseData::new(
+                        "xxx".to_string(),
+                        Err("xxx cannot be parsed to a Uuid".to_string()),
+                    ),
+                    AcknowledgeItemResponseData::new(
+                        stale.clone(),
+                        Err(format!("receipt {} does not hold a reservation", stale)),
+                    ),
+                    AcknowledgeItemResponseData::new(receipts[3].clone(), Ok(reserved[1].clone())),
+                ],
+            },
+        );
+        assert_eq!(call("confirm").await, Ok(expected));
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::extract::ws::{self, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::response::Response;
+use futures_util::future::select_all;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::domain::messages::models::message::{GetMessageError, GetMessageOptions, QueueName};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::ApiError;
+use crate::inbound::http::handlers::get_message::GetMessageResponseData;
+use crate::inbound::http::handlers::params::parse_queue_name;
+use crate::inbound::http::AppState;
+
+const DEFAULT_PREFETCH: usize = 10;
+const DEFAULT_RESERVATION_SECONDS: u64 = 30;
+
+/// How long each reserve waits for a message before the queues are looked at
+/// again.  Kept short because the reservation is timed from the request.
+const POLL_WAIT: Duration = Duration::from_secs(1);
+
+/// A frame sent by the client.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum ClientFrame {
+    Subscribe { queue: String },
+    Confirm { id: Uuid },
+    Return { id: Uuid },
+}
+
+/// A frame sent to the client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerFrame {
+    Message {
+        queue: String,
+        message: Box<GetMessageResponseData>,
+    },
+    Subscribed {
+        queue: String,
+    },
+    Confirmed {
+        id: Uuid,
+    },
+    Returned {
+        id: Uuid,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl ServerFrame {
+    fn error(message: impl ToString) -> Self {
+        Self::Error {
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A reservation held by a socket.
+#[derive(Debug, Clone)]
+struct Held {
+    queue_name: QueueName,
+    receipt: Uuid,
+    until: Instant,
+}
+
+/// The queues a socket consumes from and the reservations it holds.  Messages
+/// are reserved until `prefetch` are outstanding; each confirm or return
+/// frees a slot, as does a reservation which lapses.
+struct Consumer<MS: MessageService> {
+    service: MS,
+    queues: Vec<QueueName>,
+    held: HashMap<Uuid, Held>,
+    prefetch: usize,
+    reservation_seconds: u64,
+}
+
+impl<MS: MessageService> Consumer<MS> {
+    fn new(service: MS, prefetch: usize, reservation_seconds: u64) -> Self {
+        Self {
+            service,
+            queues: vec![],
+            held: HashMap::new(),
+            prefetch,
+            reservation_seconds,
+        }
+    }
+
+    fn subscribe(&mut self, queue_name: QueueName) {
+        if !self.queues.contains(&queue_name) {
+            self.queues.push(queue_name)
+        }
+    }
+
+    fn options(
+        &self,
+        queue_name: &QueueName,
+        action: &str,
+        receipt: Option<&Uuid>,
+    ) -> Result<GetMessageOptions, GetMessageError> {
+        let mut params = HashMap::new();
+        params.insert("queue_name".to_string(), queue_name.to_string());
+        params.insert("action".to_string(), action.to_string());
+        if let Some(receipt) = receipt {
+            params.insert("receipt".to_string(), receipt.to_string());
+        } else {
+            params.insert(
+                "reservation_seconds".to_string(),
+                self.reservation_seconds.to_string(),
+            );
+        }
+        params.try_into()
+    }
+
+    /// True if the socket has room for another message.
+    fn wants_more(&mut self) -> bool {
+        let now = Instant::now();
+        self.held.retain(|_, held| held.until > now);
+        !self.queues.is_empty() && self.held.len() < self.prefetch
+    }
+
+    /// Reserves the next message from any of the subscribed queues, waiting
+    /// until one arrives.
+    async fn reserve(&mut self) -> Result<ServerFrame, GetMessageError> {
+        loop {
+            let attempts = self
+                .queues
+                .iter()
+                .map(|queue_name| {
+                    let gmo = self
+                        .options(queue_name, "reserve", None)
+                        .map(|gmo| gmo.with_wait(Some(POLL_WAIT)));
+                    let service = &self.service;
+                    Box::pin(async move { service.get_message(gmo?).await })
+                })
+                .collect::<Vec<_>>();
+            let until = Instant::now() + Duration::from_secs(self.reservation_seconds);
+            let (result, idx, _) = select_all(attempts).await;
+            match result {
+                Ok(message) => {
+                    let Some(receipt) = message.receipt().copied() else {
+                        // The reservation lapsed on the way back to us.
+                        continue;
+                    };
+                    let queue_name = self.queues[idx].clone();
+                    // Take turns so that one busy queue does not starve the rest.
+                    self.queues.rotate_left(idx + 1);
+                    self.held.insert(
+                        *message.mid(),
+                        Held {
+                            queue_name: queue_name.clone(),
+                            receipt,
+                            until,
+                        },
+                    );
+                    return Ok(ServerFrame::Message {
+                        queue: queue_name.to_string(),
+                        message: Box::new((&message).into()),
+                    });
+                }
+                Err(GetMessageError::NoMessage(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Confirms or returns a message held by this socket.
+    async fn acknowledge(&mut self, action: &str, mid: Uuid) -> Result<(), GetMessageError> {
+        let held = self.held.get(&mid).ok_or_else(|| {
+            GetMessageError::NoMessage(format!("{} is not reserved by this consumer", mid))
+        })?;
+        let gmo = self.options(&held.queue_name, action, Some(&held.receipt))?;
+        self.service.get_message(gmo).await?;
+        self.held.remove(&mid);
+        Ok(())
+    }
+
+    async fn handle(&mut self, text: &str) -> ServerFrame {
+        let frame = match serde_json::from_str(text) {
+            Ok(frame) => frame,
+            Err(e) => return ServerFrame::error(e),
+        };
+        match frame {
+            ClientFrame::Subscribe { queue } => match QueueName::try_from(queue) {
+                Ok(queue_name) => {
+                    let queue = queue_name.to_string();
+                    self.subscribe(queue_name);
+                    ServerFrame::Subscribed { queue }
+                }
+                Err(e) => ServerFrame::error(e),
+            },
+            ClientFrame::Confirm { id } => match self.acknowledge("confirm", id).await {
+                Ok(()) => ServerFrame::Confirmed { id },
+                Err(e) => ServerFrame::error(e),
+            },
+            ClientFrame::Return { id } => match self.acknowledge("return", id).await {
+                Ok(()) => ServerFrame::Returned { id },
+                Err(e) => ServerFrame::error(e),
+            },
+        }
+    }
+
+    /// Returns every reservation still held, so that the messages of a
+    /// consumer which goes away are delivered to someone else.
+    async fn release(mut self) {
+        self.wants_more();
+        let mids = self.held.keys().copied().collect::<Vec<_>>();
+        for mid in mids {
+            if let Err(e) = self.acknowledge("return", mid).await {
+                tracing::warn!("failed to return {} on disconnect: {}", mid, e);
+            }
+        }
+    }
+}
+
+/// Upgrades to a WebSocket which is sent messages reserved from `queue_name`
+/// and any further queues the client subscribes to.  `prefetch` limits the
+/// reservations outstanding at once and `reservation_seconds` sets how long
+/// each lasts.  Reservations still held when the socket closes are returned.
+pub async fn consume<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    let prefetch = parse_param(&params, "prefetch", DEFAULT_PREFETCH as u64)? as usize;
+    let reservation_seconds =
+        parse_param(&params, "reservation_seconds", DEFAULT_RESERVATION_SECONDS)?;
+    let mut consumer = Consumer::new(
+        state.message_service.as_ref().clone(),
+        prefetch,
+        reservation_seconds,
+    );
+    consumer.subscribe(queue_name);
+    Ok(upgrade.on_upgrade(move |socket| run(socket, consumer)))
+}
+
+fn parse_param(
+    params: &HashMap<String, String>,
+    name: &str,
+    default: u64,
+) -> Result<u64, ApiError> {
+    match params.get(name) {
+        None => Ok(default),
+        Some(s) => s
+            .parse()
+            .map_err(|_| GetMessageError::InvalidParameter(name.to_string()).into()),
+    }
+}
+
+async fn run<MS: MessageService>(mut socket: WebSocket, mut consumer: Consumer<MS>) {
+    loop {
+        let wants_more = consumer.wants_more();
+        let frame = tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(ws::Message::Text(text))) => consumer.handle(&text).await,
+                Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            delivery = consumer.reserve(), if wants_more => {
+                delivery.unwrap_or_else(ServerFrame::error)
+            },
+        };
+        let text = match serde_json::to_string(&frame) {
+            Ok(text) => text,
+            Err(e) => {
+                tracing::error!("failed to encode {:?}: {}", frame, e);
+                break;
+            }
+        };
+        if socket.send(ws::Message::Text(text)).await.is_err() {
+            break;
+        }
+    }
+    consumer.release().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::messages::models::message::{CreateMessageRequest, Message};
+    use crate::domain::messages::service::Service;
+    use crate::outbound::memory::Memory;
+
+    async fn put(service: &Service<Memory>, queue: &str, content: &str) -> Message {
+        let req = CreateMessageRequest::new(content.to_string(), None, None);
+        service
+            .create_message(queue.to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap()
+    }
+
+    async fn depth(service: &Service<Memory>, queue: &str) -> usize {
+        let mut params = HashMap::new();
+        params.insert("queue_name".to_string(), queue.to_string());
+        params.insert("action".to_string(), "query".to_string());
+        let gmo: GetMessageOptions = params.try_into().unwrap();
+        service.get_info(gmo).await.unwrap().depth()
+    }
+
+    fn delivered(frame: ServerFrame) -> (String, Uuid) {
+        match frame {
+            ServerFrame::Message { queue, message } => {
+                let message = serde_json::to_value(message).unwrap();
+                (
+                    queue,
+                    Uuid::try_parse(message["mid"].as_str().unwrap()).unwrap(),
+                )
+            }
+            frame => panic!("expected a message, got {:?}", frame),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prefetch_and_acknowledge() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let msg1 = put(&service, "queue1", "msg1").await;
+        let msg2 = put(&service, "queue1", "msg2").await;
+        let _msg3 = put(&service, "queue1", "msg3").await;
+        let mut consumer = Consumer::new(service.clone(), 2, 30);
+        assert!(!consumer.wants_more());
+        assert_eq!(
+            consumer
+                .handle(r#"{"op":"subscribe","queue":"queue1"}"#)
+                .await,
+            ServerFrame::Subscribed {
+                queue: "queue1".to_string()
+            }
+        );
+
+        assert!(consumer.wants_more());
+        assert_eq!(delivered(consumer.reserve().await.unwrap()).1, *msg1.mid());
+        assert_eq!(delivered(consumer.reserve().await.unwrap()).1, *msg2.mid());
+        assert!(!consumer.wants_more());
+
+        let confirm = format!(r#"{{"op":"confirm","id":"{}"}}"#, msg1.mid());
+        assert_eq!(
+            consumer.handle(&confirm).await,
+            ServerFrame::Confirmed { id: *msg1.mid() }
+        );
+        assert!(matches!(
+            consumer.handle(&confirm).await,
+            ServerFrame::Error { .. }
+        ));
+        assert!(consumer.wants_more());
+        assert_eq!(depth(&service, "queue1").await, 2);
+
+        let ret = format!(r#"{{"op":"return","id":"{}"}}"#, msg2.mid());
+        assert_eq!(
+            consumer.handle(&ret).await,
+            ServerFrame::Returned { id: *msg2.mid() }
+        );
+        assert_eq!(delivered(consumer.reserve().await.unwrap()).1, *msg2.mid());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_multiple_queues() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let mut consumer = Consumer::new(service.clone(), 10, 30);
+        consumer.subscribe("queue1".to_string().try_into().unwrap());
+        consumer.subscribe("queue2".to_string().try_into().unwrap());
+        let msg1 = put(&service, "queue2", "msg1").await;
+        let (queue, mid) = delivered(consumer.reserve().await.unwrap());
+        assert_eq!((queue.as_str(), mid), ("queue2", *msg1.mid()));
+
+        let producer = service.clone();
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            put(&producer, "queue1", "msg2").await
+        });
+        let (queue, mid) = delivered(consumer.reserve().await.unwrap());
+        assert_eq!(queue, "queue1");
+        assert_eq!(mid, *handle.await.unwrap().mid());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_release_returns_reservations() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let msg1 = put(&service, "queue1", "msg1").await;
+        let mut consumer = Consumer::new(service.clone(), 10, 30);
+        consumer.subscribe("queue1".to_string().try_into().unwrap());
+        assert_eq!(delivered(consumer.reserve().await.unwrap()).1, *msg1.mid());
+        consumer.release().await;
+
+        let mut consumer = Consumer::new(service.clone(), 10, 30);
+        consumer.subscribe("queue1".to_string().try_into().unwrap());
+        assert_eq!(delivered(consumer.reserve().await.unwrap()).1, *msg1.mid());
+    }
+}
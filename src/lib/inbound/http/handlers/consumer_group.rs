@@ -0,0 +1,166 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::messages::models::message::{ConsumerGroup, ConsumerGroupError, QueueName};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::{ApiError, ApiSuccess};
+use crate::inbound::http::handlers::params::{parse_number, parse_queue_name};
+use crate::inbound::http::AppState;
+
+impl From<ConsumerGroupError> for ApiError {
+    fn from(e: ConsumerGroupError) -> Self {
+        match e {
+            ConsumerGroupError::NoGroup(e) => Self::NotFound(e),
+            ConsumerGroupError::InvalidCursor(e) => Self::UnprocessableEntity(e),
+            ConsumerGroupError::Unknown(e) => Self::InternalServerError(e.to_string()),
+        }
+    }
+}
+
+/// Without a `cursor` the group commits its position.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct CommitRequestBody {
+    cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConsumerGroupResponseData {
+    queue_name: String,
+    group: String,
+    position: usize,
+    committed: usize,
+}
+
+impl From<&ConsumerGroup> for ConsumerGroupResponseData {
+    fn from(group: &ConsumerGroup) -> Self {
+        Self {
+            queue_name: group.queue_name().to_string(),
+            group: group.group().to_string(),
+            position: group.position(),
+            committed: group.committed(),
+        }
+    }
+}
+
+fn parse_names(queue_name: String, group: String) -> Result<(QueueName, QueueName), ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    let group = group
+        .try_into()
+        .map_err(|_| ApiError::UnprocessableEntity("group name cannot be empty".to_string()))?;
+    Ok((queue_name, group))
+}
+
+pub async fn consumer_group<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path((queue_name, group)): Path<(String, String)>,
+) -> Result<ApiSuccess<ConsumerGroupResponseData>, ApiError> {
+    let (queue_name, group) = parse_names(queue_name, group)?;
+    state
+        .message_service
+        .consumer_group(queue_name, group)
+        .await
+        .map_err(ApiError::from)
+        .map(|ref group| ApiSuccess::new(StatusCode::OK, group.into()))
+}
+
+/// Records how far the group has handled the queue.  Messages are read
+/// through the group with `action=browse&group=...`.
+pub async fn commit_group<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path((queue_name, group)): Path<(String, String)>,
+    Json(body): Json<CommitRequestBody>,
+) -> Result<ApiSuccess<ConsumerGroupResponseData>, ApiError> {
+    let (queue_name, group) = parse_names(queue_name, group)?;
+    let cursor = parse_number(body.cursor)?.map(|n| n as usize);
+    state
+        .message_service
+        .commit_group(queue_name, group, cursor)
+        .await
+        .map_err(ApiError::from)
+        .map(|ref group| ApiSuccess::new(StatusCode::OK, group.into()))
+}
+
+pub async fn delete_group<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path((queue_name, group)): Path<(String, String)>,
+) -> Result<ApiSuccess<()>, ApiError> {
+    let (queue_name, group) = parse_names(queue_name, group)?;
+    state
+        .message_service
+        .delete_group(queue_name, group)
+        .await
+        .map_err(ApiError::from)
+        .map(|_| ApiSuccess::new(StatusCode::OK, ()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::domain::messages::models::message::{CreateMessageRequest, GetMessageOptions};
+    use crate::domain::messages::service::Service;
+    use crate::outbound::memory::Memory;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_commit_group() {
+        let service = Service::new(Memory::new().await.unwrap());
+        for content in ["msg1", "msg2", "msg3"] {
+            let req = CreateMessageRequest::new(content.to_string(), None, None);
+            service
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+        }
+        let state = || {
+            State(AppState {
+                message_service: Arc::new(service.clone()),
+                shutdown: watch::channel(false).1,
+            })
+        };
+        let path = || Path(("queue1".to_string(), "group1".to_string()));
+        let expected = ApiError::NotFound("no group group1 on queue1".to_string());
+        assert_eq!(consumer_group(state(), path()).await, Err(expected));
+
+        let gmo: GetMessageOptions = [
+            ("queue_name", "queue1"),
+            ("action", "browse"),
+            ("group", "group1"),
+            ("max_messages", "2"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<_, _>>()
+        .try_into()
+        .unwrap();
+        assert_eq!(service.get_messages(gmo).await.unwrap().len(), 2);
+        let data = |position, committed| ConsumerGroupResponseData {
+            queue_name: "queue1".to_string(),
+            group: "group1".to_string(),
+            position,
+            committed,
+        };
+        let expected = ApiSuccess::new(StatusCode::OK, data(2, 0));
+        assert_eq!(consumer_group(state(), path()).await, Ok(expected));
+
+        let expected = ApiSuccess::new(StatusCode::OK, data(2, 2));
+        let actual = commit_group(state(), path(), Json(CommitRequestBody::default())).await;
+        assert_eq!(actual, Ok(expected));
+        let body = CommitRequestBody {
+            cursor: Some("4".to_string()),
+        };
+        let expected = ApiError::UnprocessableEntity("4 is past the end of queue1".to_string());
+        assert_eq!(
+            commit_group(state(), path(), Json(body)).await,
+            Err(expected)
+        );
+
+        assert!(delete_group(state(), path()).await.is_ok());
+        assert!(delete_group(state(), path()).await.is_err());
+    }
+}
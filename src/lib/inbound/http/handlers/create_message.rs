@@ -1,8 +1,11 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::Json;
+use base64::prelude::{Engine, BASE64_STANDARD};
 #[cfg(test)]
 use mock_instant::global::Instant;
 use serde::{Deserialize, Serialize};
@@ -12,14 +15,17 @@ use thiserror::Error;
 #[cfg(not(test))]
 use std::time::Instant;
 
+use crate::domain::messages::models::message::wall_clock;
 use crate::domain::messages::models::message::{
-    CreateMessageError, CreateMessageRequest, Message, QueueNameEmptyError,
+    Content, CreateMessageError, CreateMessageRequest, Message, QueueNameEmptyError,
 };
 use crate::inbound::http::errors::{ApiError, ApiSuccess};
 use crate::inbound::http::AppState;
 
 use crate::domain::messages::ports::MessageService;
 
+const OCTET_STREAM: &str = "application/octet-stream";
+
 impl From<CreateMessageError> for ApiError {
     fn from(e: CreateMessageError) -> Self {
         match e {
@@ -28,20 +34,56 @@ impl From<CreateMessageError> for ApiError {
                 Self::InternalServerError("Internal server error".to_string())
             }
             CreateMessageError::BadQueue(s) => Self::UnprocessableEntity(s.clone()),
+            CreateMessageError::NoQueue(s) => Self::NotFound(s),
+            CreateMessageError::QueueFull(s) => Self::Conflict(s),
+            CreateMessageError::TooLarge(s) => Self::UnprocessableEntity(s),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+/// Text is sent as `content`, and binary data as `content_base64` with an
+/// optional `content_type`; exactly one of the two must be given.
+/// `delay_seconds` or an RFC 3339 `deliver_at` holds the message back until
+/// that time; at most one of them may be given.  Retrying with the same
+/// `dedup_key` returns the original message rather than creating another.
+/// `headers` is an object of string values stored alongside the content.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
 pub struct CreateMessageRequestBody {
     cid: Option<String>,
-    content: String,
+    content: Option<String>,
+    content_base64: Option<String>,
+    content_type: Option<String>,
+    headers: Option<BTreeMap<String, String>>,
     expiry_seconds: Option<String>,
+    priority: Option<String>,
+    delay_seconds: Option<String>,
+    deliver_at: Option<String>,
+    dedup_key: Option<String>,
 }
 
 impl CreateMessageRequestBody {
     fn try_into_domain(self) -> Result<CreateMessageRequest, ParseCreateMessageHttpRequestError> {
-        let content = &self.content.clone();
+        let content = match (&self.content, &self.content_base64, &self.content_type) {
+            (Some(s), None, None) => Content::Text(s.clone()),
+            (Some(_), None, Some(_)) => {
+                return Err(ParseCreateMessageHttpRequestError::TypedText);
+            }
+            (None, Some(s), content_type) => Content::binary(
+                content_type.clone().unwrap_or(OCTET_STREAM.to_string()),
+                BASE64_STANDARD
+                    .decode(s)
+                    .map_err(|_| ParseCreateMessageHttpRequestError::BadBase64(s.to_string()))?,
+            ),
+            (None, None, _) => return Err(ParseCreateMessageHttpRequestError::NoContent),
+            (Some(_), Some(_), _) => return Err(ParseCreateMessageHttpRequestError::ContentTwice),
+        };
+        self.try_into_domain_with(content)
+    }
+
+    fn try_into_domain_with(
+        self,
+        content: Content,
+    ) -> Result<CreateMessageRequest, ParseCreateMessageHttpRequestError> {
         let cid = match &self.cid {
             None => None,
             Some(s) => Some(
@@ -58,7 +100,32 @@ impl CreateMessageRequestBody {
                 Some(Instant::now() + Duration::from_secs(secs))
             }
         };
-        Ok(CreateMessageRequest::new(content.clone(), cid, expiry))
+        let priority = match &self.priority {
+            None => 0,
+            Some(s) => s
+                .parse()
+                .map_err(|_| ParseCreateMessageHttpRequestError::BadPriority(s.to_string()))?,
+        };
+        let deliver_at = match (&self.delay_seconds, &self.deliver_at) {
+            (None, None) => None,
+            (Some(s), None) => {
+                let secs = s
+                    .parse()
+                    .map_err(|_| ParseCreateMessageHttpRequestError::BadDelay(s.to_string()))?;
+                Some(Instant::now() + Duration::from_secs(secs))
+            }
+            (None, Some(s)) => {
+                let time = humantime::parse_rfc3339_weak(s)
+                    .map_err(|_| ParseCreateMessageHttpRequestError::BadDeliverAt(s.to_string()))?;
+                Some(wall_clock::from_system_time(time))
+            }
+            (Some(_), Some(_)) => return Err(ParseCreateMessageHttpRequestError::DelayTwice),
+        };
+        Ok(CreateMessageRequest::new(content, cid, expiry)
+            .with_priority(priority)
+            .with_headers(self.headers.unwrap_or_default())
+            .with_deliver_at(deliver_at)
+            .with_dedup_key(self.dedup_key))
     }
 }
 
@@ -68,37 +135,92 @@ enum ParseCreateMessageHttpRequestError {
     QueueName(#[from] QueueNameEmptyError),
     BadUuid(String),
     BadExpiry(String),
+    BadPriority(String),
+    BadDelay(String),
+    BadDeliverAt(String),
+    DelayTwice,
+    NoContent,
+    ContentTwice,
+    TypedText,
+    BadBase64(String),
+    BadBody(String),
 }
 
 impl Display for ParseCreateMessageHttpRequestError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("Create message parse error")
-    }
-}
-
-impl From<ParseCreateMessageHttpRequestError> for ApiError {
-    fn from(e: ParseCreateMessageHttpRequestError) -> Self {
-        let message = match e {
+        let message = match self {
             ParseCreateMessageHttpRequestError::QueueName(_) => {
                 "queue name cannot be empty".to_string()
             }
             ParseCreateMessageHttpRequestError::BadUuid(s) => {
                 format!("{} cannot be parsed to a Uuid", s)
             }
-            ParseCreateMessageHttpRequestError::BadExpiry(s) => {
+            ParseCreateMessageHttpRequestError::BadExpiry(s)
+            | ParseCreateMessageHttpRequestError::BadPriority(s)
+            | ParseCreateMessageHttpRequestError::BadDelay(s) => {
                 format!("{} cannot be parsed to an integer", s)
             }
+            ParseCreateMessageHttpRequestError::BadDeliverAt(s) => {
+                format!("{} cannot be parsed to an RFC 3339 timestamp", s)
+            }
+            ParseCreateMessageHttpRequestError::DelayTwice => {
+                "delay_seconds and deliver_at cannot both be given".to_string()
+            }
+            ParseCreateMessageHttpRequestError::NoContent => {
+                "one of content and content_base64 must be given".to_string()
+            }
+            ParseCreateMessageHttpRequestError::ContentTwice => {
+                "content and content_base64 cannot both be given".to_string()
+            }
+            ParseCreateMessageHttpRequestError::TypedText => {
+                "content_type can only be given with content_base64".to_string()
+            }
+            ParseCreateMessageHttpRequestError::BadBase64(s) => {
+                format!("{} cannot be decoded as base64", s)
+            }
+            ParseCreateMessageHttpRequestError::BadBody(s) => s.clone(),
         };
-        Self::UnprocessableEntity(message)
+        f.write_str(&message)
+    }
+}
+
+impl From<ParseCreateMessageHttpRequestError> for ApiError {
+    fn from(e: ParseCreateMessageHttpRequestError) -> Self {
+        Self::UnprocessableEntity(e.to_string())
     }
 }
 
+/// A JSON body is read as a `CreateMessageRequestBody`.  Any other body is
+/// stored as it is, with its content type, and the other fields of the
+/// request may then be given as query parameters.
+pub(crate) fn parse_create_request(
+    params: CreateMessageRequestBody,
+    headers: &HeaderMap,
+    body: &Bytes,
+) -> Result<CreateMessageRequest, ApiError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(OCTET_STREAM);
+    let req = if is_json(content_type) {
+        serde_json::from_slice::<CreateMessageRequestBody>(body)
+            .map_err(|e| ParseCreateMessageHttpRequestError::BadBody(e.to_string()))?
+            .try_into_domain()?
+    } else {
+        params.try_into_domain_with(Content::binary(content_type.to_string(), body.to_vec()))?
+    };
+    Ok(req)
+}
+
+/// The body is read as by `parse_create_request`.
 pub async fn create_message<MS: MessageService>(
     State(state): State<AppState<MS>>,
     Path(queue_name): Path<String>,
-    Json(body): Json<CreateMessageRequestBody>,
+    Query(params): Query<CreateMessageRequestBody>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> Result<ApiSuccess<CreateMessageResponseData>, ApiError> {
-    let domain_req = body.try_into_domain()?;
+    let domain_req = parse_create_request(params, &headers, &body)?;
     let queue_name = queue_name
         .clone()
         .try_into()
@@ -111,6 +233,60 @@ pub async fn create_message<MS: MessageService>(
         .map(|ref message| ApiSuccess::new(StatusCode::CREATED, message.into()))
 }
 
+/// Enqueues every item of the body which parses, all at once, and reports
+/// for each item in turn either the id it was given or why it was rejected.
+pub async fn create_messages<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Json(items): Json<Vec<serde_json::Value>>,
+) -> Result<ApiSuccess<CreateMessagesResponseData>, ApiError> {
+    let queue_name = queue_name
+        .clone()
+        .try_into()
+        .map_err(|_| CreateMessageError::BadQueue(queue_name.clone()))?;
+    let parsed = items
+        .into_iter()
+        .map(|item| {
+            serde_json::from_value::<CreateMessageRequestBody>(item)
+                .map_err(|e| ParseCreateMessageHttpRequestError::BadBody(e.to_string()))
+                .and_then(CreateMessageRequestBody::try_into_domain)
+        })
+        .collect::<Vec<_>>();
+    let reqs = parsed
+        .iter()
+        .filter_map(|p| p.as_ref().ok().cloned())
+        .collect::<Vec<_>>();
+    let mut messages = state
+        .message_service
+        .create_messages(queue_name, &reqs)
+        .await?
+        .into_iter();
+    let items = parsed
+        .into_iter()
+        .map(|p| match p {
+            Ok(_) => messages.next().map(|ref m| m.into()).unwrap_or_default(),
+            Err(e) => BatchItemResponseData {
+                id: None,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+    Ok(ApiSuccess::new(
+        StatusCode::CREATED,
+        CreateMessagesResponseData { items },
+    ))
+}
+
+fn is_json(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct CreateMessageResponseData {
     id: String,
@@ -123,3 +299,168 @@ impl From<&Message> for CreateMessageResponseData {
         }
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CreateMessagesResponseData {
+    items: Vec<BatchItemResponseData>,
+}
+
+/// Exactly one of `id` and `error` is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BatchItemResponseData {
+    id: Option<String>,
+    error: Option<String>,
+}
+
+impl From<&Message> for BatchItemResponseData {
+    fn from(message: &Message) -> Self {
+        Self {
+            id: Some(message.mid().to_string()),
+            error: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use axum::body::to_bytes;
+    use axum::http::HeaderValue;
+    use axum::response::IntoResponse;
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::domain::messages::models::message::GetMessageOptions;
+    use crate::domain::messages::service::Service;
+    use crate::inbound::http::handlers::get_message::get_raw_message;
+    use crate::outbound::memory::Memory;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_binary_message() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let state = || {
+            State(AppState {
+                message_service: Arc::new(service.clone()),
+                shutdown: watch::channel(false).1,
+            })
+        };
+        let post = |content_type: &str, params: CreateMessageRequestBody, body: &[u8]| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+            create_message(
+                state(),
+                Path("queue1".to_string()),
+                Query(params),
+                headers,
+                Bytes::copy_from_slice(body),
+            )
+        };
+        let png = [0x89, b'P', b'N', b'G', 0x00, 0xff];
+        let params = CreateMessageRequestBody {
+            priority: Some("3".to_string()),
+            ..Default::default()
+        };
+        assert!(post("image/png", params, &png).await.is_ok());
+        let body = format!(
+            r#"{{"content_base64":"{}","content_type":"application/x-protobuf"}}"#,
+            BASE64_STANDARD.encode([1, 2, 3])
+        );
+        assert!(
+            post("application/json", Default::default(), body.as_bytes())
+                .await
+                .is_ok()
+        );
+        for (body, error) in [
+            (
+                r#"{"content_base64":"***"}"#,
+                "*** cannot be decoded as base64",
+            ),
+            (
+                r#"{"content":"msg","content_type":"text/html"}"#,
+                "content_type can only be given with content_base64",
+            ),
+        ] {
+            let actual = post("application/json", Default::default(), body.as_bytes()).await;
+            assert_eq!(
+                actual,
+                Err(ApiError::UnprocessableEntity(error.to_string()))
+            );
+        }
+
+        let get = |action: &str| {
+            let params = HashMap::from([("action".to_string(), action.to_string())]);
+            get_raw_message(state(), Path("queue1".to_string()), Query(params))
+        };
+        let response = get("get").await.unwrap().into_response();
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE),
+            Some(&HeaderValue::from_static("image/png"))
+        );
+        assert!(response.headers().contains_key("x-message-id"));
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], &png[..]);
+
+        let gmo: GetMessageOptions = HashMap::from([
+            ("queue_name".to_string(), "queue1".to_string()),
+            ("action".to_string(), "browse".to_string()),
+        ])
+        .try_into()
+        .unwrap();
+        let message = service.get_message(gmo).await.unwrap();
+        assert_eq!(
+            message.content(),
+            &Content::binary("application/x-protobuf".to_string(), vec![1, 2, 3])
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_messages() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let state = State(AppState {
+            message_service: Arc::new(service.clone()),
+            shutdown: watch::channel(false).1,
+        });
+        let items = serde_json::from_str(
+            r#"[
+                {"content":"msg1"},
+                {"content":"msg2","priority":"high"},
+                {"cid":"xxx"},
+                {"content":"msg4","priority":"2"}
+            ]"#,
+        )
+        .unwrap();
+        let response = create_messages(state, Path("queue1".to_string()), Json(items)).await;
+
+        let gmo: GetMessageOptions = [("queue_name", "queue1"), ("action", "get")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>()
+            .try_into()
+            .unwrap();
+        let msg4 = service.get_message(gmo.clone()).await.unwrap();
+        assert_eq!(msg4.content().text(), Some("msg4"));
+        let msg1 = service.get_message(gmo.clone()).await.unwrap();
+        assert_eq!(msg1.content().text(), Some("msg1"));
+        assert!(service.get_message(gmo).await.is_err());
+        let expected = ApiSuccess::new(
+            StatusCode::CREATED,
+            CreateMessagesResponseData {
+                items: vec![
+                    (&msg1).into(),
+                    BatchItemResponseData {
+                        id: None,
+                        error: Some("high cannot be parsed to an integer".to_string()),
+                    },
+                    BatchItemResponseData {
+                        id: None,
+                        error: Some("one of content and content_base64 must be given".to_string()),
+                    },
+                    (&msg4).into(),
+                ],
+            },
+        );
+        assert_eq!(response, Ok(expected));
+    }
+}
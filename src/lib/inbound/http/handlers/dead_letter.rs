@@ -0,0 +1,81 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::messages::models::message::{DeadLetterPolicy, DeadLetterPolicyError};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::{ApiError, ApiSuccess};
+use crate::inbound::http::handlers::params::parse_queue_name;
+use crate::inbound::http::AppState;
+
+impl From<DeadLetterPolicyError> for ApiError {
+    fn from(e: DeadLetterPolicyError) -> Self {
+        match e {
+            DeadLetterPolicyError::NoQueue(e) => Self::NotFound(e),
+            DeadLetterPolicyError::InvalidPolicy(e) => Self::UnprocessableEntity(e),
+            DeadLetterPolicyError::Unknown(e) => Self::InternalServerError(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DeadLetterRequestBody {
+    max_deliveries: String,
+    queue: String,
+}
+
+impl DeadLetterRequestBody {
+    pub(crate) fn try_into_domain(self) -> Result<DeadLetterPolicy, ApiError> {
+        let max_deliveries = self.max_deliveries.parse().map_err(|_| {
+            ApiError::UnprocessableEntity(format!(
+                "{} cannot be parsed to an integer",
+                self.max_deliveries
+            ))
+        })?;
+        let queue_name = parse_queue_name(self.queue)?;
+        Ok(DeadLetterPolicy::new(max_deliveries, queue_name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadLetterResponseData {
+    queue_name: String,
+    max_deliveries: u32,
+    dead_letter_queue: String,
+}
+
+/// Sends messages from `queue_name` to another queue once they have been
+/// delivered `max_deliveries` times without being confirmed.
+pub async fn set_dead_letter<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Json(body): Json<DeadLetterRequestBody>,
+) -> Result<ApiSuccess<DeadLetterResponseData>, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    let policy = body.try_into_domain()?;
+    let data = DeadLetterResponseData {
+        queue_name: queue_name.to_string(),
+        max_deliveries: policy.max_deliveries(),
+        dead_letter_queue: policy.queue_name().to_string(),
+    };
+    state
+        .message_service
+        .set_dead_letter_policy(queue_name, Some(policy))
+        .await
+        .map_err(ApiError::from)
+        .map(|_| ApiSuccess::new(StatusCode::OK, data))
+}
+
+pub async fn remove_dead_letter<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+) -> Result<ApiSuccess<()>, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    state
+        .message_service
+        .set_dead_letter_policy(queue_name, None)
+        .await
+        .map_err(ApiError::from)
+        .map(|_| ApiSuccess::new(StatusCode::OK, ()))
+}
@@ -1,10 +1,15 @@
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use base64::prelude::{Engine, BASE64_STANDARD};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::SystemTime;
+use uuid::Uuid;
 
 use crate::domain::messages::models::message::{
-    GetMessageAction, GetMessageError, GetMessageOptions, Message, QueueSummary, QueueSummaryError,
+    Content, GetMessageAction, GetMessageError, GetMessageOptions, Message, QueueSummary,
+    QueueSummaryError,
 };
 use crate::domain::messages::ports::MessageService;
 use crate::inbound::http::errors::{ApiError, ApiSuccess};
@@ -14,6 +19,7 @@ impl From<GetMessageError> for ApiError {
     fn from(e: GetMessageError) -> Self {
         match e {
             GetMessageError::NoMessage(e) => Self::NotFound(e),
+            GetMessageError::NotReserved(e) => Self::Conflict(e),
             GetMessageError::BadUuid(e) => Self::UnprocessableEntity(format!("Bad uuid {}", e)),
             GetMessageError::MissingParameter(e) => {
                 Self::UnprocessableEntity(format!("Missing parameter {}", e))
@@ -21,6 +27,7 @@ impl From<GetMessageError> for ApiError {
             GetMessageError::InvalidParameter(e) => {
                 Self::UnprocessableEntity(format!("Bad parameter {}", e))
             }
+            GetMessageError::NoReservation(e) => Self::UnprocessableEntity(e),
             GetMessageError::Unknown(e) => Self::InternalServerError(e.to_string()),
         }
     }
@@ -76,6 +83,19 @@ pub async fn get_message<MS: MessageService>(
                 ApiSuccess::new(StatusCode::OK, GetMessageReturnType::Info(info.into()))
             });
     }
+    if params.max_messages().is_some() {
+        return state
+            .message_service
+            .get_messages(params)
+            .await
+            .map_err(ApiError::from)
+            .map(|messages| {
+                ApiSuccess::new(
+                    StatusCode::OK,
+                    GetMessageReturnType::Messages(messages.iter().map(|m| m.into()).collect()),
+                )
+            });
+    }
     state
         .message_service
         .get_message(params)
@@ -84,23 +104,93 @@ pub async fn get_message<MS: MessageService>(
         .map(|ref message| {
             ApiSuccess::new(
                 StatusCode::OK,
-                GetMessageReturnType::Message(message.into()),
+                GetMessageReturnType::Message(Box::new(message.into())),
             )
         })
 }
 
+/// Takes the options of `get_message` but answers with the content of the
+/// single message found exactly as it was sent, for clients which do not
+/// want it wrapped in JSON.
+pub async fn get_raw_message<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Query(mut params): Query<HashMap<String, String>>,
+) -> Result<RawMessage, ApiError> {
+    params.insert("queue_name".to_string(), queue_name);
+    let params: GetMessageOptions = params.try_into()?;
+    if params.action() == GetMessageAction::Query {
+        return Err(
+            GetMessageError::InvalidParameter("query not valid for a message".to_string()).into(),
+        );
+    }
+    if params.max_messages().is_some() {
+        return Err(GetMessageError::InvalidParameter("max_messages".to_string()).into());
+    }
+    state
+        .message_service
+        .get_message(params)
+        .await
+        .map_err(ApiError::from)
+        .map(RawMessage)
+}
+
+/// A message sent as its content, with its id, cursor and any receipt in
+/// `x-message-*` headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawMessage(Message);
+
+impl IntoResponse for RawMessage {
+    fn into_response(self) -> Response {
+        let message = self.0;
+        let mut headers = HeaderMap::new();
+        // A content type given in JSON need not be a valid header value.
+        let content_type = HeaderValue::from_str(message.content().content_type())
+            .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+        headers.insert(header::CONTENT_TYPE, content_type);
+        headers.insert("x-message-id", uuid_header(message.mid()));
+        headers.insert("x-message-cursor", HeaderValue::from(message.cursor()));
+        if let Some(receipt) = message.receipt() {
+            headers.insert("x-message-receipt", uuid_header(receipt));
+        }
+        (StatusCode::OK, headers, message.content().bytes().to_vec()).into_response()
+    }
+}
+
+fn uuid_header(uuid: &Uuid) -> HeaderValue {
+    HeaderValue::from_str(&uuid.to_string()).unwrap()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum GetMessageReturnType {
-    Message(GetMessageResponseData),
+    Message(Box<GetMessageResponseData>),
+    Messages(Vec<GetMessageResponseData>),
     Info(QueueSummaryResponseData),
 }
 
+/// Times are formatted as RFC 3339 UTC timestamps.  Text content is given as
+/// `content`, and binary content as `content_base64` with its
+/// `content_type`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct GetMessageResponseData {
     mid: String,
     cid: Option<String>,
     cursor: usize,
-    content: String,
+    priority: i32,
+    content: Option<String>,
+    content_base64: Option<String>,
+    content_type: Option<String>,
+    headers: BTreeMap<String, String>,
+    enqueued_at: String,
+    first_reserved_at: Option<String>,
+    reserved_until: Option<String>,
+    expires_at: Option<String>,
+    deliver_at: Option<String>,
+    receipt: Option<String>,
+}
+
+fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_millis(time).to_string()
 }
 
 impl From<&Message> for GetMessageResponseData {
@@ -109,7 +199,23 @@ impl From<&Message> for GetMessageResponseData {
             mid: message.mid().to_string(),
             cid: message.cid().map(|uid| uid.to_string()),
             cursor: message.cursor(),
-            content: message.content().clone(),
+            priority: message.priority(),
+            content: message.content().text().map(str::to_string),
+            content_base64: match message.content() {
+                Content::Text(_) => None,
+                Content::Binary { bytes, .. } => Some(BASE64_STANDARD.encode(bytes)),
+            },
+            content_type: match message.content() {
+                Content::Text(_) => None,
+                Content::Binary { content_type, .. } => Some(content_type.clone()),
+            },
+            headers: message.headers().clone(),
+            enqueued_at: timestamp(message.enqueued_at()),
+            first_reserved_at: message.first_reserved_at().map(timestamp),
+            reserved_until: message.reserved_until().map(timestamp),
+            expires_at: message.expires_at().map(timestamp),
+            deliver_at: message.deliver_at().map(timestamp),
+            receipt: message.receipt().map(|uid| uid.to_string()),
         }
     }
 }
@@ -132,13 +238,20 @@ impl From<&QueueSummary> for QueueSummaryResponseData {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::messages::models::message::wall_clock;
     use crate::domain::messages::models::message::{
-        CreateMessageError, CreateMessageRequest, QueueList, QueueListError, QueueName,
+        AcknowledgeRequest, ConsumerGroup, ConsumerGroupError, CreateMessageError,
+        CreateMessageRequest, DeadLetterPolicy, DeadLetterPolicyError, QueueError, QueueList,
+        QueueListError, QueueName, QueueSettings, RedriveError, RedriveRequest, RetentionPolicy,
+        RetentionPolicyError, SubscriptionError,
     };
     use anyhow::anyhow;
+    use mock_instant::global::Instant;
     use serde_json;
     use std::mem;
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::sync::watch;
     use uuid::Uuid;
 
     #[derive(Clone)]
@@ -173,36 +286,151 @@ mod tests {
         ) -> Result<Message, CreateMessageError> {
             unreachable!()
         }
+        async fn create_messages(
+            &self,
+            _queue_name: QueueName,
+            _reqs: &[CreateMessageRequest],
+        ) -> Result<Vec<Message>, CreateMessageError> {
+            unreachable!()
+        }
 
         async fn get_message(&self, _param: GetMessageOptions) -> Result<Message, GetMessageError> {
             self.get()
         }
+        async fn get_messages(
+            &self,
+            _param: GetMessageOptions,
+        ) -> Result<Vec<Message>, GetMessageError> {
+            self.get().map(|message| vec![message])
+        }
+        async fn acknowledge_messages(
+            &self,
+            _req: &AcknowledgeRequest,
+        ) -> Result<Vec<Result<Message, GetMessageError>>, GetMessageError> {
+            unreachable!()
+        }
 
         async fn queue_list(&self) -> Result<QueueList, QueueListError> {
             unreachable!()
         }
+        async fn create_queue(
+            &self,
+            _queue_name: QueueName,
+            _settings: &QueueSettings,
+        ) -> Result<bool, QueueError> {
+            unreachable!()
+        }
+        async fn delete_queue(&self, _queue_name: QueueName) -> Result<(), QueueError> {
+            unreachable!()
+        }
+        async fn purge_queue(&self, _queue_name: QueueName) -> Result<usize, QueueError> {
+            unreachable!()
+        }
         async fn get_info(
             &self,
             _param: GetMessageOptions,
         ) -> Result<QueueSummary, QueueSummaryError> {
             unreachable!()
         }
+        async fn set_dead_letter_policy(
+            &self,
+            _queue_name: QueueName,
+            _policy: Option<DeadLetterPolicy>,
+        ) -> Result<(), DeadLetterPolicyError> {
+            unreachable!()
+        }
+        async fn set_retention_policy(
+            &self,
+            _queue_name: QueueName,
+            _policy: Option<RetentionPolicy>,
+        ) -> Result<(), RetentionPolicyError> {
+            unreachable!()
+        }
+        async fn redrive(
+            &self,
+            _queue_name: QueueName,
+            _req: &RedriveRequest,
+        ) -> Result<Vec<Message>, RedriveError> {
+            unreachable!()
+        }
+
+        async fn publish(
+            &self,
+            _topic: QueueName,
+            _req: &CreateMessageRequest,
+        ) -> Result<Vec<(QueueName, Message)>, CreateMessageError> {
+            unreachable!()
+        }
+        async fn subscribe(
+            &self,
+            _topic: QueueName,
+            _queue_name: QueueName,
+        ) -> Result<(), SubscriptionError> {
+            unreachable!()
+        }
+        async fn unsubscribe(
+            &self,
+            _topic: QueueName,
+            _queue_name: QueueName,
+        ) -> Result<(), SubscriptionError> {
+            unreachable!()
+        }
+        async fn subscriptions(
+            &self,
+            _topic: QueueName,
+        ) -> Result<Vec<QueueName>, SubscriptionError> {
+            unreachable!()
+        }
+
+        async fn consumer_group(
+            &self,
+            _queue_name: QueueName,
+            _group: QueueName,
+        ) -> Result<ConsumerGroup, ConsumerGroupError> {
+            unreachable!()
+        }
+        async fn commit_group(
+            &self,
+            _queue_name: QueueName,
+            _group: QueueName,
+            _cursor: Option<usize>,
+        ) -> Result<ConsumerGroup, ConsumerGroupError> {
+            unreachable!()
+        }
+        async fn delete_group(
+            &self,
+            _queue_name: QueueName,
+            _group: QueueName,
+        ) -> Result<(), ConsumerGroupError> {
+            unreachable!()
+        }
     }
 
     #[tokio::test(flavor = "multi_thread")]
     async fn test_get_message_success() {
         let content = "A String".to_string();
         let message_id = Uuid::new_v4();
-        let response = Ok(Message::new(message_id, None, content.clone(), None));
+        let message = Message::new(message_id, None, content.clone(), None);
         let expected = ApiSuccess::new(
             StatusCode::OK,
-            GetMessageReturnType::Message(GetMessageResponseData {
+            GetMessageReturnType::Message(Box::new(GetMessageResponseData {
                 mid: message_id.to_string(),
                 cid: None,
                 cursor: 0,
-                content: content.clone(),
-            }),
+                priority: 0,
+                content: Some(content.clone()),
+                content_base64: None,
+                content_type: None,
+                headers: BTreeMap::new(),
+                enqueued_at: timestamp(message.enqueued_at()),
+                first_reserved_at: None,
+                reserved_until: None,
+                expires_at: None,
+                deliver_at: None,
+                receipt: None,
+            })),
         );
+        let response = Ok(message);
         let actual = get("test", r#"{"action":"browse"}"#, &response)
             .await
             .unwrap();
@@ -210,6 +438,43 @@ mod tests {
         assert_eq!(actual, expected)
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_message_timestamps() {
+        let expiry = Instant::now() + Duration::from_secs(60);
+        let reservation = Instant::now() + Duration::from_secs(10);
+        let mut message = Message::new(Uuid::new_v4(), None, "".to_string(), Some(expiry));
+        message.set_reservation(&Some(reservation));
+        let expected = ApiSuccess::new(
+            StatusCode::OK,
+            GetMessageReturnType::Message(Box::new(GetMessageResponseData {
+                mid: message.mid().to_string(),
+                cid: None,
+                cursor: 0,
+                priority: 0,
+                content: Some("".to_string()),
+                content_base64: None,
+                content_type: None,
+                headers: BTreeMap::new(),
+                enqueued_at: timestamp(message.enqueued_at()),
+                first_reserved_at: message.first_reserved_at().map(timestamp),
+                reserved_until: Some(timestamp(wall_clock::to_system_time(reservation))),
+                expires_at: Some(timestamp(wall_clock::to_system_time(expiry))),
+                deliver_at: None,
+                receipt: None,
+            })),
+        );
+        assert!(message.first_reserved_at().is_some());
+        let actual = get(
+            "test",
+            r#"{"action":"reserve","reservation_seconds":"10"}"#,
+            &Ok(message),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(actual, expected)
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_get_message_bad_mid() {
         let response = Ok(Message::new(Uuid::new_v4(), None, "".to_string(), None));
@@ -258,6 +523,92 @@ mod tests {
         assert!(actual.is_ok(), "{:?}", actual);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_messages() {
+        let message = Message::new(Uuid::new_v4(), None, "".to_string(), None);
+        let response = Ok(message.clone());
+        let expected = ApiError::UnprocessableEntity("Bad parameter max_messages".to_string());
+        for gmo in [
+            r#"{"action":"get","max_messages":"0"}"#,
+            r#"{"action":"get","max_messages":"xxx"}"#,
+            r#"{"action":"query","max_messages":"2"}"#,
+        ] {
+            let actual = get("test", gmo, &response).await;
+            assert_eq!(actual, Err(expected.clone()), "{}", gmo);
+        }
+
+        let expected = ApiSuccess::new(
+            StatusCode::OK,
+            GetMessageReturnType::Messages(vec![(&message).into()]),
+        );
+        let actual = get(
+            "test",
+            r#"{"action":"browse","max_messages":"2"}"#,
+            &response,
+        )
+        .await;
+        assert_eq!(actual, Ok(expected));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_message_bad_wait() {
+        let response = Ok(Message::new(Uuid::new_v4(), None, "".to_string(), None));
+        let expected = ApiError::UnprocessableEntity("Bad parameter wait_seconds".to_string());
+        let actual = get(
+            "test",
+            r#"{"action":"get","wait_seconds":"xxx"}"#,
+            &response,
+        )
+        .await;
+        assert_eq!(actual, Err(expected.clone()));
+
+        let actual = get(
+            "test",
+            r#"{"action":"browse","wait_seconds":"10"}"#,
+            &response,
+        )
+        .await;
+        assert_eq!(actual, Err(expected));
+
+        let actual = get("test", r#"{"action":"get","wait_seconds":"10"}"#, &response).await;
+        assert!(actual.is_ok(), "{:?}", actual);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_message_bad_extend() {
+        let response = Ok(Message::new(Uuid::new_v4(), None, "".to_string(), None));
+        let expected =
+            ApiError::UnprocessableEntity("Missing parameter reservation_seconds".to_string());
+        let receipt = "61fb8b36-c7e6-4a34-af8a-011a73f065f0";
+        let actual = get(
+            "test",
+            &format!(r#"{{"action":"extend","receipt":"{}"}}"#, receipt),
+            &response,
+        )
+        .await;
+        assert_eq!(actual, Err(expected));
+
+        let expected = ApiError::UnprocessableEntity("Missing parameter receipt".to_string());
+        let actual = get(
+            "test",
+            r#"{"action":"extend","reservation_seconds":"10"}"#,
+            &response,
+        )
+        .await;
+        assert_eq!(actual, Err(expected));
+
+        let actual = get(
+            "test",
+            &format!(
+                r#"{{"action":"extend","receipt":"{}","reservation_seconds":"10"}}"#,
+                receipt
+            ),
+            &response,
+        )
+        .await;
+        assert!(actual.is_ok(), "{:?}", actual);
+    }
+
     async fn get(
         path: &str,
         gmo: &str,
@@ -266,6 +617,7 @@ mod tests {
         let service = MockMessageService::new_get(response.clone());
         let state = axum::extract::State(AppState {
             message_service: Arc::new(service),
+            shutdown: watch::channel(false).1,
         });
 
         let path = axum::extract::Path(path.to_string());
@@ -0,0 +1,19 @@
+use crate::domain::messages::models::message::QueueName;
+use crate::inbound::http::errors::ApiError;
+
+/// A queue or topic name taken from a path or body.
+pub(crate) fn parse_queue_name(queue_name: String) -> Result<QueueName, ApiError> {
+    queue_name
+        .try_into()
+        .map_err(|_| ApiError::UnprocessableEntity("queue name cannot be empty".to_string()))
+}
+
+/// An optional whole number, which bodies carry as a string.
+pub(crate) fn parse_number(s: Option<String>) -> Result<Option<u64>, ApiError> {
+    s.map(|s| {
+        s.parse().map_err(|_| {
+            ApiError::UnprocessableEntity(format!("{} cannot be parsed to an integer", s))
+        })
+    })
+    .transpose()
+}
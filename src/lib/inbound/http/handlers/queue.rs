@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::messages::models::message::{QueueConfig, QueueError, QueueName, QueueSettings};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::{ApiError, ApiSuccess};
+use crate::inbound::http::handlers::dead_letter::DeadLetterRequestBody;
+use crate::inbound::http::handlers::params::{parse_number, parse_queue_name};
+use crate::inbound::http::handlers::retention::RetentionRequestBody;
+use crate::inbound::http::AppState;
+
+impl From<QueueError> for ApiError {
+    fn from(e: QueueError) -> Self {
+        match e {
+            QueueError::NoQueue(e) => Self::NotFound(e),
+            QueueError::InvalidSettings(e) => Self::UnprocessableEntity(e),
+            QueueError::Unknown(e) => Self::InternalServerError(e.to_string()),
+        }
+    }
+}
+
+/// Each setting takes the body of its own endpoint, and one left out is
+/// turned off.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct CreateQueueRequestBody {
+    dead_letter: Option<DeadLetterRequestBody>,
+    retention: Option<RetentionRequestBody>,
+    config: Option<QueueConfigRequestBody>,
+}
+
+impl CreateQueueRequestBody {
+    fn try_into_domain(self) -> Result<QueueSettings, ApiError> {
+        Ok(QueueSettings::default()
+            .with_dead_letter(self.dead_letter.map(|d| d.try_into_domain()).transpose()?)
+            .with_retention(self.retention.map(|r| r.try_into_domain()).transpose()?)
+            .with_config(self.config.unwrap_or_default().try_into_domain()?))
+    }
+}
+
+/// The defaults stand in for `expiry_seconds` and `reservation_seconds` when
+/// a request leaves them out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct QueueConfigRequestBody {
+    expiry_seconds: Option<String>,
+    reservation_seconds: Option<String>,
+    max_depth: Option<String>,
+    max_content_size: Option<String>,
+}
+
+impl QueueConfigRequestBody {
+    fn try_into_domain(self) -> Result<QueueConfig, ApiError> {
+        Ok(QueueConfig::default()
+            .with_expiry(parse_number(self.expiry_seconds)?.map(Duration::from_secs))
+            .with_reservation(parse_number(self.reservation_seconds)?.map(Duration::from_secs))
+            .with_max_depth(parse_number(self.max_depth)?.map(|n| n as usize))
+            .with_max_content_size(parse_number(self.max_content_size)?.map(|n| n as usize)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueueResponseData {
+    queue_name: String,
+    dead_letter: Option<DeadLetterSettingsData>,
+    retention: Option<RetentionSettingsData>,
+    config: QueueConfigData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadLetterSettingsData {
+    max_deliveries: u32,
+    queue: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RetentionSettingsData {
+    max_age_seconds: Option<u64>,
+    max_messages: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueueConfigData {
+    expiry_seconds: Option<u64>,
+    reservation_seconds: Option<u64>,
+    max_depth: Option<usize>,
+    max_content_size: Option<usize>,
+}
+
+impl QueueResponseData {
+    fn new(queue_name: &QueueName, settings: &QueueSettings) -> Self {
+        Self {
+            queue_name: queue_name.to_string(),
+            dead_letter: settings.dead_letter().map(|p| DeadLetterSettingsData {
+                max_deliveries: p.max_deliveries(),
+                queue: p.queue_name().to_string(),
+            }),
+            retention: settings.retention().map(|p| RetentionSettingsData {
+                max_age_seconds: p.max_age().map(|d| d.as_secs()),
+                max_messages: p.max_messages(),
+            }),
+            config: QueueConfigData {
+                expiry_seconds: settings.config().expiry().map(|d| d.as_secs()),
+                reservation_seconds: settings.config().reservation().map(|d| d.as_secs()),
+                max_depth: settings.config().max_depth(),
+                max_content_size: settings.config().max_content_size(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PurgeResponseData {
+    queue_name: String,
+    purged: usize,
+}
+
+/// Creates a queue, or replaces the settings of one which exists, answering
+/// 201 or 200 accordingly.
+pub async fn create_queue<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Json(body): Json<CreateQueueRequestBody>,
+) -> Result<ApiSuccess<QueueResponseData>, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    let settings = body.try_into_domain()?;
+    let data = QueueResponseData::new(&queue_name, &settings);
+    let created = state
+        .message_service
+        .create_queue(queue_name, &settings)
+        .await?;
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok(ApiSuccess::new(status, data))
+}
+
+pub async fn delete_queue<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+) -> Result<ApiSuccess<()>, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    state
+        .message_service
+        .delete_queue(queue_name)
+        .await
+        .map_err(ApiError::from)
+        .map(|_| ApiSuccess::new(StatusCode::OK, ()))
+}
+
+pub async fn purge_queue<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+) -> Result<ApiSuccess<PurgeResponseData>, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    let data = |purged| PurgeResponseData {
+        queue_name: queue_name.to_string(),
+        purged,
+    };
+    state
+        .message_service
+        .purge_queue(queue_name.clone())
+        .await
+        .map_err(ApiError::from)
+        .map(|purged| ApiSuccess::new(StatusCode::OK, data(purged)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::domain::messages::models::message::CreateMessageRequest;
+    use crate::domain::messages::service::Service;
+    use crate::outbound::memory::Memory;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_queue_lifecycle() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let state = || {
+            State(AppState {
+                message_service: Arc::new(service.clone()),
+                shutdown: watch::channel(false).1,
+            })
+        };
+        let path = || Path("queue1".to_string());
+        let body: CreateQueueRequestBody = serde_json::from_str(
+            r#"{"dead_letter":{"max_deliveries":"3","queue":"dlq"},"retention":{"max_messages":"10"},
+                "config":{"reservation_seconds":"30","max_depth":"5"}}"#,
+        )
+        .unwrap();
+        let data = QueueResponseData {
+            queue_name: "queue1".to_string(),
+            dead_letter: Some(DeadLetterSettingsData {
+                max_deliveries: 3,
+                queue: "dlq".to_string(),
+            }),
+            retention: Some(RetentionSettingsData {
+                max_age_seconds: None,
+                max_messages: Some(10),
+            }),
+            config: QueueConfigData {
+                expiry_seconds: None,
+                reservation_seconds: Some(30),
+                max_depth: Some(5),
+                max_content_size: None,
+            },
+        };
+        let expected = ApiSuccess::new(StatusCode::CREATED, data.clone());
+        let actual = create_queue(state(), path(), Json(body.clone())).await;
+        assert_eq!(actual, Ok(expected));
+        let expected = ApiSuccess::new(StatusCode::OK, data);
+        assert_eq!(
+            create_queue(state(), path(), Json(body)).await,
+            Ok(expected)
+        );
+
+        let body: CreateQueueRequestBody =
+            serde_json::from_str(r#"{"dead_letter":{"max_deliveries":"3","queue":"queue1"}}"#)
+                .unwrap();
+        let expected =
+            ApiError::UnprocessableEntity("queue1 cannot be its own dead letter queue".to_string());
+        assert_eq!(
+            create_queue(state(), path(), Json(body)).await,
+            Err(expected)
+        );
+
+        for content in ["msg1", "msg2"] {
+            let req = CreateMessageRequest::new(content.to_string(), None, None);
+            service
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+        }
+        let expected = ApiSuccess::new(
+            StatusCode::OK,
+            PurgeResponseData {
+                queue_name: "queue1".to_string(),
+                purged: 2,
+            },
+        );
+        assert_eq!(purge_queue(state(), path()).await, Ok(expected));
+
+        assert!(delete_queue(state(), path()).await.is_ok());
+        let expected = ApiError::NotFound("no queue queue1".to_string());
+        assert_eq!(delete_queue(state(), path()).await, Err(expected.clone()));
+        assert_eq!(purge_queue(state(), path()).await, Err(expected));
+    }
+}
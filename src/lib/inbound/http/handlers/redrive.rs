@@ -0,0 +1,85 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::messages::models::message::{Message, RedriveError, RedriveRequest};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::{ApiError, ApiSuccess};
+use crate::inbound::http::handlers::params::{parse_number, parse_queue_name};
+use crate::inbound::http::AppState;
+
+impl From<RedriveError> for ApiError {
+    fn from(e: RedriveError) -> Self {
+        match e {
+            RedriveError::NoQueue(e) => Self::NotFound(e),
+            RedriveError::InvalidRequest(e) => Self::UnprocessableEntity(e),
+            RedriveError::Unknown(e) => Self::InternalServerError(e.to_string()),
+        }
+    }
+}
+
+/// Any combination of `mid`, `cid` and the cursor range `after`..=`until`
+/// narrows the messages moved; with none of them the whole queue is moved.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RedriveRequestBody {
+    target: String,
+    mid: Option<String>,
+    cid: Option<String>,
+    after: Option<String>,
+    until: Option<String>,
+}
+
+impl RedriveRequestBody {
+    fn try_into_domain(self) -> Result<RedriveRequest, ApiError> {
+        let target = parse_queue_name(self.target)?;
+        Ok(RedriveRequest::new(target)
+            .with_mid(parse_uuid(self.mid)?)
+            .with_cid(parse_uuid(self.cid)?)
+            .with_cursor_range(
+                parse_number(self.after)?.map(|n| n as usize),
+                parse_number(self.until)?.map(|n| n as usize),
+            ))
+    }
+}
+
+fn parse_uuid(s: Option<String>) -> Result<Option<Uuid>, ApiError> {
+    s.map(|s| {
+        Uuid::try_parse(&s)
+            .map_err(|_| ApiError::UnprocessableEntity(format!("{} cannot be parsed to a Uuid", s)))
+    })
+    .transpose()
+}
+
+pub async fn redrive<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Json(body): Json<RedriveRequestBody>,
+) -> Result<ApiSuccess<RedriveResponseData>, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    let req = body.try_into_domain()?;
+    state
+        .message_service
+        .redrive(queue_name, &req)
+        .await
+        .map_err(ApiError::from)
+        .map(|ref messages| ApiSuccess::new(StatusCode::OK, (&req, messages).into()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RedriveResponseData {
+    target: String,
+    count: usize,
+    ids: Vec<String>,
+}
+
+impl From<(&RedriveRequest, &Vec<Message>)> for RedriveResponseData {
+    fn from((req, messages): (&RedriveRequest, &Vec<Message>)) -> Self {
+        Self {
+            target: req.target().to_string(),
+            count: messages.len(),
+            ids: messages.iter().map(|m| m.mid().to_string()).collect(),
+        }
+    }
+}
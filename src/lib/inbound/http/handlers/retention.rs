@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::messages::models::message::{RetentionPolicy, RetentionPolicyError};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::{ApiError, ApiSuccess};
+use crate::inbound::http::handlers::params::{parse_number, parse_queue_name};
+use crate::inbound::http::AppState;
+
+impl From<RetentionPolicyError> for ApiError {
+    fn from(e: RetentionPolicyError) -> Self {
+        match e {
+            RetentionPolicyError::NoQueue(e) => Self::NotFound(e),
+            RetentionPolicyError::InvalidPolicy(e) => Self::UnprocessableEntity(e),
+            RetentionPolicyError::Unknown(e) => Self::InternalServerError(e.to_string()),
+        }
+    }
+}
+
+/// Either limit may be left out; with neither, every message is kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct RetentionRequestBody {
+    max_age_seconds: Option<String>,
+    max_messages: Option<String>,
+}
+
+impl RetentionRequestBody {
+    pub(crate) fn try_into_domain(self) -> Result<RetentionPolicy, ApiError> {
+        let max_age = parse_number(self.max_age_seconds)?.map(Duration::from_secs);
+        let max_messages = parse_number(self.max_messages)?.map(|n| n as usize);
+        Ok(RetentionPolicy::new(max_age, max_messages))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RetentionResponseData {
+    queue_name: String,
+    max_age_seconds: Option<u64>,
+    max_messages: Option<usize>,
+}
+
+/// Keeps the messages of `queue_name` when they are got or confirmed, so
+/// that the queue can be replayed by browsing with `after` or a group.
+pub async fn set_retention<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Json(body): Json<RetentionRequestBody>,
+) -> Result<ApiSuccess<RetentionResponseData>, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    let policy = body.try_into_domain()?;
+    let data = RetentionResponseData {
+        queue_name: queue_name.to_string(),
+        max_age_seconds: policy.max_age().map(|d| d.as_secs()),
+        max_messages: policy.max_messages(),
+    };
+    state
+        .message_service
+        .set_retention_policy(queue_name, Some(policy))
+        .await
+        .map_err(ApiError::from)
+        .map(|_| ApiSuccess::new(StatusCode::OK, data))
+}
+
+pub async fn remove_retention<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+) -> Result<ApiSuccess<()>, ApiError> {
+    let queue_name = parse_queue_name(queue_name)?;
+    state
+        .message_service
+        .set_retention_policy(queue_name, None)
+        .await
+        .map_err(ApiError::from)
+        .map(|_| ApiSuccess::new(StatusCode::OK, ()))
+}
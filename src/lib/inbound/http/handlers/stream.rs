@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, Stream, StreamExt};
+use tokio::sync::watch;
+
+use crate::domain::messages::models::message::{
+    GetMessageAction, GetMessageError, GetMessageOptions, Message,
+};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::ApiError;
+use crate::inbound::http::handlers::get_message::GetMessageResponseData;
+use crate::inbound::http::AppState;
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// How long each request to the service waits for a message before the
+/// stream asks again.
+const POLL_WAIT: Duration = Duration::from_secs(30);
+
+/// Pushes messages from a queue as server-sent events until the client goes
+/// away or the server shuts down.  `action=browse` (the default) leaves the
+/// messages in the queue and resumes after the `Last-Event-ID` header or the
+/// `after` parameter; `action=get` removes each message as it is sent.
+pub async fn stream_messages<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(queue_name): Path<String>,
+    Query(mut params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    params.insert("queue_name".to_string(), queue_name);
+    params
+        .entry("action".to_string())
+        .or_insert_with(|| "browse".to_string());
+    if let Some(last) = headers.get(LAST_EVENT_ID) {
+        let last = last
+            .to_str()
+            .map_err(|_| GetMessageError::InvalidParameter(LAST_EVENT_ID.to_string()))?;
+        params.insert("after".to_string(), last.to_string());
+    }
+    let gmo: GetMessageOptions = params.try_into()?;
+    match gmo.action() {
+        GetMessageAction::Browse | GetMessageAction::Get => {}
+        action => {
+            return Err(GetMessageError::InvalidParameter(format!(
+                "{:?} is not valid for a stream",
+                action
+            ))
+            .into())
+        }
+    }
+    let events = messages(
+        state.message_service.as_ref().clone(),
+        gmo,
+        state.shutdown.clone(),
+    )
+    .map(|message| {
+        let event = Event::default()
+            .id(message.cursor().to_string())
+            .event("message")
+            .json_data(GetMessageResponseData::from(&message))
+            .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()));
+        Ok(event)
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// The messages matching `gmo` in the order the service hands them out,
+/// waiting for new ones when the queue runs dry.  Browsing moves the cursor
+/// past each message sent; the stream ends if the service fails or
+/// `shutdown` becomes `true`.
+fn messages<MS: MessageService>(
+    service: MS,
+    gmo: GetMessageOptions,
+    shutdown: watch::Receiver<bool>,
+) -> impl Stream<Item = Message> {
+    let gmo = gmo.with_wait(Some(POLL_WAIT));
+    stream::unfold(
+        (service, gmo, shutdown),
+        |(service, gmo, mut shutdown)| async move {
+            loop {
+                let result = tokio::select! {
+                    result = service.get_message(gmo.clone()) => result,
+                    _ = shutdown.wait_for(|&done| done) => return None,
+                };
+                match result {
+                    Ok(message) => {
+                        let gmo = match gmo.action() {
+                            GetMessageAction::Browse => gmo.with_cursor(Some(message.cursor())),
+                            _ => gmo,
+                        };
+                        return Some((message, (service, gmo, shutdown)));
+                    }
+                    Err(GetMessageError::NoMessage(_)) => {}
+                    Err(e) => {
+                        tracing::error!("ending stream for {}: {:?}", gmo.queue_name(), e);
+                        return None;
+                    }
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::messages::models::message::CreateMessageRequest;
+    use crate::domain::messages::service::Service;
+    use crate::outbound::memory::Memory;
+
+    async fn put(service: &Service<Memory>, content: &str) -> Message {
+        let req = CreateMessageRequest::new(content.to_string(), None, None);
+        service
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap()
+    }
+
+    fn gmo(action: &str, after: Option<usize>) -> GetMessageOptions {
+        let mut params = HashMap::new();
+        params.insert("queue_name".to_string(), "queue1".to_string());
+        params.insert("action".to_string(), action.to_string());
+        if let Some(after) = after {
+            params.insert("after".to_string(), after.to_string());
+        }
+        params.try_into().unwrap()
+    }
+
+    async fn depth(service: &Service<Memory>) -> usize {
+        service.get_info(gmo("query", None)).await.unwrap().depth()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_browse_stream() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let _msg1 = put(&service, "msg1").await;
+        let msg2 = put(&service, "msg2").await;
+        let (_shutdown, shutdown_rx) = watch::channel(false);
+        let stream = messages(service.clone(), gmo("browse", Some(1)), shutdown_rx);
+        tokio::pin!(stream);
+        assert_eq!(stream.next().await.unwrap(), msg2);
+
+        let waiting = tokio::time::timeout(Duration::from_millis(50), stream.next()).await;
+        assert!(waiting.is_err());
+        let msg3 = put(&service, "msg3").await;
+        assert_eq!(stream.next().await.unwrap(), msg3);
+        assert_eq!(depth(&service).await, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_stream() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let msg1 = put(&service, "msg1").await;
+        let msg2 = put(&service, "msg2").await;
+        let (_shutdown, shutdown_rx) = watch::channel(false);
+        let stream = messages(service.clone(), gmo("get", None), shutdown_rx);
+        tokio::pin!(stream);
+        assert_eq!(stream.next().await.unwrap(), msg1);
+        assert_eq!(stream.next().await.unwrap(), msg2);
+        assert_eq!(depth(&service).await, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stream_ends_on_shutdown() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let msg1 = put(&service, "msg1").await;
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let stream = messages(service.clone(), gmo("browse", None), shutdown_rx);
+        tokio::pin!(stream);
+        assert_eq!(stream.next().await.unwrap(), msg1);
+
+        let waiting = tokio::time::timeout(Duration::from_millis(50), stream.next()).await;
+        assert!(waiting.is_err());
+        shutdown.send_replace(true);
+        let ended = tokio::time::timeout(Duration::from_secs(1), stream.next()).await;
+        assert_eq!(ended.unwrap(), None);
+    }
+}
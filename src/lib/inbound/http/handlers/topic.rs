@@ -0,0 +1,222 @@
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use serde::Serialize;
+
+use crate::domain::messages::models::message::{
+    CreateMessageError, Message, QueueName, SubscriptionError,
+};
+use crate::domain::messages::ports::MessageService;
+use crate::inbound::http::errors::{ApiError, ApiSuccess};
+use crate::inbound::http::handlers::create_message::{
+    parse_create_request, CreateMessageRequestBody,
+};
+use crate::inbound::http::handlers::params::parse_queue_name;
+use crate::inbound::http::AppState;
+
+impl From<SubscriptionError> for ApiError {
+    fn from(e: SubscriptionError) -> Self {
+        match e {
+            SubscriptionError::NoQueue(e) => Self::NotFound(e),
+            SubscriptionError::NoSubscription(e) => Self::NotFound(e),
+            SubscriptionError::Unknown(e) => Self::InternalServerError(e.to_string()),
+        }
+    }
+}
+
+/// Copies a message, given as to `create_message`, into every queue
+/// subscribed to `topic`.  A topic without subscribers drops the message.
+pub async fn publish<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(topic): Path<String>,
+    Query(params): Query<CreateMessageRequestBody>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<ApiSuccess<PublishResponseData>, ApiError> {
+    let req = parse_create_request(params, &headers, &body)?;
+    let topic = topic
+        .clone()
+        .try_into()
+        .map_err(|_| CreateMessageError::BadQueue(topic.clone()))?;
+    let items = state
+        .message_service
+        .publish(topic, &req)
+        .await?
+        .iter()
+        .map(|(queue_name, message)| PublishItemResponseData::new(queue_name, message))
+        .collect();
+    Ok(ApiSuccess::new(
+        StatusCode::CREATED,
+        PublishResponseData { items },
+    ))
+}
+
+pub async fn subscribe<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path((topic, queue_name)): Path<(String, String)>,
+) -> Result<ApiSuccess<SubscriptionResponseData>, ApiError> {
+    let topic = parse_queue_name(topic)?;
+    let queue_name = parse_queue_name(queue_name)?;
+    let data = SubscriptionResponseData {
+        topic: topic.to_string(),
+        queue_name: queue_name.to_string(),
+    };
+    state
+        .message_service
+        .subscribe(topic, queue_name)
+        .await
+        .map_err(ApiError::from)
+        .map(|_| ApiSuccess::new(StatusCode::OK, data))
+}
+
+pub async fn unsubscribe<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path((topic, queue_name)): Path<(String, String)>,
+) -> Result<ApiSuccess<()>, ApiError> {
+    let topic = parse_queue_name(topic)?;
+    let queue_name = parse_queue_name(queue_name)?;
+    state
+        .message_service
+        .unsubscribe(topic, queue_name)
+        .await
+        .map_err(ApiError::from)
+        .map(|_| ApiSuccess::new(StatusCode::OK, ()))
+}
+
+pub async fn subscriptions<MS: MessageService>(
+    State(state): State<AppState<MS>>,
+    Path(topic): Path<String>,
+) -> Result<ApiSuccess<SubscriptionsResponseData>, ApiError> {
+    let topic = parse_queue_name(topic)?;
+    let queues = state
+        .message_service
+        .subscriptions(topic.clone())
+        .await?
+        .iter()
+        .map(QueueName::to_string)
+        .collect();
+    Ok(ApiSuccess::new(
+        StatusCode::OK,
+        SubscriptionsResponseData {
+            topic: topic.to_string(),
+            queues,
+        },
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublishResponseData {
+    items: Vec<PublishItemResponseData>,
+}
+
+/// The message created in one subscribed queue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublishItemResponseData {
+    queue_name: String,
+    id: String,
+}
+
+impl PublishItemResponseData {
+    fn new(queue_name: &QueueName, message: &Message) -> Self {
+        Self {
+            queue_name: queue_name.to_string(),
+            id: message.mid().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubscriptionResponseData {
+    topic: String,
+    queue_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubscriptionsResponseData {
+    topic: String,
+    queues: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use axum::http::header;
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::domain::messages::models::message::{GetMessageOptions, QueueSettings};
+    use crate::domain::messages::service::Service;
+    use crate::outbound::memory::Memory;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_publish() {
+        let service = Service::new(Memory::new().await.unwrap());
+        let state = || {
+            State(AppState {
+                message_service: Arc::new(service.clone()),
+                shutdown: watch::channel(false).1,
+            })
+        };
+        let path = |queue_name: &str| Path(("orders".to_string(), queue_name.to_string()));
+        let expected = ApiError::NotFound("no queue billing".to_string());
+        assert_eq!(subscribe(state(), path("billing")).await, Err(expected));
+        for queue_name in ["billing", "shipping"] {
+            service
+                .create_queue(
+                    queue_name.to_string().try_into().unwrap(),
+                    &QueueSettings::default(),
+                )
+                .await
+                .unwrap();
+            assert!(subscribe(state(), path(queue_name)).await.is_ok());
+        }
+        let expected = ApiSuccess::new(
+            StatusCode::OK,
+            SubscriptionsResponseData {
+                topic: "orders".to_string(),
+                queues: vec!["billing".to_string(), "shipping".to_string()],
+            },
+        );
+        let actual = subscriptions(state(), Path("orders".to_string())).await;
+        assert_eq!(actual, Ok(expected));
+
+        let publish_msg = |content: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+            let body = format!(r#"{{"content":"{}"}}"#, content);
+            publish(
+                state(),
+                Path("orders".to_string()),
+                Query(Default::default()),
+                headers,
+                Bytes::from(body),
+            )
+        };
+        assert!(publish_msg("order1").await.is_ok());
+        assert!(unsubscribe(state(), path("billing")).await.is_ok());
+        let expected = ApiError::NotFound("billing is not subscribed to orders".to_string());
+        assert_eq!(unsubscribe(state(), path("billing")).await, Err(expected));
+        assert!(publish_msg("order2").await.is_ok());
+
+        for (queue_name, contents) in [
+            ("billing", vec!["order1"]),
+            ("shipping", vec!["order1", "order2"]),
+        ] {
+            let gmo: GetMessageOptions = HashMap::from([
+                ("queue_name".to_string(), queue_name.to_string()),
+                ("action".to_string(), "get".to_string()),
+                ("max_messages".to_string(), "10".to_string()),
+            ])
+            .try_into()
+            .unwrap();
+            let messages = service.get_messages(gmo).await.unwrap();
+            let actual = messages
+                .iter()
+                .map(|m| m.content().text().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(actual, contents, "{}", queue_name);
+        }
+    }
+}
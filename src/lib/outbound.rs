@@ -1 +1,4 @@
+pub mod log;
 pub mod memory;
+pub mod sqlite;
+mod waiters;
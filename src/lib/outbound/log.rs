@@ -0,0 +1,697 @@
+use anyhow::Context;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::domain::messages::models::message::{
+    AcknowledgeRequest, ConsumerGroup, CreateMessageRequest, DeadLetterPolicy, GetMessageOptions,
+    Message, QueueList, QueueName, QueueSettings, QueueSummary, RedriveRequest, RetentionPolicy,
+};
+use crate::domain::messages::models::message::{
+    ConsumerGroupError, CreateMessageError, DeadLetterPolicyError, GetMessageError, QueueError,
+    QueueListError, QueueSummaryError, RedriveError, RetentionPolicyError, SubscriptionError,
+};
+use crate::domain::messages::ports::MessageRepository;
+use crate::outbound::memory::{Change, Journal, Memory};
+
+const SEGMENT_EXTENSION: &str = "log";
+const DEFAULT_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// A repository which keeps its state in memory and records every change to
+/// an append-only log of segment files in `dir`.  On startup the segments are
+/// replayed to rebuild the queues and then compacted into a single segment.
+#[derive(Debug, Clone)]
+pub struct Log {
+    memory: Memory,
+}
+
+/// The segments written since startup, from `first` onwards.
+#[derive(Debug)]
+struct Segments {
+    writer: Mutex<SegmentWriter>,
+    dir: PathBuf,
+    first: u64,
+}
+
+#[derive(Debug)]
+struct SegmentWriter {
+    dir: PathBuf,
+    seq: u64,
+    file: File,
+    len: u64,
+    max_len: u64,
+    /// Set when an append failed and may have left part of its record after
+    /// `len`.
+    dirty: bool,
+}
+
+impl Log {
+    pub async fn new(dir: impl AsRef<Path>) -> Result<Log, anyhow::Error> {
+        Self::with_segment_bytes(dir, DEFAULT_SEGMENT_BYTES).await
+    }
+
+    pub async fn with_segment_bytes(
+        dir: impl AsRef<Path>,
+        max_len: u64,
+    ) -> Result<Log, anyhow::Error> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create log directory {}", dir.display()))?;
+
+        let memory = Memory::new().await?;
+        let existing = segment_numbers(dir)?;
+        for seq in &existing {
+            for change in read_segment(&segment_path(dir, *seq))? {
+                memory.apply(change);
+            }
+        }
+
+        let seq = existing.last().map_or(0, |seq| seq + 1);
+        let writer = SegmentWriter::create(dir, seq, max_len)?;
+        let segments = Segments {
+            writer: Mutex::new(writer),
+            dir: dir.to_path_buf(),
+            first: seq,
+        };
+        segments.record(&memory.checkpoint())?;
+        for old in existing {
+            let path = segment_path(dir, old);
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove segment {}", path.display()))?;
+        }
+
+        Ok(Self {
+            memory: memory.with_journal(Arc::new(segments)),
+        })
+    }
+
+    /// Sets whether a message for a queue which does not exist makes the
+    /// queue, as it does unless told otherwise, or is refused.
+    pub fn with_auto_create(self, auto_create: bool) -> Self {
+        Self {
+            memory: self.memory.with_auto_create(auto_create),
+        }
+    }
+}
+
+impl SegmentWriter {
+    fn create(dir: &Path, seq: u64, max_len: u64) -> anyhow::Result<Self> {
+        let path = segment_path(dir, seq);
+        let file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to create segment {}", path.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            seq,
+            file,
+            len: 0,
+            max_len,
+            dirty: false,
+        })
+    }
+
+    /// Writes `changes` as a single line, so that a replay applies all of
+    /// them or none.
+    fn append(&mut self, changes: &[Change]) -> anyhow::Result<()> {
+        self.recover()?;
+        let mut buf = serde_json::to_vec(changes)?;
+        buf.push(b'\n');
+        let written = self
+            .file
+            .write_all(&buf)
+            .and_then(|_| self.file.sync_data());
+        if let Err(e) = written {
+            self.dirty = true;
+            let error =
+                anyhow::Error::new(e).context(format!("failed to write segment {}", self.seq));
+            return Err(match self.recover() {
+                Ok(()) => error,
+                Err(recovery) => error.context(format!("failed to recover: {:#}", recovery)),
+            });
+        }
+        self.len += buf.len() as u64;
+        if self.len >= self.max_len {
+            *self = Self::create(&self.dir, self.seq + 1, self.max_len)?;
+        }
+        Ok(())
+    }
+
+    /// Cuts off whatever a failed append left after `len`, or moves on to a
+    /// new segment if it cannot be cut off.
+    fn recover(&mut self) -> anyhow::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if self.file.set_len(self.len).is_err() {
+            *self = Self::create(&self.dir, self.seq + 1, self.max_len)?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Changes are synced before the store lock is released.
+impl Journal for Segments {
+    fn record(&self, changes: &[Change]) -> anyhow::Result<()> {
+        self.writer.lock().unwrap().append(changes)
+    }
+
+    fn replay(&self) -> anyhow::Result<Vec<Change>> {
+        let _writer = self.writer.lock().unwrap();
+        let mut changes = vec![];
+        for seq in segment_numbers(&self.dir)? {
+            if seq >= self.first {
+                changes.extend(read_segment(&segment_path(&self.dir, seq))?);
+            }
+        }
+        Ok(changes)
+    }
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", seq, SEGMENT_EXTENSION))
+}
+
+fn segment_numbers(dir: &Path) -> anyhow::Result<Vec<u64>> {
+    let mut numbers = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+        if let Some(seq) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse().ok())
+        {
+            numbers.push(seq);
+        }
+    }
+    numbers.sort();
+    Ok(numbers)
+}
+
+/// Reads the changes in the complete records of a segment, one record per
+/// line.  A trailing record without a newline was interrupted by a crash part
+/// way through a write and is dropped.
+fn read_segment(path: &Path) -> anyhow::Result<Vec<Change>> {
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("failed to read segment {}", path.display()))?;
+    let mut lines = data.split('\n').collect::<Vec<_>>();
+    if let Some(torn) = lines.pop().filter(|l| !l.is_empty()) {
+        tracing::warn!(
+            "ignoring incomplete record at the end of {}: {}",
+            path.display(),
+            torn
+        );
+    }
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(n, line)| {
+            serde_json::from_str::<Vec<Change>>(line)
+                .with_context(|| format!("corrupt record at {}:{}", path.display(), n + 1))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map(|records| records.into_iter().flatten().collect())
+}
+
+impl MessageRepository for Log {
+    async fn create_message(
+        &self,
+        queue_name: QueueName,
+        req: &CreateMessageRequest,
+    ) -> Result<Message, CreateMessageError> {
+        self.memory.create_message(queue_name, req).await
+    }
+
+    async fn create_messages(
+        &self,
+        queue_name: QueueName,
+        reqs: &[CreateMessageRequest],
+    ) -> Result<Vec<Message>, CreateMessageError> {
+        self.memory.create_messages(queue_name, reqs).await
+    }
+
+    async fn get_message(&self, gmo: GetMessageOptions) -> Result<Message, GetMessageError> {
+        self.memory.get_message(gmo).await
+    }
+
+    async fn get_messages(&self, gmo: GetMessageOptions) -> Result<Vec<Message>, GetMessageError> {
+        self.memory.get_messages(gmo).await
+    }
+
+    async fn acknowledge_messages(
+        &self,
+        req: &AcknowledgeRequest,
+    ) -> Result<Vec<Result<Message, GetMessageError>>, GetMessageError> {
+        self.memory.acknowledge_messages(req).await
+    }
+
+    async fn get_info(&self, gmo: GetMessageOptions) -> Result<QueueSummary, QueueSummaryError> {
+        self.memory.get_info(gmo).await
+    }
+
+    async fn queue_list(&self) -> Result<QueueList, QueueListError> {
+        self.memory.queue_list().await
+    }
+
+    async fn set_dead_letter_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<DeadLetterPolicy>,
+    ) -> Result<(), DeadLetterPolicyError> {
+        self.memory.set_dead_letter_policy(queue_name, policy).await
+    }
+
+    async fn create_queue(
+        &self,
+        queue_name: QueueName,
+        settings: &QueueSettings,
+    ) -> Result<bool, QueueError> {
+        self.memory.create_queue(queue_name, settings).await
+    }
+
+    async fn delete_queue(&self, queue_name: QueueName) -> Result<(), QueueError> {
+        self.memory.delete_queue(queue_name).await
+    }
+
+    async fn purge_queue(&self, queue_name: QueueName) -> Result<usize, QueueError> {
+        self.memory.purge_queue(queue_name).await
+    }
+
+    async fn set_retention_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<RetentionPolicy>,
+    ) -> Result<(), RetentionPolicyError> {
+        self.memory.set_retention_policy(queue_name, policy).await
+    }
+
+    async fn redrive(
+        &self,
+        queue_name: QueueName,
+        req: &RedriveRequest,
+    ) -> Result<Vec<Message>, RedriveError> {
+        self.memory.redrive(queue_name, req).await
+    }
+
+    async fn publish(
+        &self,
+        topic: QueueName,
+        req: &CreateMessageRequest,
+    ) -> Result<Vec<(QueueName, Message)>, CreateMessageError> {
+        self.memory.publish(topic, req).await
+    }
+
+    async fn subscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> Result<(), SubscriptionError> {
+        self.memory.subscribe(topic, queue_name).await
+    }
+
+    async fn unsubscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> Result<(), SubscriptionError> {
+        self.memory.unsubscribe(topic, queue_name).await
+    }
+
+    async fn subscriptions(&self, topic: QueueName) -> Result<Vec<QueueName>, SubscriptionError> {
+        self.memory.subscriptions(topic).await
+    }
+
+    async fn consumer_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> Result<ConsumerGroup, ConsumerGroupError> {
+        self.memory.consumer_group(queue_name, group).await
+    }
+
+    async fn commit_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+        cursor: Option<usize>,
+    ) -> Result<ConsumerGroup, ConsumerGroupError> {
+        self.memory.commit_group(queue_name, group, cursor).await
+    }
+
+    async fn delete_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> Result<(), ConsumerGroupError> {
+        self.memory.delete_group(queue_name, group).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::messages::models::message::{Content, QueueConfig};
+    use mock_instant::global::Instant;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    macro_rules! gmo {
+            ($($arg:tt)*) => {{
+            let string = format!($($arg)*);
+            let gmo: GetMessageOptions = serde_json::from_str::<HashMap<String, String>>(&string)
+            .unwrap()
+            .try_into()
+            .unwrap();
+             gmo
+        }}
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("msg_q-{}", Uuid::new_v4())))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn put(store: &Log, queue: &str, data: &str, expiry: Option<u64>) -> Message {
+        let req = CreateMessageRequest::new(
+            data.to_string(),
+            None,
+            expiry.map(|i| Instant::now() + Duration::from_secs(i)),
+        );
+        store
+            .create_message(queue.to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap()
+    }
+
+    async fn depth(store: &Log, queue_name: &str) -> usize {
+        let gmo = gmo!(r#"{{"action":"query","queue_name":"{}"}}"#, queue_name);
+        store.get_info(gmo).await.unwrap().depth()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_messages_survive_restart() {
+        let dir = TempDir::new();
+        let store = Log::new(&dir.0).await.unwrap();
+        let _msg1 = put(&store, "queue1", "msg1", None).await;
+        let msg2 = put(&store, "queue1", "msg2", None).await;
+        let msg3 = put(&store, "queue1", "msg3", None).await;
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert!(store.get_message(gmo).await.is_ok());
+        let gmo =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let reserved = store.get_message(gmo).await.unwrap();
+        assert_eq!(reserved.mid(), msg2.mid());
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        assert_eq!(depth(&store, "queue1").await, 2);
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg3);
+        let gmo = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        assert!(store.get_message(gmo).await.is_ok());
+        let msg4 = put(&store, "queue1", "msg4", None).await;
+        assert_eq!(msg4.cursor(), 4);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_write_is_rolled_back() {
+        let dir = TempDir::new();
+        fs::create_dir_all(&dir.0).unwrap();
+        let writer = SegmentWriter::create(&dir.0, 0, DEFAULT_SEGMENT_BYTES).unwrap();
+        let segments = Arc::new(Segments {
+            writer: Mutex::new(writer),
+            dir: dir.0.clone(),
+            first: 0,
+        });
+        let store = Log {
+            memory: Memory::new().await.unwrap().with_journal(segments.clone()),
+        };
+        let _msg1 = put(&store, "queue1", "msg1", None).await;
+        // Every write to /dev/full fails, and it cannot be truncated.
+        segments.writer.lock().unwrap().file =
+            OpenOptions::new().append(true).open("/dev/full").unwrap();
+        let req = CreateMessageRequest::new("msg2".to_string(), None, None);
+        let result = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await;
+        assert!(matches!(result, Err(CreateMessageError::Unknown(_))));
+        assert_eq!(depth(&store, "queue1").await, 1);
+        let _msg3 = put(&store, "queue1", "msg3", None).await;
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        assert_eq!(depth(&store, "queue1").await, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_binary_content_survives_restart() {
+        let dir = TempDir::new();
+        let store = Log::new(&dir.0).await.unwrap();
+        let content = Content::binary("image/png".to_string(), vec![0x89, b'P', 0x00, 0xff]);
+        let req = CreateMessageRequest::new(content.clone(), None, None);
+        let msg1 = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap();
+        assert_eq!(msg1.content(), &content);
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_subscriptions_survive_restart() {
+        let dir = TempDir::new();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let store = Log::new(&dir.0).await.unwrap();
+        for queue_name in ["billing", "shipping"] {
+            store
+                .create_queue(name(queue_name), &QueueSettings::default())
+                .await
+                .unwrap();
+            store
+                .subscribe(name("orders"), name(queue_name))
+                .await
+                .unwrap();
+        }
+        store
+            .unsubscribe(name("orders"), name("billing"))
+            .await
+            .unwrap();
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        assert_eq!(
+            store.subscriptions(name("orders")).await.unwrap(),
+            vec![name("shipping")]
+        );
+        let req = CreateMessageRequest::new("order1".to_string(), None, None);
+        let published = store.publish(name("orders"), &req).await.unwrap();
+        assert_eq!(published.len(), 1);
+        assert_eq!(depth(&store, "shipping").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_group_commit_survives_restart() {
+        let dir = TempDir::new();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let store = Log::new(&dir.0).await.unwrap();
+        let _msg1 = put(&store, "queue1", "msg1", None).await;
+        let msg2 = put(&store, "queue1", "msg2", None).await;
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1","group":"group1"}}"#);
+        assert!(store.get_message(gmo.clone()).await.is_ok());
+        store
+            .commit_group(name("queue1"), name("group1"), None)
+            .await
+            .unwrap();
+        assert_eq!(store.get_message(gmo.clone()).await.unwrap(), msg2);
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        let group = store
+            .consumer_group(name("queue1"), name("group1"))
+            .await
+            .unwrap();
+        assert_eq!((group.position(), group.committed()), (1, 1));
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retention_survives_restart() {
+        let dir = TempDir::new();
+        let store = Log::new(&dir.0).await.unwrap();
+        let policy = RetentionPolicy::new(None, Some(2));
+        store
+            .create_queue(
+                "queue1".to_string().try_into().unwrap(),
+                &QueueSettings::default(),
+            )
+            .await
+            .unwrap();
+        store
+            .set_retention_policy("queue1".to_string().try_into().unwrap(), Some(policy))
+            .await
+            .unwrap();
+        let _msg1 = put(&store, "queue1", "msg1", None).await;
+        let msg2 = put(&store, "queue1", "msg2", None).await;
+        let msg3 = put(&store, "queue1", "msg3", None).await;
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert_eq!(
+            store.get_message(gmo.clone()).await.unwrap().mid(),
+            msg2.mid()
+        );
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        assert_eq!(depth(&store, "queue1").await, 1);
+        assert_eq!(store.get_message(gmo).await.unwrap().mid(), msg3.mid());
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1","max_messages":"5"}}"#);
+        let browsed = store.get_messages(gmo).await.unwrap();
+        assert!(browsed.iter().all(Message::is_consumed));
+        let _msg4 = put(&store, "queue1", "msg4", None).await;
+        assert_eq!(depth(&store, "queue1").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_queue_lifecycle_survives_restart() {
+        let dir = TempDir::new();
+        let store = Log::new(&dir.0).await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let settings =
+            QueueSettings::default().with_retention(Some(RetentionPolicy::new(None, Some(1))));
+        assert!(store.create_queue(name("queue1"), &settings).await.unwrap());
+        assert!(store.create_queue(name("queue2"), &settings).await.unwrap());
+        let _msg1 = put(&store, "queue1", "msg1", None).await;
+        let _msg2 = put(&store, "queue2", "msg2", None).await;
+        assert_eq!(store.purge_queue(name("queue1")).await.unwrap(), 1);
+        store.delete_queue(name("queue2")).await.unwrap();
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        assert_eq!(
+            store.queue_list().await.unwrap().0,
+            vec!["queue1".to_string()]
+        );
+        assert_eq!(depth(&store, "queue1").await, 0);
+        let _msg3 = put(&store, "queue1", "msg3", None).await;
+        let _msg4 = put(&store, "queue1", "msg4", None).await;
+        assert_eq!(depth(&store, "queue1").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_queue_config_survives_restart() {
+        let dir = TempDir::new();
+        let store = Log::new(&dir.0).await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let config = QueueConfig::default().with_max_depth(Some(1));
+        let settings = QueueSettings::default().with_config(config);
+        store.create_queue(name("queue1"), &settings).await.unwrap();
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        let _msg1 = put(&store, "queue1", "msg1", None).await;
+        let req = CreateMessageRequest::new("msg2".to_string(), None, None);
+        assert!(matches!(
+            store.create_message(name("queue1"), &req).await,
+            Err(CreateMessageError::QueueFull(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dedup_survives_restart() {
+        let dir = TempDir::new();
+        let store = Log::new(&dir.0).await.unwrap();
+        let queue_name: QueueName = "queue1".to_string().try_into().unwrap();
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None)
+            .with_dedup_key(Some("key1".to_string()));
+        let msg1 = store
+            .create_message(queue_name.clone(), &req)
+            .await
+            .unwrap();
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        let retried = store.create_message(queue_name, &req).await.unwrap();
+        assert_eq!(retried.mid(), msg1.mid());
+        assert_eq!(depth(&store, "queue1").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cursor_survives_empty_queue() {
+        let dir = TempDir::new();
+        let store = Log::new(&dir.0).await.unwrap();
+        let _msg1 = put(&store, "queue1", "msg1", None).await;
+        let _msg2 = put(&store, "queue1", "msg2", None).await;
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert!(store.get_message(gmo.clone()).await.is_ok());
+        assert!(store.get_message(gmo).await.is_ok());
+        drop(store);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        assert_eq!(depth(&store, "queue1").await, 0);
+        let msg3 = put(&store, "queue1", "msg3", None).await;
+        assert_eq!(msg3.cursor(), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_torn_record_is_ignored() {
+        let dir = TempDir::new();
+        let store = Log::new(&dir.0).await.unwrap();
+        let msg1 = put(&store, "queue1", "msg1", None).await;
+        drop(store);
+        let seq = *segment_numbers(&dir.0).unwrap().last().unwrap();
+        // A record cut off after its first change loses all of them.
+        let path = segment_path(&dir.0, seq);
+        let data = fs::read_to_string(&path).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        let last = data.lines().last().unwrap();
+        let torn = format!(
+            "{},{{\"op\":\"create\",\"queue_name\":\"qu",
+            &last[..last.len() - 1]
+        );
+        file.write_all(torn.as_bytes()).unwrap();
+        drop(file);
+
+        let store = Log::new(&dir.0).await.unwrap();
+        assert_eq!(depth(&store, "queue1").await, 1);
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_segments_rotate_and_compact() {
+        let dir = TempDir::new();
+        let store = Log::with_segment_bytes(&dir.0, 256).await.unwrap();
+        for i in 0..10 {
+            put(&store, "queue1", &format!("msg{}", i), None).await;
+        }
+        assert!(segment_numbers(&dir.0).unwrap().len() > 1);
+        drop(store);
+
+        let store = Log::with_segment_bytes(&dir.0, 1024 * 1024).await.unwrap();
+        assert_eq!(segment_numbers(&dir.0).unwrap().len(), 1);
+        assert_eq!(depth(&store, "queue1").await, 10);
+    }
+}
@@ -1,26 +1,81 @@
-use std::collections::{HashMap, VecDeque};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
+#[cfg(test)]
+use mock_instant::global::Instant;
+
+#[cfg(not(test))]
+use std::time::Instant;
+
+use crate::domain::messages::models::message::wall_clock;
 use crate::domain::messages::models::message::{
-    CreateMessageError, GetMessageError, QueueListError, QueueSummaryError,
+    AcknowledgeRequest, ConsumerGroup, CreateMessageRequest, DeadLetterPolicy, GetMessageAction,
+    GetMessageOptions, Message, QueueConfig, QueueList, QueueName, QueueSettings, QueueSummary,
+    RedriveRequest, RetentionPolicy,
 };
 use crate::domain::messages::models::message::{
-    CreateMessageRequest, GetMessageAction, GetMessageOptions, Message, QueueList, QueueName,
-    QueueSummary,
+    ConsumerGroupError, CreateMessageError, DeadLetterPolicyError, GetMessageError, QueueError,
+    QueueListError, QueueSummaryError, RedriveError, RetentionPolicyError, SubscriptionError,
 };
 use crate::domain::messages::ports::MessageRepository;
+use crate::outbound::waiters::Waiters;
 
 #[derive(Debug, Clone)]
 pub struct Memory {
     queues: Arc<Mutex<HashMap<QueueName, Queue>>>,
+    journal: Option<Arc<dyn Journal>>,
+    waiters: Waiters,
+    auto_create: bool,
+    /// Set when a failed rollback left the queues out of step with the
+    /// journal, after which every request is refused.
+    poisoned: Arc<AtomicBool>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Default)]
-struct Queue {
+#[derive(Debug, Clone, Eq, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct Queue {
     messages: VecDeque<Message>,
     max_serial: usize,
+    #[serde(default)]
+    dead_letter: Option<DeadLetterPolicy>,
+    /// The mid and creation time of each message created with a
+    /// deduplication key.
+    #[serde(default)]
+    dedup: HashMap<String, (Uuid, SystemTime)>,
+    /// The topics whose messages are copied into this queue.
+    #[serde(default)]
+    topics: BTreeSet<QueueName>,
+    #[serde(default)]
+    groups: BTreeMap<QueueName, Group>,
+    #[serde(default)]
+    retention: Option<RetentionPolicy>,
+    #[serde(default)]
+    config: QueueConfig,
+}
+
+/// The state of a consumer group.  Only the commit is saved, so a group
+/// whose position is lost with a restart starts again from it.
+#[derive(Debug, Clone, Eq, PartialEq, Default, Serialize, Deserialize)]
+struct Group {
+    committed: usize,
+    #[serde(skip)]
+    position: Option<usize>,
+}
+
+impl Group {
+    fn position(&self) -> usize {
+        self.position.unwrap_or(self.committed)
+    }
 }
 
 impl Queue {
@@ -30,22 +85,466 @@ impl Queue {
         self.messages.push_back(message.clone());
         message
     }
+
+    /// The message created with `req`'s deduplication key, if `req` is a
+    /// duplicate of it.
+    fn duplicate(&self, req: &CreateMessageRequest) -> Option<Message> {
+        let (mid, created_at) = self.dedup.get(req.dedup_key()?)?;
+        if !req.within_dedup_window(*created_at) {
+            return None;
+        }
+        let original = self.messages.iter().find(|m| m.mid() == mid).cloned();
+        Some(original.unwrap_or_else(|| req.duplicate_of(*mid, *created_at)))
+    }
+
+    fn remember(&mut self, dedup_key: Option<String>, message: &Message) {
+        if let Some(key) = dedup_key {
+            self.dedup
+                .insert(key, (*message.mid(), message.enqueued_at()));
+        }
+    }
+
+    fn restore_message(&mut self, message: Message) {
+        self.max_serial = self.max_serial.max(message.cursor());
+        self.messages.push_back(message);
+    }
+
+    fn replace_message(&mut self, message: Message) {
+        if let Some(existing) = self.messages.iter_mut().find(|m| m.mid() == message.mid()) {
+            *existing = message;
+        }
+    }
+
+    fn remove_message(&mut self, mid: &Uuid) -> Option<Message> {
+        let idx = self.messages.iter().position(|m| m.mid() == mid)?;
+        self.messages.remove(idx)
+    }
+
+    /// Removes the messages which the retention policy no longer keeps, and
+    /// gives their mids.
+    fn trim(&mut self) -> Vec<Uuid> {
+        let Some(policy) = &self.retention else {
+            return vec![];
+        };
+        let excess = policy
+            .max_messages()
+            .map_or(0, |max| self.messages.len().saturating_sub(max));
+        let mut trimmed = vec![];
+        let mut idx = 0;
+        self.messages.retain(|m| {
+            let keep = idx >= excess && !policy.outlived(m);
+            idx += 1;
+            if !keep {
+                trimmed.push(*m.mid());
+            }
+            keep
+        });
+        trimmed
+    }
+}
+
+/// A change to the state of the store.  Changes are handed to the `Journal`
+/// (if any) while the store is locked, so replaying them in order with
+/// `Memory::apply` rebuilds the same state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(crate) enum Change {
+    Create {
+        queue_name: QueueName,
+        message: Message,
+        #[serde(default)]
+        dedup_key: Option<String>,
+    },
+    Reserve {
+        queue_name: QueueName,
+        message: Message,
+    },
+    Return {
+        queue_name: QueueName,
+        message: Message,
+    },
+    Extend {
+        queue_name: QueueName,
+        message: Message,
+    },
+    Consume {
+        queue_name: QueueName,
+        message: Message,
+    },
+    Get {
+        queue_name: QueueName,
+        mid: Uuid,
+    },
+    Confirm {
+        queue_name: QueueName,
+        mid: Uuid,
+    },
+    Expire {
+        queue_name: QueueName,
+        mid: Uuid,
+    },
+    DeadLetter {
+        queue_name: QueueName,
+        dead_letter_queue: QueueName,
+        message: Message,
+    },
+    SetDeadLetter {
+        queue_name: QueueName,
+        policy: Option<DeadLetterPolicy>,
+    },
+    SetRetention {
+        queue_name: QueueName,
+        policy: Option<RetentionPolicy>,
+    },
+    Configure {
+        queue_name: QueueName,
+        settings: QueueSettings,
+    },
+    DeleteQueue {
+        queue_name: QueueName,
+    },
+    Purge {
+        queue_name: QueueName,
+    },
+    Trim {
+        queue_name: QueueName,
+        mids: Vec<Uuid>,
+    },
+    Redrive {
+        queue_name: QueueName,
+        target: QueueName,
+        message: Message,
+    },
+    Subscribe {
+        topic: QueueName,
+        queue_name: QueueName,
+    },
+    Unsubscribe {
+        topic: QueueName,
+        queue_name: QueueName,
+    },
+    Commit {
+        queue_name: QueueName,
+        group: QueueName,
+        committed: usize,
+    },
+    DeleteGroup {
+        queue_name: QueueName,
+        group: QueueName,
+    },
+    Restore {
+        queue_name: QueueName,
+        queue: Queue,
+    },
+}
+
+impl Change {
+    /// The queue in which the change may have made a message available.
+    fn readied_queue(&self) -> Option<&QueueName> {
+        match self {
+            Change::Create { queue_name, .. } | Change::Return { queue_name, .. } => {
+                Some(queue_name)
+            }
+            Change::DeadLetter {
+                dead_letter_queue, ..
+            } => Some(dead_letter_queue),
+            Change::Redrive { target, .. } => Some(target),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) trait Journal: Debug + Send + Sync {
+    fn record(&self, changes: &[Change]) -> anyhow::Result<()>;
+
+    /// Every change recorded so far, in order.
+    fn replay(&self) -> anyhow::Result<Vec<Change>>;
+}
+
+/// The contents of every queue at `saved_at`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    saved_at: SystemTime,
+    queues: HashMap<QueueName, Queue>,
 }
 
 impl Memory {
     pub async fn new() -> Result<Memory, anyhow::Error> {
         let queues = Arc::new(Mutex::new(HashMap::new()));
-        Ok(Self { queues })
+        Ok(Self {
+            queues,
+            journal: None,
+            waiters: Waiters::default(),
+            auto_create: true,
+            poisoned: Arc::default(),
+        })
+    }
+
+    /// Loads the queues saved by `save_snapshot`, or starts empty if there is
+    /// no snapshot at `path`.  Reservations and expiries keep the time they had
+    /// remaining when the snapshot was taken.
+    pub async fn from_snapshot(path: impl AsRef<Path>) -> Result<Memory, anyhow::Error> {
+        let path = path.as_ref();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Self::new().await,
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to open snapshot {}", path.display()))
+            }
+        };
+        let mut snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+        let downtime = wall_clock::to_system_time(Instant::now())
+            .duration_since(snapshot.saved_at)
+            .unwrap_or_default();
+        for queue in snapshot.queues.values_mut() {
+            queue.messages.iter_mut().for_each(|m| m.postpone(downtime));
+        }
+        Ok(Self {
+            queues: Arc::new(Mutex::new(snapshot.queues)),
+            journal: None,
+            waiters: Waiters::default(),
+            auto_create: true,
+            poisoned: Arc::default(),
+        })
+    }
+
+    /// Sets whether a message for a queue which does not exist makes the
+    /// queue, as it does unless told otherwise, or is refused.
+    pub fn with_auto_create(self, auto_create: bool) -> Self {
+        Self {
+            auto_create,
+            ..self
+        }
+    }
+
+    /// Writes every queue to `path`, replacing any previous snapshot.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let snapshot = Snapshot {
+            saved_at: wall_clock::to_system_time(Instant::now()),
+            queues: self.queues.lock().unwrap().clone(),
+        };
+        let tmp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(
+            File::create(&tmp)
+                .with_context(|| format!("failed to create snapshot {}", tmp.display()))?,
+        );
+        serde_json::to_writer(&mut writer, &snapshot)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("failed to replace snapshot {}", path.display()))
+    }
+
+    /// Saves a snapshot to `path` every `interval` until the task is aborted.
+    pub fn save_snapshot_every(&self, path: PathBuf, interval: Duration) -> JoinHandle<()> {
+        let memory = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = memory.save_snapshot(&path) {
+                    tracing::error!("failed to save snapshot: {:?}", e);
+                }
+            }
+        })
+    }
+
+    pub(crate) fn with_journal(self, journal: Arc<dyn Journal>) -> Self {
+        Self {
+            journal: Some(journal),
+            ..self
+        }
+    }
+
+    pub(crate) fn apply(&self, change: Change) {
+        Self::apply_to(&mut self.queues.lock().unwrap(), change);
+    }
+
+    fn apply_to(queues: &mut HashMap<QueueName, Queue>, change: Change) {
+        match change {
+            Change::Create {
+                queue_name,
+                message,
+                dedup_key,
+            } => {
+                let queue = queues.entry(queue_name).or_default();
+                queue.remember(dedup_key, &message);
+                queue.restore_message(message);
+            }
+            Change::Reserve {
+                queue_name,
+                message,
+            }
+            | Change::Return {
+                queue_name,
+                message,
+            }
+            | Change::Extend {
+                queue_name,
+                message,
+            }
+            | Change::Consume {
+                queue_name,
+                message,
+            } => {
+                if let Some(queue) = queues.get_mut(&queue_name) {
+                    queue.replace_message(message)
+                }
+            }
+            Change::Get { queue_name, mid }
+            | Change::Confirm { queue_name, mid }
+            | Change::Expire { queue_name, mid } => {
+                if let Some(queue) = queues.get_mut(&queue_name) {
+                    queue.remove_message(&mid);
+                }
+            }
+            Change::DeadLetter {
+                queue_name,
+                dead_letter_queue: target,
+                message,
+            }
+            | Change::Redrive {
+                queue_name,
+                target,
+                message,
+            } => {
+                if let Some(queue) = queues.get_mut(&queue_name) {
+                    queue.remove_message(message.mid());
+                }
+                queues.entry(target).or_default().restore_message(message);
+            }
+            Change::SetDeadLetter { queue_name, policy } => {
+                queues.entry(queue_name).or_default().dead_letter = policy;
+            }
+            Change::SetRetention { queue_name, policy } => {
+                Self::set_retention(queues.entry(queue_name).or_default(), policy);
+            }
+            Change::Configure {
+                queue_name,
+                settings,
+            } => {
+                Self::configure(queues.entry(queue_name).or_default(), &settings);
+            }
+            Change::DeleteQueue { queue_name } => {
+                queues.remove(&queue_name);
+            }
+            Change::Purge { queue_name } => {
+                if let Some(queue) = queues.get_mut(&queue_name) {
+                    queue.messages.clear();
+                }
+            }
+            Change::Trim { queue_name, mids } => {
+                if let Some(queue) = queues.get_mut(&queue_name) {
+                    queue.messages.retain(|m| !mids.contains(m.mid()));
+                }
+            }
+            Change::Subscribe { topic, queue_name } => {
+                queues.entry(queue_name).or_default().topics.insert(topic);
+            }
+            Change::Unsubscribe { topic, queue_name } => {
+                if let Some(queue) = queues.get_mut(&queue_name) {
+                    queue.topics.remove(&topic);
+                }
+            }
+            Change::Commit {
+                queue_name,
+                group,
+                committed,
+            } => {
+                queues.entry(queue_name).or_default().groups.insert(
+                    group,
+                    Group {
+                        committed,
+                        position: None,
+                    },
+                );
+            }
+            Change::DeleteGroup { queue_name, group } => {
+                if let Some(queue) = queues.get_mut(&queue_name) {
+                    queue.groups.remove(&group);
+                }
+            }
+            Change::Restore { queue_name, queue } => {
+                queues.insert(queue_name, queue);
+            }
+        }
+    }
+
+    /// Changes which recreate the current state of every queue from nothing.
+    pub(crate) fn checkpoint(&self) -> Vec<Change> {
+        let queues = self.queues.lock().unwrap();
+        queues
+            .iter()
+            .map(|(queue_name, queue)| Change::Restore {
+                queue_name: queue_name.clone(),
+                queue: queue.clone(),
+            })
+            .collect()
+    }
+
+    /// Locks the queues, unless they no longer match the journal.
+    fn lock(&self) -> anyhow::Result<MutexGuard<'_, HashMap<QueueName, Queue>>> {
+        let queues = self.queues.lock().unwrap();
+        if self.poisoned.load(Ordering::SeqCst) {
+            anyhow::bail!("the store no longer matches its journal and must be restarted");
+        }
+        Ok(queues)
+    }
+
+    /// Hands `changes`, already made to `queues`, to the journal.  If it
+    /// cannot take them, `queues` is rebuilt from what it has recorded, so
+    /// the store never holds a change which its journal lost; if that fails
+    /// too, the store is poisoned.  Group positions are not recorded and
+    /// restart from their commits.
+    fn record(
+        &self,
+        queues: &mut HashMap<QueueName, Queue>,
+        changes: &[Change],
+    ) -> anyhow::Result<()> {
+        if let Some(journal) = self.journal.as_ref().filter(|_| !changes.is_empty()) {
+            if let Err(e) = journal.record(changes) {
+                match journal.replay() {
+                    Ok(recorded) => {
+                        queues.clear();
+                        for change in recorded {
+                            Self::apply_to(queues, change);
+                        }
+                    }
+                    Err(replay) => {
+                        tracing::error!("failed to roll back changes: {:?}", replay);
+                        self.poisoned.store(true, Ordering::SeqCst);
+                    }
+                }
+                return Err(e);
+            }
+        }
+        changes
+            .iter()
+            .filter_map(Change::readied_queue)
+            .for_each(|queue_name| self.waiters.wake(queue_name));
+        Ok(())
     }
 
     fn get_message_impl(&self, gmo: &GetMessageOptions, queue: &mut Queue, idx: usize) -> Message {
         match gmo.action() {
-            GetMessageAction::Browse => queue.messages.get(idx).unwrap().clone(),
+            GetMessageAction::Browse | GetMessageAction::Query => {
+                queue.messages.get(idx).unwrap().clone()
+            }
+            GetMessageAction::Get | GetMessageAction::Confirm if queue.retention.is_some() => {
+                let msg = queue.messages.get_mut(idx).unwrap();
+                msg.consume();
+                msg.clone()
+            }
             GetMessageAction::Get => queue.messages.remove(idx).unwrap(),
             GetMessageAction::Confirm => queue.messages.remove(idx).unwrap(),
             GetMessageAction::Reserve => {
                 let msg = queue.messages.get_mut(idx).unwrap();
                 msg.set_reservation(gmo.reservation());
+                msg.set_receipt(Some(Uuid::new_v4()));
                 msg.clone()
             }
             GetMessageAction::Return => {
@@ -53,39 +552,322 @@ impl Memory {
                 msg.remove_reservation();
                 msg.clone()
             }
-            GetMessageAction::Query => todo!(),
+            GetMessageAction::Extend => {
+                let msg = queue.messages.get_mut(idx).unwrap();
+                msg.extend_reservation(gmo.reservation());
+                msg.clone()
+            }
         }
     }
 
-    fn purge_expired_messages(&self) -> usize {
-        let mut queues = self.queues.lock().unwrap();
-        queues
-            .values_mut()
-            .map(|q| {
-                let depth = q.messages.len();
-                q.messages.retain(|m| !m.is_expired());
-                depth - q.messages.len()
+    fn change_for(gmo: &GetMessageOptions, message: &Message) -> Option<Change> {
+        let queue_name = gmo.queue_name().clone();
+        match gmo.action() {
+            GetMessageAction::Get | GetMessageAction::Confirm if message.is_consumed() => {
+                Some(Change::Consume {
+                    queue_name,
+                    message: message.clone(),
+                })
+            }
+            GetMessageAction::Get => Some(Change::Get {
+                queue_name,
+                mid: *message.mid(),
+            }),
+            GetMessageAction::Confirm => Some(Change::Confirm {
+                queue_name,
+                mid: *message.mid(),
+            }),
+            GetMessageAction::Reserve => Some(Change::Reserve {
+                queue_name,
+                message: message.clone(),
+            }),
+            GetMessageAction::Return => Some(Change::Return {
+                queue_name,
+                message: message.clone(),
+            }),
+            GetMessageAction::Extend => Some(Change::Extend {
+                queue_name,
+                message: message.clone(),
+            }),
+            GetMessageAction::Browse | GetMessageAction::Query => None,
+        }
+    }
+
+    /// Moves a message to the end of `dead_letter_queue`, unreserved and with a
+    /// new cursor.  The message stays where it is if `dead_letter_queue` does
+    /// not exist and the store does not make queues.
+    fn dead_letter(
+        &self,
+        queues: &mut HashMap<QueueName, Queue>,
+        queue_name: &QueueName,
+        mid: &Uuid,
+        dead_letter_queue: &QueueName,
+    ) -> Option<Change> {
+        if !self.auto_create && !queues.contains_key(dead_letter_queue) {
+            return None;
+        }
+        let mut message = queues.get_mut(queue_name)?.remove_message(mid)?;
+        message.remove_reservation();
+        let message = queues
+            .entry(dead_letter_queue.clone())
+            .or_default()
+            .add_message(message);
+        Some(Change::DeadLetter {
+            queue_name: queue_name.clone(),
+            dead_letter_queue: dead_letter_queue.clone(),
+            message,
+        })
+    }
+
+    /// Dead-letters messages whose last reservation lapsed after they had
+    /// used up their deliveries.
+    fn dead_letter_lapsed(&self, queues: &mut HashMap<QueueName, Queue>) -> Vec<Change> {
+        let lapsed = queues
+            .iter()
+            .filter_map(|(queue_name, queue)| {
+                Some((queue_name, queue, queue.dead_letter.as_ref()?))
+            })
+            .flat_map(|(queue_name, queue, policy)| {
+                queue
+                    .messages
+                    .iter()
+                    .filter(|m| m.reservation_lapsed() && policy.exhausted(m))
+                    .map(|m| (queue_name.clone(), *m.mid(), policy.queue_name().clone()))
             })
-            .sum()
+            .collect::<Vec<_>>();
+        lapsed
+            .into_iter()
+            .filter_map(|(queue_name, mid, dead_letter_queue)| {
+                self.dead_letter(queues, &queue_name, &mid, &dead_letter_queue)
+            })
+            .collect()
     }
-}
 
-impl MessageRepository for Memory {
-    async fn get_message(&self, gmo: GetMessageOptions) -> Result<Message, GetMessageError> {
-        let mut queues = self.queues.lock().unwrap();
+    fn take_messages(
+        &self,
+        queues: &mut HashMap<QueueName, Queue>,
+        gmo: &GetMessageOptions,
+        limit: usize,
+        changes: &mut Vec<Change>,
+    ) -> Result<Vec<Message>, GetMessageError> {
         let queue = queues
             .get_mut(gmo.queue_name())
             .ok_or(())
             .map_err(|_| GetMessageError::NoMessage(format!("no queue {}", gmo.queue_name())))?;
-        let idx = queue
-            .messages
+        let mut gmo = gmo
+            .clone()
+            .with_default_reservation(queue.config.reservation())?;
+        if let Some(group) = gmo.group() {
+            let position = queue.groups.entry(group.clone()).or_default().position();
+            gmo = gmo.with_cursor(Some(position));
+        }
+        let gmo = &gmo;
+        let mids = gmo
+            .select(&queue.messages, limit)
+            .into_iter()
+            .map(|(_, m)| *m.mid())
+            .collect::<Vec<_>>();
+        if mids.is_empty() {
+            return Err(gmo.no_match(queue.messages.iter()));
+        }
+
+        let mut messages = Vec::with_capacity(mids.len());
+        for mid in mids {
+            let queue = queues.get_mut(gmo.queue_name()).unwrap();
+            let idx = queue.messages.iter().position(|m| m.mid() == &mid).unwrap();
+            let message = self.get_message_impl(gmo, queue, idx);
+            changes.extend(Self::change_for(gmo, &message));
+            if gmo.action() == GetMessageAction::Return {
+                if let Some(policy) = queue.dead_letter.clone().filter(|p| p.exhausted(&message)) {
+                    changes.extend(self.dead_letter(
+                        queues,
+                        gmo.queue_name(),
+                        message.mid(),
+                        policy.queue_name(),
+                    ));
+                }
+            }
+            messages.push(message);
+        }
+        if let (Some(group), Some(last)) = (gmo.group(), messages.last()) {
+            let queue = queues.get_mut(gmo.queue_name()).unwrap();
+            queue.groups.get_mut(group).unwrap().position = Some(last.cursor());
+        }
+        Ok(messages)
+    }
+
+    /// Takes up to `limit` messages matching `gmo`, waiting as long as it
+    /// allows for the first to arrive.
+    async fn take_waiting(
+        &self,
+        gmo: &GetMessageOptions,
+        limit: usize,
+    ) -> Result<Vec<Message>, GetMessageError> {
+        self.waiters
+            .wait_for(gmo.queue_name(), gmo.wait(), || {
+                let mut queues = self.lock()?;
+                let mut changes = Self::trim_retained(&mut queues);
+                changes.extend(self.dead_letter_lapsed(&mut queues));
+                let result = self.take_messages(&mut queues, gmo, limit, &mut changes);
+                self.record(&mut queues, &changes)?;
+                result
+            })
+            .await
+    }
+
+    /// `reqs` as `queue_name` takes them, or why it refuses them.  A queue
+    /// which does not exist yet is judged by the default config it would be
+    /// made with, if the store makes queues at all.
+    fn admit_messages(
+        &self,
+        queues: &HashMap<QueueName, Queue>,
+        queue_name: &QueueName,
+        reqs: &[CreateMessageRequest],
+    ) -> Result<Vec<CreateMessageRequest>, CreateMessageError> {
+        if reqs.is_empty() {
+            return Ok(vec![]);
+        }
+        let defaults = QueueConfig::default();
+        let (config, depth, count) = match queues.get(queue_name) {
+            Some(queue) => (
+                &queue.config,
+                queue.messages.iter().filter(|m| !m.is_consumed()).count(),
+                reqs.iter()
+                    .filter(|req| queue.duplicate(req).is_none())
+                    .count(),
+            ),
+            None if self.auto_create => (&defaults, 0, reqs.len()),
+            None => {
+                return Err(CreateMessageError::NoQueue(format!(
+                    "no queue {}",
+                    queue_name
+                )))
+            }
+        };
+        config.admit(queue_name, depth, count)?;
+        reqs.iter()
+            .map(|req| config.prepare(queue_name, req))
+            .collect()
+    }
+
+    /// Adds `reqs` to the end of `queue_name`, or hands back the original
+    /// message for any which duplicates an earlier one.
+    fn add_messages(
+        queues: &mut HashMap<QueueName, Queue>,
+        queue_name: &QueueName,
+        reqs: &[CreateMessageRequest],
+        changes: &mut Vec<Change>,
+    ) -> Vec<Message> {
+        if reqs.is_empty() {
+            return Vec::new();
+        }
+        let entry = queues.entry(queue_name.clone()).or_default();
+        if let Some(req) = reqs.iter().find(|req| req.dedup_key().is_some()) {
+            entry
+                .dedup
+                .retain(|_, (_, created_at)| req.within_dedup_window(*created_at));
+        }
+        let mut messages = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            if let Some(original) = entry.duplicate(req) {
+                messages.push(original);
+                continue;
+            }
+            let message = entry.add_message(req.message(Uuid::new_v4()));
+            entry.remember(req.dedup_key().cloned(), &message);
+            changes.push(Change::Create {
+                queue_name: queue_name.clone(),
+                message: message.clone(),
+                dedup_key: req.dedup_key().cloned(),
+            });
+            messages.push(message);
+        }
+        messages
+    }
+
+    /// The queues subscribed to `topic`, in name order.
+    fn subscribers(queues: &HashMap<QueueName, Queue>, topic: &QueueName) -> Vec<QueueName> {
+        let mut subscribers = queues
             .iter()
-            .position(|e| gmo.matches(e))
-            .ok_or(())
-            .map_err(|_| GetMessageError::NoMessage(format!("{}", gmo.queue_name(),)))?;
-        //tracing::info!("removing: {}", remove);
+            .filter(|(_, queue)| queue.topics.contains(topic))
+            .map(|(queue_name, _)| queue_name.clone())
+            .collect::<Vec<_>>();
+        subscribers.sort();
+        subscribers
+    }
+
+    /// Trims every queue which retains its messages to its policy.
+    fn trim_retained(queues: &mut HashMap<QueueName, Queue>) -> Vec<Change> {
+        queues
+            .iter_mut()
+            .filter_map(|(queue_name, queue)| {
+                let mids = queue.trim();
+                (!mids.is_empty()).then(|| Change::Trim {
+                    queue_name: queue_name.clone(),
+                    mids,
+                })
+            })
+            .collect()
+    }
+
+    /// Without a policy, messages are no longer kept once consumed.
+    fn set_retention(queue: &mut Queue, policy: Option<RetentionPolicy>) {
+        if policy.is_none() {
+            queue.messages.retain(|m| !m.is_consumed());
+        }
+        queue.retention = policy;
+    }
+
+    fn configure(queue: &mut Queue, settings: &QueueSettings) {
+        queue.dead_letter = settings.dead_letter().cloned();
+        queue.config = settings.config().clone();
+        Self::set_retention(queue, settings.retention().cloned());
+    }
+
+    fn purge_expired_messages(queues: &mut HashMap<QueueName, Queue>) -> Vec<Change> {
+        let mut changes = vec![];
+        for (queue_name, queue) in queues.iter_mut() {
+            queue.messages.retain(|m| {
+                let expired = m.is_expired();
+                if expired {
+                    changes.push(Change::Expire {
+                        queue_name: queue_name.clone(),
+                        mid: *m.mid(),
+                    });
+                }
+                !expired
+            });
+        }
+        changes
+    }
+}
+
+impl MessageRepository for Memory {
+    async fn get_message(&self, gmo: GetMessageOptions) -> Result<Message, GetMessageError> {
+        let mut messages = self.take_waiting(&gmo, 1).await?;
+        Ok(messages.remove(0))
+    }
+
+    async fn get_messages(&self, gmo: GetMessageOptions) -> Result<Vec<Message>, GetMessageError> {
+        let limit = gmo.max_messages().unwrap_or(1);
+        self.take_waiting(&gmo, limit).await
+    }
 
-        Ok(self.get_message_impl(&gmo, queue, idx))
+    async fn acknowledge_messages(
+        &self,
+        req: &AcknowledgeRequest,
+    ) -> Result<Vec<Result<Message, GetMessageError>>, GetMessageError> {
+        let mut queues = self.lock()?;
+        let mut changes = self.dead_letter_lapsed(&mut queues);
+        let results = req
+            .options()
+            .map(|gmo| {
+                self.take_messages(&mut queues, &gmo, 1, &mut changes)
+                    .map(|mut messages| messages.remove(0))
+            })
+            .collect();
+        self.record(&mut queues, &changes)?;
+        Ok(results)
     }
 
     async fn create_message(
@@ -93,97 +875,379 @@ impl MessageRepository for Memory {
         queue_name: QueueName,
         req: &CreateMessageRequest,
     ) -> Result<Message, CreateMessageError> {
-        self.purge_expired_messages();
-        let mid = Uuid::new_v4();
-        let mut queues = self.queues.lock().unwrap();
-        let content = req.content().clone();
-        let message = Message::new(mid, req.cid().copied(), content, req.expiry().cloned());
-        let entry = queues.entry(queue_name.clone()).or_default();
-        Ok(entry.add_message(message))
+        let mut messages = self
+            .create_messages(queue_name, std::slice::from_ref(req))
+            .await?;
+        Ok(messages.remove(0))
+    }
+
+    async fn create_messages(
+        &self,
+        queue_name: QueueName,
+        reqs: &[CreateMessageRequest],
+    ) -> Result<Vec<Message>, CreateMessageError> {
+        let mut queues = self.lock()?;
+        let mut changes = Self::purge_expired_messages(&mut queues);
+        changes.extend(self.dead_letter_lapsed(&mut queues));
+        let admitted = self.admit_messages(&queues, &queue_name, reqs);
+        let messages = match admitted {
+            Ok(reqs) => Self::add_messages(&mut queues, &queue_name, &reqs, &mut changes),
+            Err(e) => {
+                self.record(&mut queues, &changes)?;
+                return Err(e);
+            }
+        };
+        changes.extend(Self::trim_retained(&mut queues));
+        self.record(&mut queues, &changes)?;
+        Ok(messages)
     }
 
     async fn queue_list(&self) -> Result<QueueList, QueueListError> {
-        let queues = self.queues.lock().unwrap();
+        let queues = self.lock()?;
 
         Ok(QueueList(
-            queues
-                .iter()
-                .map(|(k, _)| k.to_string())
-                .collect::<Vec<_>>(),
+            queues.keys().map(|k| k.to_string()).collect::<Vec<_>>(),
         ))
     }
 
     async fn get_info(&self, gmo: GetMessageOptions) -> Result<QueueSummary, QueueSummaryError> {
-        let queues = self.queues.lock().unwrap();
+        let queues = self.lock()?;
         let queue = queues
             .get(gmo.queue_name())
             .ok_or(())
             .map_err(|_| QueueSummaryError::NoQueue(format!("no queue {}", gmo.queue_name())))?;
-        Ok(QueueSummary::new(gmo.queue_name(), queue.messages.len()))
+        Ok(QueueSummary::new(
+            gmo.queue_name(),
+            queue.messages.iter().filter(|m| !m.is_consumed()).count(),
+        ))
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use mock_instant::global::{Instant, MockClock};
-    use std::time::Duration;
-
-    use serde_json;
 
-    macro_rules! gmo {
-            ($($arg:tt)*) => {{
-            let string = format!($($arg)*);
-            let gmo: GetMessageOptions = serde_json::from_str::<HashMap<String, String>>(&string)
-            .unwrap()
-            .try_into()
-            .unwrap();
-             gmo
-        }}
+    async fn create_queue(
+        &self,
+        queue_name: QueueName,
+        settings: &QueueSettings,
+    ) -> Result<bool, QueueError> {
+        let mut queues = self.lock()?;
+        let created = !queues.contains_key(&queue_name);
+        Self::configure(queues.entry(queue_name.clone()).or_default(), settings);
+        let mut changes = vec![Change::Configure {
+            queue_name,
+            settings: settings.clone(),
+        }];
+        changes.extend(Self::trim_retained(&mut queues));
+        self.record(&mut queues, &changes)?;
+        Ok(created)
     }
 
-    async fn put(
-        store: &mut Memory,
-        queue: &str,
-        data: &str,
-        cid: Option<&str>,
-        expiry: Option<u64>,
-    ) -> Result<Message, CreateMessageError> {
-        let req = CreateMessageRequest::new(
-            data.to_string(),
-            cid.map(|s| uuid::Uuid::try_parse(s).unwrap()),
-            expiry.map(|i| Instant::now() + Duration::from_secs(i)),
-        );
-        store
-            .create_message(queue.to_string().try_into().unwrap(), &req)
-            .await
+    async fn delete_queue(&self, queue_name: QueueName) -> Result<(), QueueError> {
+        let mut queues = self.lock()?;
+        if queues.remove(&queue_name).is_none() {
+            return Err(QueueError::NoQueue(format!("no queue {}", queue_name)));
+        }
+        self.record(&mut queues, &[Change::DeleteQueue { queue_name }])?;
+        Ok(())
     }
 
-    async fn depth(store: &Memory, queue_name: &str) -> usize {
-        let gmo = gmo!(r#"{{"action":"query","queue_name":"{}"}}"#, queue_name);
-        let summary = store.get_info(gmo).await.unwrap();
-        summary.depth()
+    async fn purge_queue(&self, queue_name: QueueName) -> Result<usize, QueueError> {
+        let mut queues = self.lock()?;
+        let queue = queues
+            .get_mut(&queue_name)
+            .ok_or_else(|| QueueError::NoQueue(format!("no queue {}", queue_name)))?;
+        let purged = queue.messages.len();
+        queue.messages.clear();
+        self.record(&mut queues, &[Change::Purge { queue_name }])?;
+        Ok(purged)
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_create_store() {
-        let store = Memory::new().await;
-        assert!(store.is_ok(), "{:?}", store);
+    async fn set_dead_letter_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<DeadLetterPolicy>,
+    ) -> Result<(), DeadLetterPolicyError> {
+        let mut queues = self.lock()?;
+        let queue = queues
+            .get_mut(&queue_name)
+            .ok_or_else(|| DeadLetterPolicyError::NoQueue(format!("no queue {}", queue_name)))?;
+        queue.dead_letter = policy.clone();
+        self.record(&mut queues, &[Change::SetDeadLetter { queue_name, policy }])?;
+        Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_empty_store() {
-        let store = Memory::new().await.unwrap();
-        let queue_list = store.queue_list().await.unwrap();
-        assert!(queue_list.0.is_empty(), "{:?}", queue_list);
-
-        let gmo = gmo!(r#"{{"action":"query","queue_name":"test"}}"#,);
-        let summary = store.get_info(gmo).await;
-        assert!(summary.is_err(), "{:?}", summary);
+    async fn set_retention_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<RetentionPolicy>,
+    ) -> Result<(), RetentionPolicyError> {
+        let mut queues = self.lock()?;
+        let queue = queues
+            .get_mut(&queue_name)
+            .ok_or_else(|| RetentionPolicyError::NoQueue(format!("no queue {}", queue_name)))?;
+        Self::set_retention(queue, policy.clone());
+        let mut changes = vec![Change::SetRetention { queue_name, policy }];
+        changes.extend(Self::trim_retained(&mut queues));
+        self.record(&mut queues, &changes)?;
+        Ok(())
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_create_message() {
+    async fn redrive(
+        &self,
+        queue_name: QueueName,
+        req: &RedriveRequest,
+    ) -> Result<Vec<Message>, RedriveError> {
+        let mut queues = self.lock()?;
+        let mut changes = Self::purge_expired_messages(&mut queues);
+        changes.extend(self.dead_letter_lapsed(&mut queues));
+        let missing = if !queues.contains_key(&queue_name) {
+            Some(&queue_name)
+        } else if !self.auto_create && !queues.contains_key(req.target()) {
+            Some(req.target())
+        } else {
+            None
+        };
+        if let Some(missing) = missing {
+            self.record(&mut queues, &changes)?;
+            return Err(RedriveError::NoQueue(format!("no queue {}", missing)));
+        }
+        let queue = queues.get_mut(&queue_name).unwrap();
+        let (moved, kept): (VecDeque<_>, VecDeque<_>) =
+            queue.messages.drain(..).partition(|m| req.matches(m));
+        queue.messages = kept;
+        let target = queues.entry(req.target().clone()).or_default();
+        let moved = moved
+            .into_iter()
+            .map(|mut message| {
+                message.redrive();
+                target.add_message(message)
+            })
+            .collect::<Vec<_>>();
+        changes.extend(moved.iter().map(|message| Change::Redrive {
+            queue_name: queue_name.clone(),
+            target: req.target().clone(),
+            message: message.clone(),
+        }));
+        changes.extend(Self::trim_retained(&mut queues));
+        self.record(&mut queues, &changes)?;
+        Ok(moved)
+    }
+
+    async fn publish(
+        &self,
+        topic: QueueName,
+        req: &CreateMessageRequest,
+    ) -> Result<Vec<(QueueName, Message)>, CreateMessageError> {
+        let mut queues = self.lock()?;
+        let mut changes = Self::purge_expired_messages(&mut queues);
+        changes.extend(self.dead_letter_lapsed(&mut queues));
+        // Every subscriber must take the message before any is given it.
+        let admitted = Self::subscribers(&queues, &topic)
+            .into_iter()
+            .map(|queue_name| {
+                self.admit_messages(&queues, &queue_name, std::slice::from_ref(req))
+                    .map(|reqs| (queue_name, reqs))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let admitted = match admitted {
+            Ok(admitted) => admitted,
+            Err(e) => {
+                self.record(&mut queues, &changes)?;
+                return Err(e);
+            }
+        };
+        let published = admitted
+            .into_iter()
+            .map(|(queue_name, reqs)| {
+                let mut messages =
+                    Self::add_messages(&mut queues, &queue_name, &reqs, &mut changes);
+                (queue_name, messages.remove(0))
+            })
+            .collect();
+        changes.extend(Self::trim_retained(&mut queues));
+        self.record(&mut queues, &changes)?;
+        Ok(published)
+    }
+
+    async fn subscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> Result<(), SubscriptionError> {
+        let mut queues = self.lock()?;
+        queues
+            .get_mut(&queue_name)
+            .ok_or_else(|| SubscriptionError::NoQueue(format!("no queue {}", queue_name)))?
+            .topics
+            .insert(topic.clone());
+        self.record(&mut queues, &[Change::Subscribe { topic, queue_name }])?;
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> Result<(), SubscriptionError> {
+        let mut queues = self.lock()?;
+        let subscribed = queues
+            .get_mut(&queue_name)
+            .is_some_and(|queue| queue.topics.remove(&topic));
+        if !subscribed {
+            return Err(SubscriptionError::NoSubscription(format!(
+                "{} is not subscribed to {}",
+                queue_name, topic
+            )));
+        }
+        self.record(&mut queues, &[Change::Unsubscribe { topic, queue_name }])?;
+        Ok(())
+    }
+
+    async fn subscriptions(&self, topic: QueueName) -> Result<Vec<QueueName>, SubscriptionError> {
+        let queues = self.lock()?;
+        Ok(Self::subscribers(&queues, &topic))
+    }
+
+    async fn consumer_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> Result<ConsumerGroup, ConsumerGroupError> {
+        let queues = self.lock()?;
+        let state = queues
+            .get(&queue_name)
+            .and_then(|queue| queue.groups.get(&group))
+            .ok_or_else(|| no_group(&queue_name, &group))?;
+        Ok(ConsumerGroup::new(
+            queue_name,
+            group,
+            state.position(),
+            state.committed,
+        ))
+    }
+
+    async fn commit_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+        cursor: Option<usize>,
+    ) -> Result<ConsumerGroup, ConsumerGroupError> {
+        let mut queues = self.lock()?;
+        let queue = queues
+            .get_mut(&queue_name)
+            .ok_or_else(|| no_group(&queue_name, &group))?;
+        let committed = match cursor {
+            Some(cursor) if cursor > queue.max_serial => {
+                return Err(ConsumerGroupError::InvalidCursor(format!(
+                    "{} is past the end of {}",
+                    cursor, queue_name
+                )))
+            }
+            Some(cursor) => cursor,
+            None => queue
+                .groups
+                .get(&group)
+                .ok_or_else(|| no_group(&queue_name, &group))?
+                .position(),
+        };
+        queue.groups.insert(
+            group.clone(),
+            Group {
+                committed,
+                position: None,
+            },
+        );
+        self.record(
+            &mut queues,
+            &[Change::Commit {
+                queue_name: queue_name.clone(),
+                group: group.clone(),
+                committed,
+            }],
+        )?;
+        Ok(ConsumerGroup::new(queue_name, group, committed, committed))
+    }
+
+    async fn delete_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> Result<(), ConsumerGroupError> {
+        let mut queues = self.lock()?;
+        let deleted = queues
+            .get_mut(&queue_name)
+            .is_some_and(|queue| queue.groups.remove(&group).is_some());
+        if !deleted {
+            return Err(no_group(&queue_name, &group));
+        }
+        self.record(&mut queues, &[Change::DeleteGroup { queue_name, group }])?;
+        Ok(())
+    }
+}
+
+fn no_group(queue_name: &QueueName, group: &QueueName) -> ConsumerGroupError {
+    ConsumerGroupError::NoGroup(format!("no group {} on {}", group, queue_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_instant::global::{Instant, MockClock};
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use serde_json;
+
+    macro_rules! gmo {
+            ($($arg:tt)*) => {{
+            let string = format!($($arg)*);
+            let gmo: GetMessageOptions = serde_json::from_str::<HashMap<String, String>>(&string)
+            .unwrap()
+            .try_into()
+            .unwrap();
+             gmo
+        }}
+    }
+
+    async fn put(
+        store: &mut Memory,
+        queue: &str,
+        data: &str,
+        cid: Option<&str>,
+        expiry: Option<u64>,
+    ) -> Result<Message, CreateMessageError> {
+        let req = CreateMessageRequest::new(
+            data.to_string(),
+            cid.map(|s| uuid::Uuid::try_parse(s).unwrap()),
+            expiry.map(|i| Instant::now() + Duration::from_secs(i)),
+        );
+        store
+            .create_message(queue.to_string().try_into().unwrap(), &req)
+            .await
+    }
+
+    async fn depth(store: &Memory, queue_name: &str) -> usize {
+        let gmo = gmo!(r#"{{"action":"query","queue_name":"{}"}}"#, queue_name);
+        let summary = store.get_info(gmo).await.unwrap();
+        summary.depth()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_store() {
+        let store = Memory::new().await;
+        assert!(store.is_ok(), "{:?}", store);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_empty_store() {
+        let store = Memory::new().await.unwrap();
+        let queue_list = store.queue_list().await.unwrap();
+        assert!(queue_list.0.is_empty(), "{:?}", queue_list);
+
+        let gmo = gmo!(r#"{{"action":"query","queue_name":"test"}}"#,);
+        let summary = store.get_info(gmo).await;
+        assert!(summary.is_err(), "{:?}", summary);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_message() {
         let store = Memory::new().await.unwrap();
         let req = CreateMessageRequest::new("msg1".to_string(), None, None);
 
@@ -192,7 +1256,7 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(msg1.content(), &"msg1".to_string());
+        assert_eq!(msg1.content().text(), Some("msg1"));
         assert_eq!(msg1.cursor(), 1);
         let msg2 = store
             .create_message("queue1".to_string().try_into().unwrap(), &req)
@@ -213,6 +1277,17 @@ mod tests {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_empty_batch() {
+        let store = Memory::new().await.unwrap();
+        let created = store
+            .create_messages("queue1".to_string().try_into().unwrap(), &[])
+            .await
+            .unwrap();
+        assert!(created.is_empty());
+        assert!(store.queue_list().await.unwrap().0.is_empty());
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_browse_message() {
         let mut store = Memory::new().await.unwrap();
@@ -239,6 +1314,17 @@ mod tests {
         assert_eq!(msg, msg2);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_query_message() {
+        let mut store = Memory::new().await.unwrap();
+        let msg1 = put(&mut store, "queue1", "msg1", None, None).await.unwrap();
+        let gmo = gmo!(r#"{{"action":"query","queue_name":"queue1"}}"#,);
+        let msg = store.get_message(gmo.clone()).await;
+        assert_eq!(msg.unwrap(), msg1);
+        let msg = store.get_message(gmo.clone()).await;
+        assert_eq!(msg.unwrap(), msg1);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_get_message() {
         let mut store = Memory::new().await.unwrap();
@@ -355,13 +1441,13 @@ mod tests {
         let msg = store.get_message(gmo.clone()).await;
         assert!(msg.is_ok());
         let msg = msg.unwrap();
-        assert_eq!(msg.content(), &"msg2".to_string());
+        assert_eq!(msg.content().text(), Some("msg2"));
         assert_eq!(depth(&store, "queue1").await, 3);
         let fail = store.get_message(gmo.clone()).await;
         assert!(fail.is_err());
         let gmo = gmo!(
-            r#"{{"action":"confirm","queue_name":"queue1","mid":"{}"}}"#,
-            msg.mid()
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            msg.receipt().unwrap()
         );
         let msg = store.get_message(gmo.clone()).await;
         assert!(msg.is_ok());
@@ -382,13 +1468,13 @@ mod tests {
         let msg = store.get_message(reserve_gmo.clone()).await;
         assert!(msg.is_ok());
         let msg = msg.unwrap();
-        assert_eq!(msg.content(), &"msg2".to_string());
+        assert_eq!(msg.content().text(), Some("msg2"));
         assert_eq!(depth(&store, "queue1").await, 3);
         let fail = store.get_message(reserve_gmo.clone()).await;
         assert!(fail.is_err());
         let return_gmo = gmo!(
-            r#"{{"action":"return","queue_name":"queue1","mid":"{}"}}"#,
-            msg.mid()
+            r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+            msg.receipt().unwrap()
         );
         let msg = store.get_message(return_gmo.clone()).await;
         assert!(msg.is_ok());
@@ -396,7 +1482,30 @@ mod tests {
         let msg = store.get_message(reserve_gmo.clone()).await;
         assert!(msg.is_ok());
         let msg = msg.unwrap();
-        assert_eq!(msg.content(), &"msg2".to_string());
+        assert_eq!(msg.content().text(), Some("msg2"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_first_reserved_at() {
+        let mut store = Memory::new().await.unwrap();
+        let msg1 = put(&mut store, "queue1", "msg1", None, None).await.unwrap();
+        assert_eq!(msg1.first_reserved_at(), None);
+        assert!(msg1.enqueued_at() <= wall_clock::to_system_time(Instant::now()));
+        let reserve_gmo =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#,);
+        let reserved = store.get_message(reserve_gmo.clone()).await.unwrap();
+        let first_reserved_at = reserved.first_reserved_at();
+        assert!(first_reserved_at.is_some());
+        assert!(reserved.reserved_until().is_some());
+
+        let return_gmo = gmo!(
+            r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        assert!(store.get_message(return_gmo).await.is_ok());
+        let reserved = store.get_message(reserve_gmo).await.unwrap();
+        assert_eq!(reserved.first_reserved_at(), first_reserved_at);
+        assert_eq!(reserved.enqueued_at(), msg1.enqueued_at());
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -427,7 +1536,7 @@ mod tests {
         assert!(msg_r1.is_ok());
         assert!(msg_r2.is_ok());
         let msg_r1 = msg_r1.unwrap();
-        assert_eq!(msg_r1.content(), &"msg2".to_string());
+        assert_eq!(msg_r1.content().text(), Some("msg2"));
         assert_eq!(depth(&store, "queue1").await, 3);
 
         let fail = store.get_message(browse_gmo1.clone()).await;
@@ -492,6 +1601,324 @@ mod tests {
         assert_eq!(msg_r2, msg2);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_extend_reservation() {
+        let store = Memory::new().await.unwrap();
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None);
+        let msg1 = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap();
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let reserved = store.get_message(reserve).await.unwrap();
+        let receipt = *reserved.receipt().unwrap();
+        let extend = gmo!(
+            r#"{{"action":"extend","queue_name":"queue1","receipt":"{}","reservation_seconds":"20"}}"#,
+            receipt
+        );
+        MockClock::advance(Duration::from_secs(5));
+        let extended = store.get_message(extend.clone()).await.unwrap();
+        assert!(extended.reserved_until() > reserved.reserved_until());
+        assert_eq!(extended.deliveries(), 1);
+
+        MockClock::advance(Duration::from_secs(10));
+        let browse = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert!(store.get_message(browse.clone()).await.is_err());
+        MockClock::advance(Duration::from_secs(20));
+        assert!(store.get_message(browse).await.is_ok());
+        let lapsed = store.get_message(extend).await;
+        assert!(matches!(lapsed, Err(GetMessageError::NotReserved(_))));
+
+        // Once someone else holds the message the old receipt is no use.
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let again = store.get_message(reserve).await.unwrap();
+        assert_eq!(again.mid(), msg1.mid());
+        assert_ne!(again.receipt(), Some(&receipt));
+        let stale = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            receipt
+        );
+        let stale = store.get_message(stale).await;
+        assert!(matches!(stale, Err(GetMessageError::NoMessage(_))));
+        let stale = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","mid":"{}","receipt":"{}"}}"#,
+            msg1.mid(),
+            receipt
+        );
+        let stale = store.get_message(stale).await;
+        assert!(matches!(stale, Err(GetMessageError::NotReserved(_))));
+        let confirm = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            again.receipt().unwrap()
+        );
+        assert!(store.get_message(confirm).await.is_ok());
+
+        let missing = gmo!(
+            r#"{{"action":"extend","queue_name":"queue1","receipt":"{}","reservation_seconds":"20"}}"#,
+            Uuid::new_v4()
+        );
+        let missing = store.get_message(missing).await;
+        assert!(matches!(missing, Err(GetMessageError::NoMessage(_))));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_messages() {
+        let store = Memory::new().await.unwrap();
+        let mut messages = vec![];
+        for (content, priority) in [("msg1", 0), ("msg2", 0), ("msg3", 5), ("msg4", 0)] {
+            let req =
+                CreateMessageRequest::new(content.to_string(), None, None).with_priority(priority);
+            let message = store
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+            messages.push(message);
+        }
+        let mids = |found: &[Message]| found.iter().map(|m| *m.mid()).collect::<Vec<_>>();
+
+        let browse = gmo!(r#"{{"action":"browse","queue_name":"queue1","max_messages":"3"}}"#);
+        let page = store.get_messages(browse).await.unwrap();
+        assert_eq!(mids(&page), mids(&messages[..3]));
+        let browse = gmo!(
+            r#"{{"action":"browse","queue_name":"queue1","max_messages":"3","after":"{}"}}"#,
+            page[2].cursor()
+        );
+        let page = store.get_messages(browse).await.unwrap();
+        assert_eq!(mids(&page), mids(&messages[3..]));
+
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","max_messages":"2","reservation_seconds":"10"}}"#
+        );
+        let reserved = store.get_messages(reserve).await.unwrap();
+        assert_eq!(
+            mids(&reserved),
+            vec![*messages[2].mid(), *messages[0].mid()]
+        );
+        assert!(reserved.iter().all(|m| m.receipt().is_some()));
+
+        let get = gmo!(r#"{{"action":"get","queue_name":"queue1","max_messages":"10"}}"#);
+        let got = store.get_messages(get.clone()).await.unwrap();
+        assert_eq!(mids(&got), vec![*messages[1].mid(), *messages[3].mid()]);
+        assert!(matches!(
+            store.get_messages(get).await,
+            Err(GetMessageError::NoMessage(_))
+        ));
+        assert_eq!(depth(&store, "queue1").await, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_acknowledge_messages() {
+        let store = Memory::new().await.unwrap();
+        let queue_name: QueueName = "queue1".to_string().try_into().unwrap();
+        for content in ["msg1", "msg2", "msg3"] {
+            let req = CreateMessageRequest::new(content.to_string(), None, None);
+            store
+                .create_message(queue_name.clone(), &req)
+                .await
+                .unwrap();
+        }
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","max_messages":"3","reservation_seconds":"10"}}"#
+        );
+        let reserved = store.get_messages(reserve).await.unwrap();
+        let receipts = reserved
+            .iter()
+            .map(|m| *m.receipt().unwrap())
+            .collect::<Vec<_>>();
+
+        let req = AcknowledgeRequest::new(
+            queue_name.clone(),
+            GetMessageAction::Confirm,
+            vec![receipts[0], Uuid::new_v4(), receipts[2]],
+        )
+        .unwrap();
+        let results = store.acknowledge_messages(&req).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().mid(), reserved[0].mid());
+        assert!(matches!(results[1], Err(GetMessageError::NoMessage(_))));
+        assert_eq!(results[2].as_ref().unwrap().mid(), reserved[2].mid());
+        assert_eq!(depth(&store, "queue1").await, 1);
+
+        let req = AcknowledgeRequest::new(
+            queue_name,
+            GetMessageAction::Return,
+            vec![receipts[1], receipts[0]],
+        )
+        .unwrap();
+        let results = store.acknowledge_messages(&req).await.unwrap();
+        assert!(!results[0].as_ref().unwrap().is_reserved());
+        assert!(results[1].is_err());
+        let browse = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(
+            store.get_message(browse).await.unwrap().mid(),
+            reserved[1].mid()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dedup_key() {
+        let store = Memory::new().await.unwrap();
+        let queue_name: QueueName = "queue1".to_string().try_into().unwrap();
+        let req = |content: &str| {
+            CreateMessageRequest::new(content.to_string(), None, None)
+                .with_dedup_key(Some("key1".to_string()))
+                .with_dedup_window(Duration::from_secs(60))
+        };
+        let msg1 = store
+            .create_message(queue_name.clone(), &req("msg1"))
+            .await
+            .unwrap();
+        let retried = store
+            .create_messages(queue_name.clone(), &[req("msg1"), req("msg1")])
+            .await
+            .unwrap();
+        assert!(retried.iter().all(|m| m.mid() == msg1.mid()));
+        assert_eq!(depth(&store, "queue1").await, 1);
+
+        let other = gmo!(r#"{{"action":"query","queue_name":"queue2"}}"#);
+        let msg2 = store
+            .create_message("queue2".to_string().try_into().unwrap(), &req("msg2"))
+            .await
+            .unwrap();
+        assert_ne!(msg2.mid(), msg1.mid());
+        assert_eq!(store.get_info(other).await.unwrap().depth(), 1);
+
+        let get = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(get).await.unwrap().mid(), msg1.mid());
+        MockClock::advance(Duration::from_secs(30));
+        let retried = store
+            .create_message(queue_name.clone(), &req("msg1"))
+            .await
+            .unwrap();
+        assert_eq!(retried.mid(), msg1.mid());
+        assert_eq!(retried.enqueued_at(), msg1.enqueued_at());
+        assert_eq!(depth(&store, "queue1").await, 0);
+
+        MockClock::advance(Duration::from_secs(31));
+        let msg3 = store
+            .create_message(queue_name.clone(), &req("msg1"))
+            .await
+            .unwrap();
+        assert_ne!(msg3.mid(), msg1.mid());
+        assert_eq!(depth(&store, "queue1").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_delayed_delivery() {
+        let store = Memory::new().await.unwrap();
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None)
+            .with_deliver_at(Some(Instant::now() + Duration::from_secs(10)));
+        let msg1 = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap();
+        assert!(msg1.is_delayed());
+        assert!(msg1.deliver_at().is_some());
+        for action in ["browse", "get"] {
+            let gmo = gmo!(r#"{{"action":"{}","queue_name":"queue1"}}"#, action);
+            assert!(store.get_message(gmo).await.is_err());
+        }
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        assert!(store.get_message(reserve.clone()).await.is_err());
+        assert_eq!(depth(&store, "queue1").await, 1);
+
+        MockClock::advance(Duration::from_secs(15));
+        assert_eq!(store.get_message(reserve).await.unwrap().mid(), msg1.mid());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_priority() {
+        let store = Memory::new().await.unwrap();
+        let mut messages = vec![];
+        for (data, priority) in [("msg1", 0), ("msg2", 5), ("msg3", 0), ("msg4", 5)] {
+            let req =
+                CreateMessageRequest::new(data.to_string(), None, None).with_priority(priority);
+            let message = store
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+            messages.push(message);
+        }
+        let [msg1, msg2, msg3, msg4] = <[Message; 4]>::try_from(messages).unwrap();
+        assert_eq!(msg2.priority(), 5);
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg1);
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1","order":"priority"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg2);
+        let gmo = gmo!(
+            r#"{{"action":"browse","queue_name":"queue1","order":"priority","after":"4","after_priority":"5"}}"#
+        );
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg1);
+
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        let order = [msg2, msg4, msg1, msg3];
+        for expected in order {
+            assert_eq!(store.get_message(gmo.clone()).await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_headers() {
+        let mut store = Memory::new().await.unwrap();
+        let headers = BTreeMap::from([
+            ("content-type".to_string(), "application/json".to_string()),
+            ("trace-id".to_string(), "abc123".to_string()),
+        ]);
+        let req =
+            CreateMessageRequest::new("msg1".to_string(), None, None).with_headers(headers.clone());
+        let msg1 = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap();
+        assert_eq!(msg1.headers(), &headers);
+        let msg2 = put(&mut store, "queue1", "msg2", None, None).await.unwrap();
+        assert!(msg2.headers().is_empty());
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap().headers(), &headers);
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1","max_messages":"2"}}"#);
+        let taken = store.get_messages(gmo).await.unwrap();
+        assert_eq!(taken, vec![msg1, msg2]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_selector() {
+        let store = Memory::new().await.unwrap();
+        let mut messages = vec![];
+        for (data, region, priority) in [("msg1", "us", 5), ("msg2", "eu", 1), ("msg3", "eu", 5)] {
+            let req = CreateMessageRequest::new(data.to_string(), None, None)
+                .with_priority(priority)
+                .with_headers(BTreeMap::from([("region".to_string(), region.to_string())]));
+            let message = store
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+            messages.push(message);
+        }
+
+        let gmo = gmo!(
+            r#"{{"action":"browse","queue_name":"queue1","selector":"region = 'eu'","max_messages":"5"}}"#
+        );
+        let browsed = store.get_messages(gmo).await.unwrap();
+        assert_eq!(browsed, vec![messages[1].clone(), messages[2].clone()]);
+        let gmo = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10","selector":"region = 'eu' AND priority > 3"}}"#
+        );
+        let reserved = store.get_message(gmo.clone()).await.unwrap();
+        assert_eq!(reserved.mid(), messages[2].mid());
+        assert!(matches!(
+            store.get_message(gmo).await,
+            Err(GetMessageError::NoMessage(_))
+        ));
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1","selector":"region IN ('eu')"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), messages[1]);
+        assert_eq!(depth(&store, "queue1").await, 2);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_expired_messages() {
         let mut store = Memory::new().await.unwrap();
@@ -506,4 +1933,596 @@ mod tests {
         let _msg4 = put(&mut store, "queue1", "msg4", None, None).await.unwrap();
         assert_eq!(depth(&store, "queue1").await, 3);
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dead_letter_on_return() {
+        let mut store = Memory::new().await.unwrap();
+        let dlq: QueueName = "dlq".to_string().try_into().unwrap();
+        let policy = DeadLetterPolicy::new(2, dlq);
+        let msg1 = put(&mut store, "queue1", "msg1", None, None).await.unwrap();
+        store
+            .set_dead_letter_policy("queue1".to_string().try_into().unwrap(), Some(policy))
+            .await
+            .unwrap();
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"1000"}}"#);
+        let ret = |msg: &Message| {
+            gmo!(
+                r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+                msg.receipt().unwrap()
+            )
+        };
+
+        let reserved = store.get_message(reserve.clone()).await.unwrap();
+        assert_eq!(reserved.deliveries(), 1);
+        assert!(store.get_message(ret(&reserved)).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 1);
+        let reserved = store.get_message(reserve.clone()).await.unwrap();
+        assert_eq!(reserved.deliveries(), 2);
+        assert!(store.get_message(ret(&reserved)).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 0);
+        assert_eq!(depth(&store, "dlq").await, 1);
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"dlq"}}"#);
+        let dead = store.get_message(gmo).await.unwrap();
+        assert_eq!(dead.mid(), msg1.mid());
+        assert_eq!(dead.content(), msg1.content());
+        assert!(!dead.is_reserved());
+        assert_eq!(dead.cursor(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dead_letter_on_lapse() {
+        let mut store = Memory::new().await.unwrap();
+        let dlq: QueueName = "dlq".to_string().try_into().unwrap();
+        let policy = DeadLetterPolicy::new(1, dlq);
+        let msg1 = put(&mut store, "queue1", "msg1", None, None).await.unwrap();
+        store
+            .set_dead_letter_policy("queue1".to_string().try_into().unwrap(), Some(policy))
+            .await
+            .unwrap();
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        assert!(store.get_message(reserve.clone()).await.is_ok());
+
+        MockClock::advance(Duration::from_secs(15));
+        assert!(store.get_message(reserve).await.is_err());
+        assert_eq!(depth(&store, "queue1").await, 0);
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"dlq"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap().mid(), msg1.mid());
+
+        store
+            .set_dead_letter_policy("queue1".to_string().try_into().unwrap(), None)
+            .await
+            .unwrap();
+        let msg2 = put(&mut store, "queue1", "msg2", None, None).await.unwrap();
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"1000"}}"#);
+        let reserved = store.get_message(reserve).await.unwrap();
+        assert_eq!(reserved.mid(), msg2.mid());
+        let ret = gmo!(
+            r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        assert!(store.get_message(ret).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_redrive() {
+        let mut store = Memory::new().await.unwrap();
+        let cid = Uuid::new_v4();
+        let target: QueueName = "queue2".to_string().try_into().unwrap();
+        let source: QueueName = "queue1".to_string().try_into().unwrap();
+        let _existing = put(&mut store, "queue2", "existing", None, None)
+            .await
+            .unwrap();
+        let msg1 = put(&mut store, "queue1", "msg1", None, None).await.unwrap();
+        let msg2 = put(&mut store, "queue1", "msg2", Some(&cid.to_string()), None)
+            .await
+            .unwrap();
+        let msg3 = put(&mut store, "queue1", "msg3", None, None).await.unwrap();
+        let msg4 = put(&mut store, "queue1", "msg4", None, None).await.unwrap();
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","mid":"{}","reservation_seconds":"1000"}}"#,
+            msg4.mid()
+        );
+        assert!(store.get_message(reserve).await.is_ok());
+
+        let req = RedriveRequest::new(target.clone()).with_cid(Some(cid));
+        let moved = store.redrive(source.clone(), &req).await.unwrap();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].mid(), msg2.mid());
+        assert_eq!(moved[0].cid(), Some(&cid));
+        assert_eq!(moved[0].content(), msg2.content());
+        assert_eq!(moved[0].cursor(), 2);
+
+        let req = RedriveRequest::new(target.clone()).with_cursor_range(Some(1), Some(3));
+        let moved = store.redrive(source.clone(), &req).await.unwrap();
+        assert_eq!(
+            moved.iter().map(|m| *m.mid()).collect::<Vec<_>>(),
+            vec![*msg3.mid()]
+        );
+        assert_eq!(moved[0].cursor(), 3);
+
+        // The reserved message stays where it is.
+        let req = RedriveRequest::new(target.clone());
+        let moved = store.redrive(source.clone(), &req).await.unwrap();
+        assert_eq!(
+            moved.iter().map(|m| *m.mid()).collect::<Vec<_>>(),
+            vec![*msg1.mid()]
+        );
+        assert_eq!(depth(&store, "queue1").await, 1);
+        assert_eq!(depth(&store, "queue2").await, 4);
+
+        let req = RedriveRequest::new(source).with_mid(Some(*msg1.mid()));
+        let moved = store.redrive(target, &req).await.unwrap();
+        assert_eq!(moved[0].cursor(), 5);
+        assert_eq!(depth(&store, "queue1").await, 2);
+
+        let req = RedriveRequest::new("queue1".to_string().try_into().unwrap());
+        let missing = store
+            .redrive("queue3".to_string().try_into().unwrap(), &req)
+            .await;
+        assert!(matches!(missing, Err(RedriveError::NoQueue(_))));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_publish() {
+        let mut store = Memory::new().await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let req = CreateMessageRequest::new("order1".to_string(), None, None);
+        assert!(store
+            .publish(name("orders"), &req)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            store.subscribe(name("orders"), name("billing")).await,
+            Err(SubscriptionError::NoQueue(_))
+        ));
+        for queue_name in ["shipping", "billing"] {
+            store
+                .create_queue(name(queue_name), &QueueSettings::default())
+                .await
+                .unwrap();
+        }
+        for queue_name in ["shipping", "billing", "shipping"] {
+            store
+                .subscribe(name("orders"), name(queue_name))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            store.subscriptions(name("orders")).await.unwrap(),
+            vec![name("billing"), name("shipping")]
+        );
+        assert!(store
+            .subscriptions(name("queue1"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let published = store.publish(name("orders"), &req).await.unwrap();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].0, name("billing"));
+        assert_eq!(published[1].0, name("shipping"));
+        assert_ne!(published[0].1.mid(), published[1].1.mid());
+        let _order0 = put(&mut store, "orders", "order0", None, None)
+            .await
+            .unwrap();
+        assert_eq!(depth(&store, "orders").await, 1);
+
+        store
+            .unsubscribe(name("orders"), name("billing"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.unsubscribe(name("orders"), name("billing")).await,
+            Err(SubscriptionError::NoSubscription(_))
+        ));
+        let req = CreateMessageRequest::new("order2".to_string(), None, None);
+        store.publish(name("orders"), &req).await.unwrap();
+        assert_eq!(depth(&store, "billing").await, 1);
+        assert_eq!(depth(&store, "shipping").await, 2);
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"billing"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), published[0].1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_consumer_groups() {
+        let mut store = Memory::new().await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let mut messages = vec![];
+        for data in ["msg1", "msg2", "msg3"] {
+            messages.push(put(&mut store, "queue1", data, None, None).await.unwrap());
+        }
+        let read = |group: &str| {
+            gmo!(
+                r#"{{"action":"browse","queue_name":"queue1","group":"{}","max_messages":"2"}}"#,
+                group
+            )
+        };
+        assert_eq!(
+            store.get_messages(read("group1")).await.unwrap(),
+            messages[..2]
+        );
+        assert_eq!(
+            store.get_messages(read("group1")).await.unwrap(),
+            messages[2..]
+        );
+        assert!(matches!(
+            store.get_messages(read("group1")).await,
+            Err(GetMessageError::NoMessage(_))
+        ));
+        assert_eq!(
+            store.get_messages(read("group2")).await.unwrap(),
+            messages[..2]
+        );
+        assert_eq!(depth(&store, "queue1").await, 3);
+
+        let group = store
+            .consumer_group(name("queue1"), name("group1"))
+            .await
+            .unwrap();
+        assert_eq!((group.position(), group.committed()), (3, 0));
+        let group = store
+            .commit_group(name("queue1"), name("group2"), None)
+            .await
+            .unwrap();
+        assert_eq!((group.position(), group.committed()), (2, 2));
+        let group = store
+            .commit_group(name("queue1"), name("group1"), Some(1))
+            .await
+            .unwrap();
+        assert_eq!((group.position(), group.committed()), (1, 1));
+        assert_eq!(
+            store.get_messages(read("group1")).await.unwrap(),
+            messages[1..]
+        );
+        assert!(matches!(
+            store
+                .commit_group(name("queue1"), name("group1"), Some(4))
+                .await,
+            Err(ConsumerGroupError::InvalidCursor(_))
+        ));
+        assert!(matches!(
+            store
+                .commit_group(name("queue1"), name("group3"), None)
+                .await,
+            Err(ConsumerGroupError::NoGroup(_))
+        ));
+
+        store
+            .delete_group(name("queue1"), name("group1"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.consumer_group(name("queue1"), name("group1")).await,
+            Err(ConsumerGroupError::NoGroup(_))
+        ));
+        assert_eq!(
+            store.get_messages(read("group1")).await.unwrap(),
+            messages[..2]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retention() {
+        let mut store = Memory::new().await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let policy = RetentionPolicy::new(Some(Duration::from_secs(60)), Some(3));
+        assert!(matches!(
+            store
+                .set_retention_policy(name("queue1"), Some(policy.clone()))
+                .await,
+            Err(RetentionPolicyError::NoQueue(_))
+        ));
+        store
+            .create_queue(name("queue1"), &QueueSettings::default())
+            .await
+            .unwrap();
+        store
+            .set_retention_policy(name("queue1"), Some(policy))
+            .await
+            .unwrap();
+        let mut messages = vec![];
+        for data in ["msg1", "msg2", "msg3"] {
+            messages.push(put(&mut store, "queue1", data, None, None).await.unwrap());
+        }
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        let got = store.get_message(gmo.clone()).await.unwrap();
+        assert_eq!(got.mid(), messages[0].mid());
+        assert!(got.is_consumed());
+        let gmo2 =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let reserved = store.get_message(gmo2).await.unwrap();
+        assert_eq!(reserved.mid(), messages[1].mid());
+        let gmo3 = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        assert!(store.get_message(gmo3).await.unwrap().is_consumed());
+        assert_eq!(depth(&store, "queue1").await, 1);
+        assert_eq!(
+            store.get_message(gmo.clone()).await.unwrap().mid(),
+            messages[2].mid()
+        );
+        assert!(matches!(
+            store.get_message(gmo).await,
+            Err(GetMessageError::NoMessage(_))
+        ));
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1","max_messages":"5"}}"#);
+        let browsed = store.get_messages(gmo.clone()).await.unwrap();
+        assert_eq!(
+            browsed.iter().map(Message::mid).collect::<Vec<_>>(),
+            messages.iter().map(Message::mid).collect::<Vec<_>>()
+        );
+        let _msg4 = put(&mut store, "queue1", "msg4", None, None).await.unwrap();
+        let browsed = store.get_messages(gmo.clone()).await.unwrap();
+        assert_eq!(browsed.len(), 3);
+        assert_eq!(browsed[0].mid(), messages[1].mid());
+
+        MockClock::advance(Duration::from_secs(61));
+        let msg5 = put(&mut store, "queue1", "msg5", None, None).await.unwrap();
+        assert_eq!(store.get_messages(gmo).await.unwrap(), vec![msg5]);
+
+        store
+            .set_retention_policy(name("queue1"), None)
+            .await
+            .unwrap();
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert!(store.get_message(gmo).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_queue_lifecycle() {
+        let mut store = Memory::new().await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let settings = QueueSettings::default()
+            .with_dead_letter(Some(DeadLetterPolicy::new(1, name("dlq"))))
+            .with_retention(Some(RetentionPolicy::new(None, Some(2))));
+        assert!(store.create_queue(name("queue1"), &settings).await.unwrap());
+        assert!(!store.create_queue(name("queue1"), &settings).await.unwrap());
+        assert_eq!(depth(&store, "queue1").await, 0);
+        for data in ["msg1", "msg2", "msg3"] {
+            put(&mut store, "queue1", data, None, None).await.unwrap();
+        }
+        assert_eq!(depth(&store, "queue1").await, 2);
+        let gmo =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let reserved = store.get_message(gmo).await.unwrap();
+        let gmo = gmo!(
+            r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        store.get_message(gmo).await.unwrap();
+        assert_eq!(depth(&store, "dlq").await, 1);
+
+        assert_eq!(store.purge_queue(name("queue1")).await.unwrap(), 1);
+        assert_eq!(depth(&store, "queue1").await, 0);
+        store.delete_queue(name("queue1")).await.unwrap();
+        assert!(!store
+            .queue_list()
+            .await
+            .unwrap()
+            .0
+            .contains(&"queue1".to_string()));
+        assert!(matches!(
+            store.delete_queue(name("queue1")).await,
+            Err(QueueError::NoQueue(_))
+        ));
+        assert!(matches!(
+            store.purge_queue(name("queue1")).await,
+            Err(QueueError::NoQueue(_))
+        ));
+
+        for data in ["msg1", "msg2", "msg3"] {
+            put(&mut store, "queue1", data, None, None).await.unwrap();
+        }
+        assert_eq!(depth(&store, "queue1").await, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_queue_config() {
+        let store = Memory::new().await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let send = |queue: &str, data: &str| {
+            let req = CreateMessageRequest::new(data.to_string(), None, None);
+            let store = store.clone();
+            let queue_name = name(queue);
+            async move { store.create_message(queue_name, &req).await }
+        };
+        let config = QueueConfig::default()
+            .with_expiry(Some(Duration::from_secs(10)))
+            .with_reservation(Some(Duration::from_secs(30)))
+            .with_max_depth(Some(2))
+            .with_max_content_size(Some(4));
+        let settings = QueueSettings::default().with_config(config);
+        store.create_queue(name("queue1"), &settings).await.unwrap();
+        assert!(send("queue1", "msg1").await.is_ok());
+        assert!(matches!(
+            send("queue1", "msg12").await,
+            Err(CreateMessageError::TooLarge(_))
+        ));
+        assert!(send("queue1", "msg2").await.is_ok());
+        assert!(matches!(
+            send("queue1", "msg3").await,
+            Err(CreateMessageError::QueueFull(_))
+        ));
+        let gmo = gmo!(r#"{{"action":"reserve","queue_name":"queue1"}}"#);
+        let reserved = store.get_message(gmo).await.unwrap();
+        assert!(reserved.is_reserved());
+
+        MockClock::advance(Duration::from_secs(11));
+        assert!(send("queue1", "msg3").await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 1);
+
+        assert!(send("queue2", "msg1").await.is_ok());
+        let gmo = gmo!(r#"{{"action":"reserve","queue_name":"queue2"}}"#);
+        assert!(matches!(
+            store.get_message(gmo).await,
+            Err(GetMessageError::NoReservation(_))
+        ));
+
+        let store = store.with_auto_create(false);
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None);
+        assert!(matches!(
+            store.create_message(name("queue3"), &req).await,
+            Err(CreateMessageError::NoQueue(_))
+        ));
+        assert!(store.create_message(name("queue2"), &req).await.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_no_auto_create() {
+        let store = Memory::new().await.unwrap().with_auto_create(false);
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let policy = DeadLetterPolicy::new(1, name("dlq"));
+        assert!(matches!(
+            store
+                .set_dead_letter_policy(name("queue1"), Some(policy.clone()))
+                .await,
+            Err(DeadLetterPolicyError::NoQueue(_))
+        ));
+        assert!(matches!(
+            store.set_retention_policy(name("queue1"), None).await,
+            Err(RetentionPolicyError::NoQueue(_))
+        ));
+        assert!(matches!(
+            store.subscribe(name("orders"), name("queue1")).await,
+            Err(SubscriptionError::NoQueue(_))
+        ));
+        assert!(store.queue_list().await.unwrap().0.is_empty());
+
+        let settings = QueueSettings::default().with_dead_letter(Some(policy));
+        store.create_queue(name("queue1"), &settings).await.unwrap();
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None);
+        let msg1 = store.create_message(name("queue1"), &req).await.unwrap();
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"1000"}}"#);
+        let ret = |msg: &Message| {
+            gmo!(
+                r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+                msg.receipt().unwrap()
+            )
+        };
+        // Without the dead-letter queue, the message stays where it is.
+        let reserved = store.get_message(reserve.clone()).await.unwrap();
+        assert!(store.get_message(ret(&reserved)).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 1);
+        let req = RedriveRequest::new(name("queue2"));
+        assert!(matches!(
+            store.redrive(name("queue1"), &req).await,
+            Err(RedriveError::NoQueue(_))
+        ));
+
+        store
+            .create_queue(name("dlq"), &QueueSettings::default())
+            .await
+            .unwrap();
+        let reserved = store.get_message(reserve).await.unwrap();
+        assert!(store.get_message(ret(&reserved)).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 0);
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"dlq"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap().mid(), msg1.mid());
+        assert_eq!(store.queue_list().await.unwrap().0.len(), 2);
+    }
+
+    /// A journal whose disk has gone away.
+    #[derive(Debug)]
+    struct BrokenJournal;
+
+    impl Journal for BrokenJournal {
+        fn record(&self, _changes: &[Change]) -> anyhow::Result<()> {
+            anyhow::bail!("cannot record")
+        }
+
+        fn replay(&self) -> anyhow::Result<Vec<Change>> {
+            anyhow::bail!("cannot replay")
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_rollback_poisons_store() {
+        let store = Memory::new()
+            .await
+            .unwrap()
+            .with_journal(Arc::new(BrokenJournal));
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None);
+        let created = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await;
+        assert!(matches!(created, Err(CreateMessageError::Unknown(_))));
+        assert!(store.queue_list().await.is_err());
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert!(matches!(
+            store.get_message(gmo).await,
+            Err(GetMessageError::Unknown(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_long_poll() {
+        let mut store = Memory::new().await.unwrap();
+        let _msg1 = put(&mut store, "queue2", "msg1", None, None).await.unwrap();
+        let waiter = store.clone();
+        let handle = tokio::spawn(async move {
+            let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1","wait_seconds":"10"}}"#);
+            waiter.get_message(gmo).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+        let msg2 = put(&mut store, "queue1", "msg2", None, None).await.unwrap();
+        assert_eq!(handle.await.unwrap().unwrap(), msg2);
+        assert_eq!(depth(&store, "queue1").await, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_long_poll_times_out() {
+        let store = Memory::new().await.unwrap();
+        let started = tokio::time::Instant::now();
+        let gmo = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10","wait_seconds":"1"}}"#
+        );
+        let result = store.get_message(gmo).await;
+        assert!(matches!(result, Err(GetMessageError::NoMessage(_))));
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_snapshot_restore() {
+        let path = std::env::temp_dir().join(format!("msg_q-{}.json", Uuid::new_v4()));
+        let store = Memory::from_snapshot(&path).await.unwrap();
+        assert!(store.queue_list().await.unwrap().0.is_empty());
+
+        let mut store = store;
+        let _msg1 = put(&mut store, "queue1", "msg1", None, None).await.unwrap();
+        let msg2 = put(&mut store, "queue1", "msg2", None, None).await.unwrap();
+        let msg3 = put(&mut store, "queue1", "msg3", None, Some(1000))
+            .await
+            .unwrap();
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#,);
+        assert!(store.get_message(gmo).await.is_ok());
+        let reserve_gmo = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","mid":"{}","reservation_seconds":"1000"}}"#,
+            msg2.mid()
+        );
+        assert!(store.get_message(reserve_gmo).await.is_ok());
+        store.save_snapshot(&path).unwrap();
+
+        // Time passing while the server is down does not count against the
+        // reservation or the expiry.
+        MockClock::advance(Duration::from_secs(2000));
+        let mut store = Memory::from_snapshot(&path).await.unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(depth(&store, "queue1").await, 2);
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#,);
+        assert_eq!(store.get_message(gmo).await.unwrap().mid(), msg3.mid());
+        let msg4 = put(&mut store, "queue1", "msg4", None, None).await.unwrap();
+        assert_eq!(msg4.cursor(), 4);
+        assert_eq!(depth(&store, "queue1").await, 3);
+    }
 }
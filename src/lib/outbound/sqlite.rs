@@ -0,0 +1,2384 @@
+use anyhow::Context;
+use rusqlite::types::{ToSqlOutput, ValueRef};
+use rusqlite::{named_params, Connection, OptionalExtension, Row, Transaction};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+#[cfg(test)]
+use mock_instant::global::Instant;
+
+#[cfg(not(test))]
+use std::time::Instant;
+
+use crate::domain::messages::models::message::wall_clock;
+use crate::domain::messages::models::message::{
+    AcknowledgeRequest, ConsumerGroup, Content, CreateMessageRequest, DeadLetterPolicy,
+    GetMessageAction, GetMessageOptions, Message, MessageOrder, QueueConfig, QueueList, QueueName,
+    QueueSettings, QueueSummary, RedriveRequest, RetentionPolicy,
+};
+use crate::domain::messages::models::message::{
+    ConsumerGroupError, CreateMessageError, DeadLetterPolicyError, GetMessageError, QueueError,
+    QueueListError, QueueSummaryError, RedriveError, RetentionPolicyError, SubscriptionError,
+};
+use crate::domain::messages::ports::MessageRepository;
+use crate::outbound::waiters::Waiters;
+
+/// Each entry upgrades the schema by one version, and `PRAGMA user_version`
+/// records how many have been applied.  Times are stored as milliseconds since
+/// the unix epoch so that the tables can be read with ordinary SQL tools.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS queues (
+        queue_name TEXT PRIMARY KEY,
+        max_serial INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        queue_name TEXT NOT NULL,
+        cursor INTEGER NOT NULL,
+        mid TEXT NOT NULL UNIQUE,
+        cid TEXT,
+        content TEXT NOT NULL,
+        reserved_until INTEGER,
+        expires_at INTEGER,
+        PRIMARY KEY (queue_name, cursor)
+    );
+    CREATE INDEX IF NOT EXISTS messages_by_cid ON messages (queue_name, cid);
+    CREATE INDEX IF NOT EXISTS messages_by_expiry ON messages (expires_at);",
+    "ALTER TABLE messages ADD COLUMN enqueued_at INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE messages ADD COLUMN first_reserved_at INTEGER;",
+    "ALTER TABLE messages ADD COLUMN deliveries INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE queues ADD COLUMN max_deliveries INTEGER;
+    ALTER TABLE queues ADD COLUMN dead_letter_queue TEXT;",
+    "ALTER TABLE messages ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX IF NOT EXISTS messages_by_priority ON messages (queue_name, priority DESC, cursor);",
+    "ALTER TABLE messages ADD COLUMN deliver_at INTEGER;",
+    "ALTER TABLE messages ADD COLUMN receipt TEXT;
+    CREATE INDEX IF NOT EXISTS messages_by_receipt ON messages (receipt);",
+    "CREATE TABLE dedup (
+        queue_name TEXT NOT NULL,
+        dedup_key TEXT NOT NULL,
+        mid TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (queue_name, dedup_key)
+    );",
+    "ALTER TABLE messages ADD COLUMN headers TEXT NOT NULL DEFAULT '{}';",
+    "ALTER TABLE messages ADD COLUMN content_type TEXT;",
+    "CREATE TABLE subscriptions (
+        topic TEXT NOT NULL,
+        queue_name TEXT NOT NULL,
+        PRIMARY KEY (topic, queue_name)
+    );",
+    "CREATE TABLE consumer_groups (
+        queue_name TEXT NOT NULL,
+        group_name TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        committed INTEGER NOT NULL,
+        PRIMARY KEY (queue_name, group_name)
+    );",
+    "ALTER TABLE messages ADD COLUMN consumed INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE queues ADD COLUMN retained INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE queues ADD COLUMN retention_max_age INTEGER;
+    ALTER TABLE queues ADD COLUMN retention_max_messages INTEGER;",
+    "ALTER TABLE queues ADD COLUMN default_expiry INTEGER;
+    ALTER TABLE queues ADD COLUMN default_reservation INTEGER;
+    ALTER TABLE queues ADD COLUMN max_depth INTEGER;
+    ALTER TABLE queues ADD COLUMN max_content_size INTEGER;",
+];
+
+const MESSAGE_COLUMNS: &str = "mid, cid, cursor, content, reserved_until, expires_at, \
+     enqueued_at, first_reserved_at, deliveries, priority, deliver_at, receipt, headers, content_type, \
+     consumed";
+
+#[derive(Debug, Clone)]
+pub struct Sqlite {
+    connection: Arc<Mutex<Connection>>,
+    waiters: Waiters,
+    auto_create: bool,
+}
+
+impl Sqlite {
+    pub async fn new(path: impl AsRef<Path>) -> Result<Sqlite, anyhow::Error> {
+        let path = path.as_ref();
+        let mut connection = Connection::open(path)
+            .with_context(|| format!("failed to open database {}", path.display()))?;
+        migrate(&mut connection).context("failed to migrate schema")?;
+        // As in the other stores, a group's position does not outlive the
+        // process and the group starts again from its commit.
+        connection
+            .execute("UPDATE consumer_groups SET position = committed", [])
+            .context("failed to reset consumer groups")?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            waiters: Waiters::default(),
+            auto_create: true,
+        })
+    }
+
+    /// Sets whether a message for a queue which does not exist makes the
+    /// queue, as it does unless told otherwise, or is refused.
+    pub fn with_auto_create(self, auto_create: bool) -> Self {
+        Self {
+            auto_create,
+            ..self
+        }
+    }
+
+    fn find_messages(
+        tx: &Transaction,
+        gmo: &GetMessageOptions,
+        now: i64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Message>> {
+        let queue_name = gmo.queue_name().to_string();
+        let mid = gmo.mid().map(|u| u.to_string());
+        let messages = match gmo.action() {
+            GetMessageAction::Confirm | GetMessageAction::Return | GetMessageAction::Extend => tx
+                .prepare_cached(&format!(
+                    "SELECT {} FROM messages
+                     WHERE queue_name = :queue_name AND receipt = :receipt
+                       AND (:mid IS NULL OR mid = :mid) AND reserved_until > :now",
+                    MESSAGE_COLUMNS
+                ))?
+                .query_map(
+                    named_params! {
+                        ":queue_name": queue_name,
+                        ":receipt": gmo.receipt().map(|u| u.to_string()),
+                        ":mid": mid,
+                        ":now": now,
+                    },
+                    message_from_row,
+                )?
+                .collect::<Result<Vec<_>, _>>(),
+            _ => {
+                let cid = gmo.cid().map(|u| u.to_string());
+                let after = gmo.cursor().map(|c| c as i64);
+                let order = match gmo.order() {
+                    MessageOrder::Cursor => "cursor",
+                    MessageOrder::Priority => "priority DESC, cursor",
+                };
+                // A selector cannot be put into SQL, so rows are read until
+                // enough of them pass it.
+                let sql_limit = match gmo.selector() {
+                    None => limit as i64,
+                    Some(_) => -1,
+                };
+                tx.prepare_cached(&format!(
+                    "SELECT {} FROM messages
+                     WHERE queue_name = :queue_name
+                       AND (reserved_until IS NULL OR reserved_until <= :now)
+                       AND (expires_at IS NULL OR expires_at > :now)
+                       AND (deliver_at IS NULL OR deliver_at <= :now)
+                       AND (:mid IS NULL OR mid = :mid)
+                       AND (:cid IS NULL OR cid = :cid)
+                       AND (:browse OR consumed = 0)
+                       AND (:after IS NULL
+                            OR (:after_priority IS NULL AND cursor > :after)
+                            OR priority < :after_priority
+                            OR (priority = :after_priority AND cursor > :after))
+                     ORDER BY {} LIMIT :limit",
+                    MESSAGE_COLUMNS, order
+                ))?
+                .query_map(
+                    named_params! {
+                        ":queue_name": queue_name,
+                        ":mid": mid,
+                        ":cid": cid,
+                        ":browse": gmo.action() == GetMessageAction::Browse,
+                        ":after": after,
+                        ":after_priority": gmo.after_priority(),
+                        ":now": now,
+                        ":limit": sql_limit,
+                    },
+                    message_from_row,
+                )?
+                .filter(|row| row.as_ref().map_or(true, |m| gmo.selects(m)))
+                .take(limit)
+                .collect::<Result<Vec<_>, _>>()
+            }
+        };
+        Ok(messages?)
+    }
+
+    fn get_messages_tx(
+        connection: &mut Connection,
+        gmo: &GetMessageOptions,
+        limit: usize,
+        auto_create: bool,
+    ) -> anyhow::Result<Result<Vec<Message>, GetMessageError>> {
+        let tx = connection.transaction()?;
+        let now = to_millis(Instant::now());
+        trim_retained(&tx, now)?;
+        dead_letter_lapsed(&tx, now, auto_create)?;
+        if !queue_exists(&tx, gmo.queue_name())? {
+            return Ok(Err(GetMessageError::NoMessage(format!(
+                "no queue {}",
+                gmo.queue_name()
+            ))));
+        }
+        let reservation = queue_config(&tx, gmo.queue_name())?.and_then(|c| c.reservation());
+        let mut gmo = match gmo.clone().with_default_reservation(reservation) {
+            Ok(gmo) => gmo,
+            Err(e) => return Ok(Err(e)),
+        };
+        if let Some(group) = gmo.group() {
+            let position = group_position(&tx, gmo.queue_name(), group)?;
+            gmo = gmo.with_cursor(Some(position));
+        }
+        let gmo = &gmo;
+        let found = Self::find_messages(&tx, gmo, now, limit)?;
+        if found.is_empty() {
+            let error = no_match(&tx, gmo)?;
+            // Keeps a group made by this browse.
+            tx.commit()?;
+            return Ok(Err(error));
+        }
+        let mut messages = Vec::with_capacity(found.len());
+        for message in found {
+            messages.push(Self::take_message(&tx, gmo, message, now, auto_create)?);
+        }
+        if let (Some(group), Some(last)) = (gmo.group(), messages.last()) {
+            tx.execute(
+                "UPDATE consumer_groups SET position = :position
+                 WHERE queue_name = :queue_name AND group_name = :group_name",
+                named_params! {
+                    ":queue_name": gmo.queue_name().to_string(),
+                    ":group_name": group.to_string(),
+                    ":position": last.cursor() as i64,
+                },
+            )?;
+        }
+        tx.commit()?;
+        Ok(Ok(messages))
+    }
+
+    fn acknowledge_messages_tx(
+        connection: &mut Connection,
+        req: &AcknowledgeRequest,
+        auto_create: bool,
+    ) -> anyhow::Result<Vec<Result<Message, GetMessageError>>> {
+        let tx = connection.transaction()?;
+        let now = to_millis(Instant::now());
+        dead_letter_lapsed(&tx, now, auto_create)?;
+        let mut results = Vec::with_capacity(req.receipts().len());
+        for gmo in req.options() {
+            let result = match Self::find_messages(&tx, &gmo, now, 1)?.pop() {
+                Some(message) => Ok(Self::take_message(&tx, &gmo, message, now, auto_create)?),
+                None => Err(no_match(&tx, &gmo)?),
+            };
+            results.push(result);
+        }
+        tx.commit()?;
+        Ok(results)
+    }
+
+    /// Applies the action of `gmo` to `message`, which it matched.
+    fn take_message(
+        tx: &Transaction,
+        gmo: &GetMessageOptions,
+        mut message: Message,
+        now: i64,
+        auto_create: bool,
+    ) -> anyhow::Result<Message> {
+        let queue_name = gmo.queue_name().to_string();
+        let cursor = message.cursor() as i64;
+        match gmo.action() {
+            GetMessageAction::Browse | GetMessageAction::Query => {}
+            GetMessageAction::Get | GetMessageAction::Confirm => {
+                if retains(tx, gmo.queue_name())? {
+                    tx.execute(
+                        "UPDATE messages SET consumed = 1, reserved_until = NULL, receipt = NULL
+                         WHERE queue_name = :queue_name AND cursor = :cursor",
+                        named_params! {":queue_name": queue_name, ":cursor": cursor},
+                    )?;
+                    message = load_message(tx, message.mid())?;
+                } else {
+                    tx.execute(
+                        "DELETE FROM messages WHERE queue_name = :queue_name AND cursor = :cursor",
+                        named_params! {":queue_name": queue_name, ":cursor": cursor},
+                    )?;
+                }
+            }
+            GetMessageAction::Reserve => {
+                tx.execute(
+                    "UPDATE messages
+                     SET reserved_until = :until,
+                         receipt = :receipt,
+                         first_reserved_at = COALESCE(first_reserved_at, :now),
+                         deliveries = deliveries + 1
+                     WHERE queue_name = :queue_name AND cursor = :cursor",
+                    named_params! {
+                        ":queue_name": queue_name,
+                        ":cursor": cursor,
+                        ":until": gmo.reservation().map(to_millis),
+                        ":receipt": Uuid::new_v4().to_string(),
+                        ":now": now,
+                    },
+                )?;
+                message = load_message(tx, message.mid())?;
+            }
+            GetMessageAction::Return => {
+                tx.execute(
+                    "UPDATE messages SET reserved_until = NULL, receipt = NULL
+                     WHERE queue_name = :queue_name AND cursor = :cursor",
+                    named_params! {":queue_name": queue_name, ":cursor": cursor},
+                )?;
+                message = load_message(tx, message.mid())?;
+                if let Some(policy) = dead_letter_policy(tx, gmo.queue_name())? {
+                    if policy.exhausted(&message)
+                        && (auto_create || queue_exists(tx, policy.queue_name())?)
+                    {
+                        move_message(tx, message.mid(), policy.queue_name())?;
+                    }
+                }
+            }
+            GetMessageAction::Extend => {
+                tx.execute(
+                    "UPDATE messages SET reserved_until = :until
+                     WHERE queue_name = :queue_name AND cursor = :cursor",
+                    named_params! {
+                        ":queue_name": queue_name,
+                        ":cursor": cursor,
+                        ":until": gmo.reservation().map(to_millis),
+                    },
+                )?;
+                message = load_message(tx, message.mid())?;
+            }
+        }
+        Ok(message)
+    }
+
+    /// Takes up to `limit` messages matching `gmo`, waiting as long as it
+    /// allows for the first to arrive.
+    async fn take_waiting(
+        &self,
+        gmo: &GetMessageOptions,
+        limit: usize,
+    ) -> Result<Vec<Message>, GetMessageError> {
+        let result = self
+            .waiters
+            .wait_for(gmo.queue_name(), gmo.wait(), || {
+                let mut connection = self.connection.lock().unwrap();
+                Self::get_messages_tx(&mut connection, gmo, limit, self.auto_create)?
+            })
+            .await;
+        if result.is_ok() && gmo.action() == GetMessageAction::Return {
+            self.waiters.wake(gmo.queue_name());
+        }
+        result
+    }
+
+    fn create_messages_tx(
+        connection: &mut Connection,
+        queue_name: &QueueName,
+        reqs: &[CreateMessageRequest],
+        auto_create: bool,
+    ) -> anyhow::Result<Result<Vec<Message>, CreateMessageError>> {
+        let tx = connection.transaction()?;
+        let now = to_millis(Instant::now());
+        tx.execute(
+            "DELETE FROM messages WHERE expires_at <= :now",
+            named_params! {":now": now},
+        )?;
+        dead_letter_lapsed(&tx, now, auto_create)?;
+        let reqs = match admit_messages(&tx, queue_name, reqs, auto_create)? {
+            Ok(reqs) => reqs,
+            Err(e) => return Ok(Err(e)),
+        };
+        let messages = Self::insert_messages(&tx, queue_name, &reqs)?;
+        trim_retained(&tx, now)?;
+        tx.commit()?;
+        Ok(Ok(messages))
+    }
+
+    fn publish_tx(
+        connection: &mut Connection,
+        topic: &QueueName,
+        req: &CreateMessageRequest,
+        auto_create: bool,
+    ) -> anyhow::Result<Result<Vec<(QueueName, Message)>, CreateMessageError>> {
+        let tx = connection.transaction()?;
+        let now = to_millis(Instant::now());
+        tx.execute(
+            "DELETE FROM messages WHERE expires_at <= :now",
+            named_params! {":now": now},
+        )?;
+        dead_letter_lapsed(&tx, now, auto_create)?;
+        // Every subscriber must take the message before any is given it.
+        let mut admitted = vec![];
+        for queue_name in subscribers(&tx, topic)? {
+            match admit_messages(&tx, &queue_name, std::slice::from_ref(req), auto_create)? {
+                Ok(reqs) => admitted.push((queue_name, reqs)),
+                Err(e) => return Ok(Err(e)),
+            }
+        }
+        let mut published = vec![];
+        for (queue_name, reqs) in admitted {
+            let mut messages = Self::insert_messages(&tx, &queue_name, &reqs)?;
+            published.push((queue_name, messages.remove(0)));
+        }
+        trim_retained(&tx, now)?;
+        tx.commit()?;
+        Ok(Ok(published))
+    }
+
+    /// Adds `reqs` to the end of `queue_name`, or hands back the original
+    /// message for any which duplicates an earlier one.
+    fn insert_messages(
+        tx: &Transaction,
+        queue_name: &QueueName,
+        reqs: &[CreateMessageRequest],
+    ) -> anyhow::Result<Vec<Message>> {
+        if let Some(req) = reqs.iter().find(|req| req.dedup_key().is_some()) {
+            tx.execute(
+                "DELETE FROM dedup WHERE queue_name = :queue_name AND created_at <= :cutoff",
+                named_params! {
+                    ":queue_name": queue_name.to_string(),
+                    ":cutoff": dedup_cutoff(req),
+                },
+            )?;
+        }
+        let mut messages = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            if let Some(original) = find_duplicate(tx, queue_name, req)? {
+                messages.push(original);
+                continue;
+            }
+            let cursor: i64 = tx.query_row(
+                "INSERT INTO queues (queue_name, max_serial) VALUES (:queue_name, 1)
+                 ON CONFLICT (queue_name) DO UPDATE SET max_serial = max_serial + 1
+                 RETURNING max_serial",
+                named_params! {":queue_name": queue_name.to_string()},
+                |row| row.get(0),
+            )?;
+            let message = req.message(Uuid::new_v4());
+            tx.execute(
+                "INSERT INTO messages
+                     (queue_name, cursor, mid, cid, content, content_type, expires_at,
+                      enqueued_at, priority, deliver_at, headers)
+                 VALUES
+                     (:queue_name, :cursor, :mid, :cid, :content, :content_type, :expires_at,
+                      :enqueued_at, :priority, :deliver_at, :headers)",
+                named_params! {
+                    ":queue_name": queue_name.to_string(),
+                    ":cursor": cursor,
+                    ":mid": message.mid().to_string(),
+                    ":cid": message.cid().map(|u| u.to_string()),
+                    ":content": content_to_sql(message.content()),
+                    ":content_type": match message.content() {
+                        Content::Text(_) => None,
+                        Content::Binary { content_type, .. } => Some(content_type),
+                    },
+                    ":expires_at": req.expiry().copied().map(to_millis),
+                    ":enqueued_at": system_to_millis(message.enqueued_at()),
+                    ":priority": req.priority(),
+                    ":deliver_at": req.deliver_at().copied().map(to_millis),
+                    ":headers": serde_json::to_string(message.headers())?,
+                },
+            )?;
+            if let Some(key) = req.dedup_key() {
+                tx.execute(
+                    "INSERT OR REPLACE INTO dedup (queue_name, dedup_key, mid, created_at)
+                     VALUES (:queue_name, :dedup_key, :mid, :created_at)",
+                    named_params! {
+                        ":queue_name": queue_name.to_string(),
+                        ":dedup_key": key,
+                        ":mid": message.mid().to_string(),
+                        ":created_at": system_to_millis(message.enqueued_at()),
+                    },
+                )?;
+            }
+            messages.push(load_message(tx, message.mid())?);
+        }
+        Ok(messages)
+    }
+
+    fn commit_group_tx(
+        connection: &mut Connection,
+        queue_name: &QueueName,
+        group: &QueueName,
+        cursor: Option<usize>,
+    ) -> anyhow::Result<Result<ConsumerGroup, ConsumerGroupError>> {
+        let tx = connection.transaction()?;
+        let max_serial: Option<i64> = tx
+            .query_row(
+                "SELECT max_serial FROM queues WHERE queue_name = :queue_name",
+                named_params! {":queue_name": queue_name.to_string()},
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(max_serial) = max_serial else {
+            return Ok(Err(no_group(queue_name, group)));
+        };
+        let committed = match cursor {
+            Some(cursor) if cursor as i64 > max_serial => {
+                return Ok(Err(ConsumerGroupError::InvalidCursor(format!(
+                    "{} is past the end of {}",
+                    cursor, queue_name
+                ))))
+            }
+            Some(cursor) => cursor,
+            None => match load_group(&tx, queue_name, group)? {
+                Some(found) => found.position(),
+                None => return Ok(Err(no_group(queue_name, group))),
+            },
+        };
+        tx.execute(
+            "INSERT INTO consumer_groups (queue_name, group_name, position, committed)
+             VALUES (:queue_name, :group_name, :committed, :committed)
+             ON CONFLICT (queue_name, group_name) DO UPDATE
+             SET position = excluded.position, committed = excluded.committed",
+            named_params! {
+                ":queue_name": queue_name.to_string(),
+                ":group_name": group.to_string(),
+                ":committed": committed as i64,
+            },
+        )?;
+        tx.commit()?;
+        Ok(Ok(ConsumerGroup::new(
+            queue_name.clone(),
+            group.clone(),
+            committed,
+            committed,
+        )))
+    }
+
+    fn set_retention_policy_tx(
+        connection: &mut Connection,
+        queue_name: &QueueName,
+        policy: Option<&RetentionPolicy>,
+    ) -> anyhow::Result<Result<(), RetentionPolicyError>> {
+        let tx = connection.transaction()?;
+        if !queue_exists(&tx, queue_name)? {
+            return Ok(Err(RetentionPolicyError::NoQueue(format!(
+                "no queue {}",
+                queue_name
+            ))));
+        }
+        set_retention(&tx, queue_name, policy)?;
+        trim_retained(&tx, to_millis(Instant::now()))?;
+        tx.commit()?;
+        Ok(Ok(()))
+    }
+
+    fn create_queue_tx(
+        connection: &mut Connection,
+        queue_name: &QueueName,
+        settings: &QueueSettings,
+    ) -> anyhow::Result<bool> {
+        let tx = connection.transaction()?;
+        let created = !queue_exists(&tx, queue_name)?;
+        let dead_letter = settings.dead_letter();
+        tx.execute(
+            "INSERT INTO queues (queue_name, max_serial, max_deliveries, dead_letter_queue)
+             VALUES (:queue_name, 0, :max_deliveries, :dead_letter_queue)
+             ON CONFLICT (queue_name) DO UPDATE
+             SET max_deliveries = excluded.max_deliveries,
+                 dead_letter_queue = excluded.dead_letter_queue",
+            named_params! {
+                ":queue_name": queue_name.to_string(),
+                ":max_deliveries": dead_letter.map(|p| p.max_deliveries()),
+                ":dead_letter_queue": dead_letter.map(|p| p.queue_name().to_string()),
+            },
+        )?;
+        set_retention(&tx, queue_name, settings.retention())?;
+        set_config(&tx, queue_name, settings.config())?;
+        trim_retained(&tx, to_millis(Instant::now()))?;
+        tx.commit()?;
+        Ok(created)
+    }
+
+    fn delete_queue_tx(
+        connection: &mut Connection,
+        queue_name: &QueueName,
+    ) -> anyhow::Result<Result<(), QueueError>> {
+        let tx = connection.transaction()?;
+        let params = named_params! {":queue_name": queue_name.to_string()};
+        if tx.execute("DELETE FROM queues WHERE queue_name = :queue_name", params)? == 0 {
+            return Ok(Err(QueueError::NoQueue(format!("no queue {}", queue_name))));
+        }
+        for table in ["messages", "dedup", "consumer_groups", "subscriptions"] {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE queue_name = :queue_name", table),
+                params,
+            )?;
+        }
+        tx.commit()?;
+        Ok(Ok(()))
+    }
+
+    fn purge_queue_tx(
+        connection: &mut Connection,
+        queue_name: &QueueName,
+    ) -> anyhow::Result<Result<usize, QueueError>> {
+        let tx = connection.transaction()?;
+        if !queue_exists(&tx, queue_name)? {
+            return Ok(Err(QueueError::NoQueue(format!("no queue {}", queue_name))));
+        }
+        let purged = tx.execute(
+            "DELETE FROM messages WHERE queue_name = :queue_name",
+            named_params! {":queue_name": queue_name.to_string()},
+        )?;
+        tx.commit()?;
+        Ok(Ok(purged))
+    }
+
+    fn redrive_tx(
+        connection: &mut Connection,
+        queue_name: &QueueName,
+        req: &RedriveRequest,
+        auto_create: bool,
+    ) -> anyhow::Result<Result<Vec<Message>, RedriveError>> {
+        let tx = connection.transaction()?;
+        let now = to_millis(Instant::now());
+        tx.execute(
+            "DELETE FROM messages WHERE expires_at <= :now",
+            named_params! {":now": now},
+        )?;
+        dead_letter_lapsed(&tx, now, auto_create)?;
+        let missing = if !queue_exists(&tx, queue_name)? {
+            Some(queue_name)
+        } else if !auto_create && !queue_exists(&tx, req.target())? {
+            Some(req.target())
+        } else {
+            None
+        };
+        if let Some(missing) = missing {
+            tx.commit()?;
+            return Ok(Err(RedriveError::NoQueue(format!("no queue {}", missing))));
+        }
+        let mids = tx
+            .prepare_cached(
+                "SELECT mid FROM messages
+                 WHERE queue_name = :queue_name
+                   AND (reserved_until IS NULL OR reserved_until <= :now)
+                   AND (:mid IS NULL OR mid = :mid)
+                   AND (:cid IS NULL OR cid = :cid)
+                   AND (:after IS NULL OR cursor > :after)
+                   AND (:until IS NULL OR cursor <= :until)
+                 ORDER BY cursor",
+            )?
+            .query_map(
+                named_params! {
+                    ":queue_name": queue_name.to_string(),
+                    ":mid": req.mid().map(|u| u.to_string()),
+                    ":cid": req.cid().map(|u| u.to_string()),
+                    ":after": req.after().map(|c| c as i64),
+                    ":until": req.until().map(|c| c as i64),
+                    ":now": now,
+                },
+                |row| parse_uuid(0, row.get(0)?),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut moved = vec![];
+        for mid in mids {
+            move_message(&tx, &mid, req.target())?;
+            tx.execute(
+                "UPDATE messages SET deliveries = 0 WHERE mid = :mid",
+                named_params! {":mid": mid.to_string()},
+            )?;
+            moved.push(load_message(&tx, &mid)?);
+        }
+        trim_retained(&tx, now)?;
+        tx.commit()?;
+        Ok(Ok(moved))
+    }
+}
+
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let tx = connection.transaction()?;
+    let version: usize = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for migration in MIGRATIONS.iter().skip(version) {
+        tx.execute_batch(migration)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    tx.commit()
+}
+
+fn load_message(tx: &Transaction, mid: &Uuid) -> rusqlite::Result<Message> {
+    tx.query_row(
+        &format!("SELECT {} FROM messages WHERE mid = :mid", MESSAGE_COLUMNS),
+        named_params! {":mid": mid.to_string()},
+        message_from_row,
+    )
+}
+
+/// Keys remembered from at or before this time no longer deduplicate `req`.
+fn dedup_cutoff(req: &CreateMessageRequest) -> i64 {
+    system_to_millis(wall_clock::to_system_time(Instant::now()) - req.dedup_window())
+}
+
+/// The message created with `req`'s deduplication key, if `req` is a
+/// duplicate of it.
+fn find_duplicate(
+    tx: &Transaction,
+    queue_name: &QueueName,
+    req: &CreateMessageRequest,
+) -> anyhow::Result<Option<Message>> {
+    let Some(key) = req.dedup_key() else {
+        return Ok(None);
+    };
+    let original = tx
+        .query_row(
+            "SELECT mid, created_at FROM dedup
+             WHERE queue_name = :queue_name AND dedup_key = :dedup_key AND created_at > :cutoff",
+            named_params! {
+                ":queue_name": queue_name.to_string(),
+                ":dedup_key": key,
+                ":cutoff": dedup_cutoff(req),
+            },
+            |row| Ok((parse_uuid(0, row.get(0)?)?, row.get::<_, i64>(1)?)),
+        )
+        .optional()?;
+    let Some((mid, created_at)) = original else {
+        return Ok(None);
+    };
+    let message = load_message(tx, &mid)
+        .optional()?
+        .unwrap_or_else(|| req.duplicate_of(mid, system_from_millis(created_at)));
+    Ok(Some(message))
+}
+
+fn dead_letter_policy(
+    tx: &Transaction,
+    queue_name: &QueueName,
+) -> anyhow::Result<Option<DeadLetterPolicy>> {
+    let policy: Option<(Option<u32>, Option<String>)> = tx
+        .query_row(
+            "SELECT max_deliveries, dead_letter_queue FROM queues WHERE queue_name = :queue_name",
+            named_params! {":queue_name": queue_name.to_string()},
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    match policy {
+        Some((Some(max_deliveries), Some(dead_letter_queue))) => Ok(Some(DeadLetterPolicy::new(
+            max_deliveries,
+            dead_letter_queue.try_into()?,
+        ))),
+        _ => Ok(None),
+    }
+}
+
+/// Moves a message to the end of `target`, unreserved and with a new cursor.
+fn move_message(tx: &Transaction, mid: &Uuid, target: &QueueName) -> rusqlite::Result<()> {
+    let cursor: i64 = tx.query_row(
+        "INSERT INTO queues (queue_name, max_serial) VALUES (:queue_name, 1)
+         ON CONFLICT (queue_name) DO UPDATE SET max_serial = max_serial + 1
+         RETURNING max_serial",
+        named_params! {":queue_name": target.to_string()},
+        |row| row.get(0),
+    )?;
+    tx.execute(
+        "UPDATE messages
+         SET queue_name = :queue_name, cursor = :cursor, reserved_until = NULL, receipt = NULL
+         WHERE mid = :mid",
+        named_params! {
+            ":queue_name": target.to_string(),
+            ":cursor": cursor,
+            ":mid": mid.to_string(),
+        },
+    )?;
+    Ok(())
+}
+
+/// Dead-letters messages whose last reservation lapsed after they had used up
+/// their deliveries, unless the dead-letter queue does not exist and
+/// `auto_create` does not allow it to be made.
+fn dead_letter_lapsed(tx: &Transaction, now: i64, auto_create: bool) -> anyhow::Result<()> {
+    let lapsed = tx
+        .prepare_cached(
+            "SELECT m.mid, q.dead_letter_queue FROM messages m JOIN queues q USING (queue_name)
+             WHERE q.max_deliveries IS NOT NULL AND m.reserved_until <= :now
+               AND m.deliveries >= q.max_deliveries
+               AND (:auto_create OR q.dead_letter_queue IN (SELECT queue_name FROM queues))",
+        )?
+        .query_map(
+            named_params! {":now": now, ":auto_create": auto_create},
+            |row| Ok((parse_uuid(0, row.get(0)?)?, row.get::<_, String>(1)?)),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (mid, dead_letter_queue) in lapsed {
+        move_message(tx, &mid, &dead_letter_queue.try_into()?)?;
+    }
+    Ok(())
+}
+
+/// Turns retention on for `queue_name` with `policy`, or off with `None`.
+fn set_retention(
+    tx: &Transaction,
+    queue_name: &QueueName,
+    policy: Option<&RetentionPolicy>,
+) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO queues
+             (queue_name, max_serial, retained, retention_max_age, retention_max_messages)
+         VALUES (:queue_name, 0, :retained, :max_age, :max_messages)
+         ON CONFLICT (queue_name) DO UPDATE
+         SET retained = excluded.retained,
+             retention_max_age = excluded.retention_max_age,
+             retention_max_messages = excluded.retention_max_messages",
+        named_params! {
+            ":queue_name": queue_name.to_string(),
+            ":retained": policy.is_some(),
+            ":max_age": policy.and_then(|p| p.max_age()).map(|d| d.as_millis() as i64),
+            ":max_messages": policy.and_then(|p| p.max_messages()).map(|n| n as i64),
+        },
+    )?;
+    if policy.is_none() {
+        // Without a policy, messages are no longer kept once consumed.
+        tx.execute(
+            "DELETE FROM messages WHERE queue_name = :queue_name AND consumed",
+            named_params! {":queue_name": queue_name.to_string()},
+        )?;
+    }
+    Ok(())
+}
+
+fn set_config(
+    tx: &Transaction,
+    queue_name: &QueueName,
+    config: &QueueConfig,
+) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO queues
+             (queue_name, max_serial, default_expiry, default_reservation, max_depth,
+              max_content_size)
+         VALUES (:queue_name, 0, :expiry, :reservation, :max_depth, :max_content_size)
+         ON CONFLICT (queue_name) DO UPDATE
+         SET default_expiry = excluded.default_expiry,
+             default_reservation = excluded.default_reservation,
+             max_depth = excluded.max_depth,
+             max_content_size = excluded.max_content_size",
+        named_params! {
+            ":queue_name": queue_name.to_string(),
+            ":expiry": config.expiry().map(|d| d.as_millis() as i64),
+            ":reservation": config.reservation().map(|d| d.as_millis() as i64),
+            ":max_depth": config.max_depth().map(|n| n as i64),
+            ":max_content_size": config.max_content_size().map(|n| n as i64),
+        },
+    )?;
+    Ok(())
+}
+
+/// The config of `queue_name`, if it exists.
+fn queue_config(tx: &Transaction, queue_name: &QueueName) -> rusqlite::Result<Option<QueueConfig>> {
+    tx.query_row(
+        "SELECT default_expiry, default_reservation, max_depth, max_content_size
+         FROM queues WHERE queue_name = :queue_name",
+        named_params! {":queue_name": queue_name.to_string()},
+        |row| {
+            let millis = |i| -> rusqlite::Result<Option<Duration>> {
+                Ok(row
+                    .get::<_, Option<i64>>(i)?
+                    .map(|m| Duration::from_millis(m as u64)))
+            };
+            Ok(QueueConfig::default()
+                .with_expiry(millis(0)?)
+                .with_reservation(millis(1)?)
+                .with_max_depth(row.get::<_, Option<i64>>(2)?.map(|n| n as usize))
+                .with_max_content_size(row.get::<_, Option<i64>>(3)?.map(|n| n as usize)))
+        },
+    )
+    .optional()
+}
+
+/// `reqs` as `queue_name` takes them, or why it refuses them.  A queue which
+/// does not exist yet is judged by the default config it would be made with,
+/// if `auto_create` allows it to be made at all.
+fn admit_messages(
+    tx: &Transaction,
+    queue_name: &QueueName,
+    reqs: &[CreateMessageRequest],
+    auto_create: bool,
+) -> anyhow::Result<Result<Vec<CreateMessageRequest>, CreateMessageError>> {
+    if reqs.is_empty() {
+        return Ok(Ok(vec![]));
+    }
+    let (config, depth, count) = match queue_config(tx, queue_name)? {
+        Some(config) => {
+            let depth: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM messages WHERE queue_name = :queue_name AND NOT consumed",
+                named_params! {":queue_name": queue_name.to_string()},
+                |row| row.get(0),
+            )?;
+            let mut count = 0;
+            for req in reqs {
+                if find_duplicate(tx, queue_name, req)?.is_none() {
+                    count += 1;
+                }
+            }
+            (config, depth as usize, count)
+        }
+        None if auto_create => (QueueConfig::default(), 0, reqs.len()),
+        None => {
+            return Ok(Err(CreateMessageError::NoQueue(format!(
+                "no queue {}",
+                queue_name
+            ))))
+        }
+    };
+    if let Err(e) = config.admit(queue_name, depth, count) {
+        return Ok(Err(e));
+    }
+    Ok(reqs
+        .iter()
+        .map(|req| config.prepare(queue_name, req))
+        .collect())
+}
+
+fn retains(tx: &Transaction, queue_name: &QueueName) -> rusqlite::Result<bool> {
+    tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM queues WHERE queue_name = :queue_name AND retained)",
+        named_params! {":queue_name": queue_name.to_string()},
+        |row| row.get(0),
+    )
+}
+
+/// Trims every queue which retains its messages to its policy.
+fn trim_retained(tx: &Transaction, now: i64) -> anyhow::Result<()> {
+    tx.execute(
+        "DELETE FROM messages
+         WHERE enqueued_at <= :now - (SELECT retention_max_age FROM queues
+                                      WHERE queue_name = messages.queue_name AND retained)",
+        named_params! {":now": now},
+    )?;
+    let limits = tx
+        .prepare_cached(
+            "SELECT queue_name, retention_max_messages FROM queues
+             WHERE retained AND retention_max_messages IS NOT NULL",
+        )?
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (queue_name, max_messages) in limits {
+        tx.execute(
+            "DELETE FROM messages
+             WHERE queue_name = :queue_name
+               AND cursor <= (SELECT cursor FROM messages WHERE queue_name = :queue_name
+                              ORDER BY cursor DESC LIMIT 1 OFFSET :max_messages)",
+            named_params! {":queue_name": queue_name, ":max_messages": max_messages},
+        )?;
+    }
+    Ok(())
+}
+
+/// Explains why nothing in the queue matched `gmo`, from the messages the
+/// receipt or mid refer to.
+fn no_match(tx: &Transaction, gmo: &GetMessageOptions) -> rusqlite::Result<GetMessageError> {
+    let candidates = tx
+        .prepare_cached(&format!(
+            "SELECT {} FROM messages
+             WHERE queue_name = :queue_name AND (receipt = :receipt OR mid = :mid)",
+            MESSAGE_COLUMNS
+        ))?
+        .query_map(
+            named_params! {
+                ":queue_name": gmo.queue_name().to_string(),
+                ":receipt": gmo.receipt().map(|u| u.to_string()),
+                ":mid": gmo.mid().map(|u| u.to_string()),
+            },
+            message_from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(gmo.no_match(candidates.iter()))
+}
+
+fn queue_exists(tx: &Transaction, queue_name: &QueueName) -> rusqlite::Result<bool> {
+    tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM queues WHERE queue_name = :queue_name)",
+        named_params! {":queue_name": queue_name.to_string()},
+        |row| row.get(0),
+    )
+}
+
+fn to_millis(instant: Instant) -> i64 {
+    system_to_millis(wall_clock::to_system_time(instant))
+}
+
+fn from_millis(millis: i64) -> Instant {
+    wall_clock::from_system_time(system_from_millis(millis))
+}
+
+fn system_to_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+fn system_from_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+fn parse_uuid(idx: usize, s: String) -> rusqlite::Result<Uuid> {
+    Uuid::try_parse(&s).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+/// The position of `group`, which is made at the start of the queue if it
+/// is new.
+fn group_position(
+    tx: &Transaction,
+    queue_name: &QueueName,
+    group: &QueueName,
+) -> rusqlite::Result<usize> {
+    tx.execute(
+        "INSERT OR IGNORE INTO consumer_groups (queue_name, group_name, position, committed)
+         VALUES (:queue_name, :group_name, 0, 0)",
+        named_params! {
+            ":queue_name": queue_name.to_string(),
+            ":group_name": group.to_string(),
+        },
+    )?;
+    tx.query_row(
+        "SELECT position FROM consumer_groups
+         WHERE queue_name = :queue_name AND group_name = :group_name",
+        named_params! {
+            ":queue_name": queue_name.to_string(),
+            ":group_name": group.to_string(),
+        },
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|position| position as usize)
+}
+
+fn load_group(
+    tx: &Transaction,
+    queue_name: &QueueName,
+    group: &QueueName,
+) -> rusqlite::Result<Option<ConsumerGroup>> {
+    tx.query_row(
+        "SELECT position, committed FROM consumer_groups
+         WHERE queue_name = :queue_name AND group_name = :group_name",
+        named_params! {
+            ":queue_name": queue_name.to_string(),
+            ":group_name": group.to_string(),
+        },
+        |row| {
+            Ok(ConsumerGroup::new(
+                queue_name.clone(),
+                group.clone(),
+                row.get::<_, i64>(0)? as usize,
+                row.get::<_, i64>(1)? as usize,
+            ))
+        },
+    )
+    .optional()
+}
+
+fn no_group(queue_name: &QueueName, group: &QueueName) -> ConsumerGroupError {
+    ConsumerGroupError::NoGroup(format!("no group {} on {}", group, queue_name))
+}
+
+/// The queues subscribed to `topic`, in name order.
+fn subscribers(tx: &Transaction, topic: &QueueName) -> anyhow::Result<Vec<QueueName>> {
+    let names = tx
+        .prepare_cached(
+            "SELECT queue_name FROM subscriptions WHERE topic = :topic ORDER BY queue_name",
+        )?
+        .query_map(named_params! {":topic": topic.to_string()}, |row| {
+            row.get::<_, String>(0)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    names.into_iter().map(|name| Ok(name.try_into()?)).collect()
+}
+
+/// Text is stored as TEXT and binary content as a BLOB, so that text stays
+/// readable with ordinary SQL tools.
+fn content_to_sql(content: &Content) -> ToSqlOutput<'_> {
+    match content {
+        Content::Text(s) => ToSqlOutput::Borrowed(ValueRef::Text(s.as_bytes())),
+        Content::Binary { bytes, .. } => ToSqlOutput::Borrowed(ValueRef::Blob(bytes)),
+    }
+}
+
+fn message_from_row(row: &Row) -> rusqlite::Result<Message> {
+    let mid = parse_uuid(0, row.get(0)?)?;
+    let cid = row
+        .get::<_, Option<String>>(1)?
+        .map(|s| parse_uuid(1, s))
+        .transpose()?;
+    let cursor: i64 = row.get(2)?;
+    let reserved_until: Option<i64> = row.get(4)?;
+    let expires_at: Option<i64> = row.get(5)?;
+    let enqueued_at: i64 = row.get(6)?;
+    let first_reserved_at: Option<i64> = row.get(7)?;
+    let deliveries: u32 = row.get(8)?;
+    let priority: i32 = row.get(9)?;
+    let deliver_at: Option<i64> = row.get(10)?;
+    let receipt = row
+        .get::<_, Option<String>>(11)?
+        .map(|s| parse_uuid(11, s))
+        .transpose()?;
+    let headers = serde_json::from_str(&row.get::<_, String>(12)?).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(12, rusqlite::types::Type::Text, e.into())
+    })?;
+    let content = match row.get::<_, Option<String>>(13)? {
+        None => Content::Text(row.get(3)?),
+        Some(content_type) => Content::binary(content_type, row.get(3)?),
+    };
+    let consumed: bool = row.get(14)?;
+
+    let mut message = Message::new(mid, cid, content, expires_at.map(from_millis));
+    message.set_cursor(cursor as usize);
+    message.set_priority(priority);
+    message.set_headers(headers);
+    message.set_consumed(consumed);
+    message.set_deliver_at(&deliver_at.map(from_millis));
+    message.set_receipt(receipt);
+    message.set_reservation(&reserved_until.map(from_millis));
+    message.restore_history(
+        system_from_millis(enqueued_at),
+        first_reserved_at.map(system_from_millis),
+        deliveries,
+    );
+    Ok(message)
+}
+
+impl MessageRepository for Sqlite {
+    async fn get_message(&self, gmo: GetMessageOptions) -> Result<Message, GetMessageError> {
+        let mut messages = self.take_waiting(&gmo, 1).await?;
+        Ok(messages.remove(0))
+    }
+
+    async fn get_messages(&self, gmo: GetMessageOptions) -> Result<Vec<Message>, GetMessageError> {
+        let limit = gmo.max_messages().unwrap_or(1);
+        self.take_waiting(&gmo, limit).await
+    }
+
+    async fn acknowledge_messages(
+        &self,
+        req: &AcknowledgeRequest,
+    ) -> Result<Vec<Result<Message, GetMessageError>>, GetMessageError> {
+        let results = {
+            let mut connection = self.connection.lock().unwrap();
+            Self::acknowledge_messages_tx(&mut connection, req, self.auto_create)?
+        };
+        if req.action() == GetMessageAction::Return && results.iter().any(Result::is_ok) {
+            self.waiters.wake(req.queue_name());
+        }
+        Ok(results)
+    }
+
+    async fn create_message(
+        &self,
+        queue_name: QueueName,
+        req: &CreateMessageRequest,
+    ) -> Result<Message, CreateMessageError> {
+        let mut messages = self
+            .create_messages(queue_name, std::slice::from_ref(req))
+            .await?;
+        Ok(messages.remove(0))
+    }
+
+    async fn create_messages(
+        &self,
+        queue_name: QueueName,
+        reqs: &[CreateMessageRequest],
+    ) -> Result<Vec<Message>, CreateMessageError> {
+        let messages = {
+            let mut connection = self.connection.lock().unwrap();
+            Self::create_messages_tx(&mut connection, &queue_name, reqs, self.auto_create)??
+        };
+        self.waiters.wake(&queue_name);
+        Ok(messages)
+    }
+
+    async fn queue_list(&self) -> Result<QueueList, QueueListError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare_cached("SELECT queue_name FROM queues")
+            .map_err(anyhow::Error::from)?;
+        let names = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(anyhow::Error::from)?;
+        Ok(QueueList(names))
+    }
+
+    async fn get_info(&self, gmo: GetMessageOptions) -> Result<QueueSummary, QueueSummaryError> {
+        let connection = self.connection.lock().unwrap();
+        let depth: Option<i64> = connection
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM messages
+                         WHERE queue_name = :queue_name AND NOT consumed)
+                 FROM queues WHERE queue_name = :queue_name",
+                named_params! {":queue_name": gmo.queue_name().to_string()},
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(anyhow::Error::from)?;
+        let depth = depth
+            .ok_or_else(|| QueueSummaryError::NoQueue(format!("no queue {}", gmo.queue_name())))?;
+        Ok(QueueSummary::new(gmo.queue_name(), depth as usize))
+    }
+
+    async fn set_dead_letter_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<DeadLetterPolicy>,
+    ) -> Result<(), DeadLetterPolicyError> {
+        let connection = self.connection.lock().unwrap();
+        let updated = connection
+            .execute(
+                "UPDATE queues SET max_deliveries = :max_deliveries,
+                                   dead_letter_queue = :dead_letter_queue
+                 WHERE queue_name = :queue_name",
+                named_params! {
+                    ":queue_name": queue_name.to_string(),
+                    ":max_deliveries": policy.as_ref().map(|p| p.max_deliveries()),
+                    ":dead_letter_queue": policy.as_ref().map(|p| p.queue_name().to_string()),
+                },
+            )
+            .map_err(anyhow::Error::from)?;
+        if updated == 0 {
+            return Err(DeadLetterPolicyError::NoQueue(format!(
+                "no queue {}",
+                queue_name
+            )));
+        }
+        Ok(())
+    }
+
+    async fn create_queue(
+        &self,
+        queue_name: QueueName,
+        settings: &QueueSettings,
+    ) -> Result<bool, QueueError> {
+        let mut connection = self.connection.lock().unwrap();
+        Ok(Self::create_queue_tx(
+            &mut connection,
+            &queue_name,
+            settings,
+        )?)
+    }
+
+    async fn delete_queue(&self, queue_name: QueueName) -> Result<(), QueueError> {
+        let mut connection = self.connection.lock().unwrap();
+        Self::delete_queue_tx(&mut connection, &queue_name)?
+    }
+
+    async fn purge_queue(&self, queue_name: QueueName) -> Result<usize, QueueError> {
+        let mut connection = self.connection.lock().unwrap();
+        Self::purge_queue_tx(&mut connection, &queue_name)?
+    }
+
+    async fn set_retention_policy(
+        &self,
+        queue_name: QueueName,
+        policy: Option<RetentionPolicy>,
+    ) -> Result<(), RetentionPolicyError> {
+        let mut connection = self.connection.lock().unwrap();
+        Self::set_retention_policy_tx(&mut connection, &queue_name, policy.as_ref())?
+    }
+
+    async fn redrive(
+        &self,
+        queue_name: QueueName,
+        req: &RedriveRequest,
+    ) -> Result<Vec<Message>, RedriveError> {
+        let moved = {
+            let mut connection = self.connection.lock().unwrap();
+            Self::redrive_tx(&mut connection, &queue_name, req, self.auto_create)??
+        };
+        self.waiters.wake(req.target());
+        Ok(moved)
+    }
+
+    async fn publish(
+        &self,
+        topic: QueueName,
+        req: &CreateMessageRequest,
+    ) -> Result<Vec<(QueueName, Message)>, CreateMessageError> {
+        let published = {
+            let mut connection = self.connection.lock().unwrap();
+            Self::publish_tx(&mut connection, &topic, req, self.auto_create)??
+        };
+        for (queue_name, _) in &published {
+            self.waiters.wake(queue_name);
+        }
+        Ok(published)
+    }
+
+    async fn subscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> Result<(), SubscriptionError> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction().map_err(anyhow::Error::from)?;
+        if !queue_exists(&tx, &queue_name).map_err(anyhow::Error::from)? {
+            return Err(SubscriptionError::NoQueue(format!(
+                "no queue {}",
+                queue_name
+            )));
+        }
+        tx.execute(
+            "INSERT OR IGNORE INTO subscriptions (topic, queue_name) VALUES (:topic, :queue_name)",
+            named_params! {":topic": topic.to_string(), ":queue_name": queue_name.to_string()},
+        )
+        .map_err(anyhow::Error::from)?;
+        tx.commit().map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        topic: QueueName,
+        queue_name: QueueName,
+    ) -> Result<(), SubscriptionError> {
+        let connection = self.connection.lock().unwrap();
+        let deleted = connection
+            .execute(
+                "DELETE FROM subscriptions WHERE topic = :topic AND queue_name = :queue_name",
+                named_params! {":topic": topic.to_string(), ":queue_name": queue_name.to_string()},
+            )
+            .map_err(anyhow::Error::from)?;
+        if deleted == 0 {
+            return Err(SubscriptionError::NoSubscription(format!(
+                "{} is not subscribed to {}",
+                queue_name, topic
+            )));
+        }
+        Ok(())
+    }
+
+    async fn subscriptions(&self, topic: QueueName) -> Result<Vec<QueueName>, SubscriptionError> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction().map_err(anyhow::Error::from)?;
+        Ok(subscribers(&tx, &topic)?)
+    }
+
+    async fn consumer_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> Result<ConsumerGroup, ConsumerGroupError> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction().map_err(anyhow::Error::from)?;
+        load_group(&tx, &queue_name, &group)
+            .map_err(anyhow::Error::from)?
+            .ok_or_else(|| no_group(&queue_name, &group))
+    }
+
+    async fn commit_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+        cursor: Option<usize>,
+    ) -> Result<ConsumerGroup, ConsumerGroupError> {
+        let mut connection = self.connection.lock().unwrap();
+        Self::commit_group_tx(&mut connection, &queue_name, &group, cursor)?
+    }
+
+    async fn delete_group(
+        &self,
+        queue_name: QueueName,
+        group: QueueName,
+    ) -> Result<(), ConsumerGroupError> {
+        let connection = self.connection.lock().unwrap();
+        let deleted = connection
+            .execute(
+                "DELETE FROM consumer_groups
+                 WHERE queue_name = :queue_name AND group_name = :group_name",
+                named_params! {
+                    ":queue_name": queue_name.to_string(),
+                    ":group_name": group.to_string(),
+                },
+            )
+            .map_err(anyhow::Error::from)?;
+        if deleted == 0 {
+            return Err(no_group(&queue_name, &group));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_instant::global::MockClock;
+    use std::collections::{BTreeMap, HashMap};
+
+    macro_rules! gmo {
+            ($($arg:tt)*) => {{
+            let string = format!($($arg)*);
+            let gmo: GetMessageOptions = serde_json::from_str::<HashMap<String, String>>(&string)
+            .unwrap()
+            .try_into()
+            .unwrap();
+             gmo
+        }}
+    }
+
+    async fn put(
+        store: &Sqlite,
+        queue: &str,
+        data: &str,
+        cid: Option<&str>,
+        expiry: Option<u64>,
+    ) -> Message {
+        let req = CreateMessageRequest::new(
+            data.to_string(),
+            cid.map(|s| Uuid::try_parse(s).unwrap()),
+            expiry.map(|i| Instant::now() + Duration::from_secs(i)),
+        );
+        store
+            .create_message(queue.to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap()
+    }
+
+    async fn depth(store: &Sqlite, queue_name: &str) -> usize {
+        let gmo = gmo!(r#"{{"action":"query","queue_name":"{}"}}"#, queue_name);
+        store.get_info(gmo).await.unwrap().depth()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_message() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let gmo = gmo!(r#"{{"action":"query","queue_name":"queue1"}}"#);
+        assert!(store.get_info(gmo).await.is_err());
+
+        let msg1 = put(&store, "queue1", "msg1", None, None).await;
+        let msg2 = put(&store, "queue1", "msg2", None, None).await;
+        assert_eq!(msg1.content().text(), Some("msg1"));
+        assert_eq!(msg1.cursor(), 1);
+        assert_eq!(msg2.cursor(), 2);
+        assert_eq!(depth(&store, "queue1").await, 2);
+        assert_eq!(
+            store.queue_list().await.unwrap(),
+            QueueList(vec!["queue1".to_string()])
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_browse_and_get_message() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let cid = Uuid::new_v4().to_string();
+        let msg1 = put(&store, "queue1", "msg1", None, None).await;
+        let msg2 = put(&store, "queue1", "msg2", Some(&cid), None).await;
+        let msg3 = put(&store, "queue1", "msg3", None, None).await;
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg1);
+        let gmo = gmo!(
+            r#"{{"action":"browse","queue_name":"queue1","cid":"{}"}}"#,
+            cid
+        );
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg2);
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1","after":"2"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg3);
+        assert_eq!(depth(&store, "queue1").await, 3);
+
+        let gmo = gmo!(
+            r#"{{"action":"get","queue_name":"queue1","mid":"{}"}}"#,
+            msg2.mid()
+        );
+        assert_eq!(store.get_message(gmo.clone()).await.unwrap(), msg2);
+        assert!(store.get_message(gmo).await.is_err());
+        assert_eq!(depth(&store, "queue1").await, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reserve_confirm_return() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let msg1 = put(&store, "queue1", "msg1", None, None).await;
+        let msg2 = put(&store, "queue1", "msg2", None, None).await;
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let reserved = store.get_message(reserve.clone()).await.unwrap();
+        assert_eq!(reserved.mid(), msg1.mid());
+        assert!(reserved.is_reserved());
+
+        let gmo = gmo!(
+            r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        let returned = store.get_message(gmo.clone()).await.unwrap();
+        assert!(!returned.is_reserved());
+        assert!(store.get_message(gmo).await.is_err());
+
+        let reserved = store.get_message(reserve.clone()).await.unwrap();
+        assert_eq!(reserved.mid(), msg1.mid());
+        let reserved = store.get_message(reserve.clone()).await.unwrap();
+        assert_eq!(reserved.mid(), msg2.mid());
+        assert!(store.get_message(reserve).await.is_err());
+
+        let gmo = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        assert!(store.get_message(gmo).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_extend_reservation() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None);
+        let msg1 = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap();
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let reserved = store.get_message(reserve).await.unwrap();
+        let receipt = *reserved.receipt().unwrap();
+        let extend = gmo!(
+            r#"{{"action":"extend","queue_name":"queue1","receipt":"{}","reservation_seconds":"20"}}"#,
+            receipt
+        );
+        MockClock::advance(Duration::from_secs(5));
+        let extended = store.get_message(extend.clone()).await.unwrap();
+        assert!(extended.reserved_until() > reserved.reserved_until());
+        assert_eq!(extended.deliveries(), 1);
+
+        MockClock::advance(Duration::from_secs(10));
+        let browse = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert!(store.get_message(browse.clone()).await.is_err());
+        MockClock::advance(Duration::from_secs(20));
+        assert!(store.get_message(browse).await.is_ok());
+        let lapsed = store.get_message(extend).await;
+        assert!(matches!(lapsed, Err(GetMessageError::NotReserved(_))));
+
+        // Once someone else holds the message the old receipt is no use.
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let again = store.get_message(reserve).await.unwrap();
+        assert_eq!(again.mid(), msg1.mid());
+        assert_ne!(again.receipt(), Some(&receipt));
+        let stale = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            receipt
+        );
+        let stale = store.get_message(stale).await;
+        assert!(matches!(stale, Err(GetMessageError::NoMessage(_))));
+        let stale = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","mid":"{}","receipt":"{}"}}"#,
+            msg1.mid(),
+            receipt
+        );
+        let stale = store.get_message(stale).await;
+        assert!(matches!(stale, Err(GetMessageError::NotReserved(_))));
+        let confirm = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            again.receipt().unwrap()
+        );
+        assert!(store.get_message(confirm).await.is_ok());
+
+        let missing = gmo!(
+            r#"{{"action":"extend","queue_name":"queue1","receipt":"{}","reservation_seconds":"20"}}"#,
+            Uuid::new_v4()
+        );
+        let missing = store.get_message(missing).await;
+        assert!(matches!(missing, Err(GetMessageError::NoMessage(_))));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_messages() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let msg1 = put(&store, "queue1", "msg1", None, None).await;
+        let reqs = ["msg2", "msg3"]
+            .map(|s| CreateMessageRequest::new(s.to_string(), None, None).with_priority(1));
+        let created = store
+            .create_messages("queue1".to_string().try_into().unwrap(), &reqs)
+            .await
+            .unwrap();
+        assert_eq!(created.len(), 2);
+        assert_eq!(created[0].content().text(), Some("msg2"));
+        assert_eq!(created[0].cursor(), 2);
+        assert_eq!(created[1].cursor(), 3);
+        assert_eq!(created[1].priority(), 1);
+        assert_eq!(depth(&store, "queue1").await, 3);
+
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        for expected in [&created[0], &created[1], &msg1] {
+            assert_eq!(&store.get_message(gmo.clone()).await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_create_empty_batch() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let created = store
+            .create_messages("queue1".to_string().try_into().unwrap(), &[])
+            .await
+            .unwrap();
+        assert!(created.is_empty());
+        assert!(store.queue_list().await.unwrap().0.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_messages() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let mut messages = vec![];
+        for (content, priority) in [("msg1", 0), ("msg2", 0), ("msg3", 5), ("msg4", 0)] {
+            let req =
+                CreateMessageRequest::new(content.to_string(), None, None).with_priority(priority);
+            let message = store
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+            messages.push(message);
+        }
+        let mids = |found: &[Message]| found.iter().map(|m| *m.mid()).collect::<Vec<_>>();
+
+        let browse = gmo!(r#"{{"action":"browse","queue_name":"queue1","max_messages":"3"}}"#);
+        let page = store.get_messages(browse).await.unwrap();
+        assert_eq!(mids(&page), mids(&messages[..3]));
+        let browse = gmo!(
+            r#"{{"action":"browse","queue_name":"queue1","max_messages":"3","after":"{}"}}"#,
+            page[2].cursor()
+        );
+        let page = store.get_messages(browse).await.unwrap();
+        assert_eq!(mids(&page), mids(&messages[3..]));
+
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","max_messages":"2","reservation_seconds":"10"}}"#
+        );
+        let reserved = store.get_messages(reserve).await.unwrap();
+        assert_eq!(
+            mids(&reserved),
+            vec![*messages[2].mid(), *messages[0].mid()]
+        );
+        assert!(reserved.iter().all(|m| m.receipt().is_some()));
+
+        let get = gmo!(r#"{{"action":"get","queue_name":"queue1","max_messages":"10"}}"#);
+        let got = store.get_messages(get.clone()).await.unwrap();
+        assert_eq!(mids(&got), vec![*messages[1].mid(), *messages[3].mid()]);
+        assert!(matches!(
+            store.get_messages(get).await,
+            Err(GetMessageError::NoMessage(_))
+        ));
+        assert_eq!(depth(&store, "queue1").await, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_acknowledge_messages() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let queue_name: QueueName = "queue1".to_string().try_into().unwrap();
+        for content in ["msg1", "msg2", "msg3"] {
+            let req = CreateMessageRequest::new(content.to_string(), None, None);
+            store
+                .create_message(queue_name.clone(), &req)
+                .await
+                .unwrap();
+        }
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","max_messages":"3","reservation_seconds":"10"}}"#
+        );
+        let reserved = store.get_messages(reserve).await.unwrap();
+        let receipts = reserved
+            .iter()
+            .map(|m| *m.receipt().unwrap())
+            .collect::<Vec<_>>();
+
+        let req = AcknowledgeRequest::new(
+            queue_name.clone(),
+            GetMessageAction::Confirm,
+            vec![receipts[0], Uuid::new_v4(), receipts[2]],
+        )
+        .unwrap();
+        let results = store.acknowledge_messages(&req).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().mid(), reserved[0].mid());
+        assert!(matches!(results[1], Err(GetMessageError::NoMessage(_))));
+        assert_eq!(results[2].as_ref().unwrap().mid(), reserved[2].mid());
+        assert_eq!(depth(&store, "queue1").await, 1);
+
+        let req = AcknowledgeRequest::new(
+            queue_name,
+            GetMessageAction::Return,
+            vec![receipts[1], receipts[0]],
+        )
+        .unwrap();
+        let results = store.acknowledge_messages(&req).await.unwrap();
+        assert!(!results[0].as_ref().unwrap().is_reserved());
+        assert!(results[1].is_err());
+        let browse = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(
+            store.get_message(browse).await.unwrap().mid(),
+            reserved[1].mid()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dedup_key() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let queue_name: QueueName = "queue1".to_string().try_into().unwrap();
+        let req = |content: &str| {
+            CreateMessageRequest::new(content.to_string(), None, None)
+                .with_dedup_key(Some("key1".to_string()))
+                .with_dedup_window(Duration::from_secs(60))
+        };
+        let msg1 = store
+            .create_message(queue_name.clone(), &req("msg1"))
+            .await
+            .unwrap();
+        let retried = store
+            .create_messages(queue_name.clone(), &[req("msg1"), req("msg1")])
+            .await
+            .unwrap();
+        assert!(retried.iter().all(|m| m.mid() == msg1.mid()));
+        assert_eq!(depth(&store, "queue1").await, 1);
+
+        let other = gmo!(r#"{{"action":"query","queue_name":"queue2"}}"#);
+        let msg2 = store
+            .create_message("queue2".to_string().try_into().unwrap(), &req("msg2"))
+            .await
+            .unwrap();
+        assert_ne!(msg2.mid(), msg1.mid());
+        assert_eq!(store.get_info(other).await.unwrap().depth(), 1);
+
+        let get = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(get).await.unwrap().mid(), msg1.mid());
+        MockClock::advance(Duration::from_secs(30));
+        let retried = store
+            .create_message(queue_name.clone(), &req("msg1"))
+            .await
+            .unwrap();
+        assert_eq!(retried.mid(), msg1.mid());
+        assert_eq!(retried.enqueued_at(), msg1.enqueued_at());
+        assert_eq!(depth(&store, "queue1").await, 0);
+
+        MockClock::advance(Duration::from_secs(31));
+        let msg3 = store
+            .create_message(queue_name.clone(), &req("msg1"))
+            .await
+            .unwrap();
+        assert_ne!(msg3.mid(), msg1.mid());
+        assert_eq!(depth(&store, "queue1").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_delayed_delivery() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None)
+            .with_deliver_at(Some(Instant::now() + Duration::from_secs(10)));
+        let msg1 = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap();
+        assert!(msg1.is_delayed());
+        assert!(msg1.deliver_at().is_some());
+        for action in ["browse", "get"] {
+            let gmo = gmo!(r#"{{"action":"{}","queue_name":"queue1"}}"#, action);
+            assert!(store.get_message(gmo).await.is_err());
+        }
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        assert!(store.get_message(reserve.clone()).await.is_err());
+        assert_eq!(depth(&store, "queue1").await, 1);
+
+        MockClock::advance(Duration::from_secs(15));
+        assert_eq!(store.get_message(reserve).await.unwrap().mid(), msg1.mid());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_priority() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let mut messages = vec![];
+        for (data, priority) in [("msg1", 0), ("msg2", 5), ("msg3", 0), ("msg4", 5)] {
+            let req =
+                CreateMessageRequest::new(data.to_string(), None, None).with_priority(priority);
+            let message = store
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+            messages.push(message);
+        }
+        assert_eq!(messages[1].priority(), 5);
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), messages[0]);
+        let gmo = gmo!(
+            r#"{{"action":"browse","queue_name":"queue1","order":"priority","after":"2","after_priority":"5"}}"#
+        );
+        assert_eq!(store.get_message(gmo).await.unwrap(), messages[3]);
+
+        let gmo =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        for idx in [1, 3, 0, 2] {
+            let reserved = store.get_message(gmo.clone()).await.unwrap();
+            assert_eq!(reserved.mid(), messages[idx].mid());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_headers() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let headers = BTreeMap::from([
+            ("content-type".to_string(), "application/json".to_string()),
+            ("trace-id".to_string(), "abc123".to_string()),
+        ]);
+        let req =
+            CreateMessageRequest::new("msg1".to_string(), None, None).with_headers(headers.clone());
+        let msg1 = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap();
+        assert_eq!(msg1.headers(), &headers);
+        let msg2 = put(&store, "queue1", "msg2", None, None).await;
+        assert!(msg2.headers().is_empty());
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap().headers(), &headers);
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1","max_messages":"2"}}"#);
+        let taken = store.get_messages(gmo).await.unwrap();
+        assert_eq!(taken, vec![msg1, msg2]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_binary_content() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let content = Content::binary("image/png".to_string(), vec![0x89, b'P', 0x00, 0xff]);
+        let req = CreateMessageRequest::new(content.clone(), None, None);
+        let msg1 = store
+            .create_message("queue1".to_string().try_into().unwrap(), &req)
+            .await
+            .unwrap();
+        assert_eq!(msg1.content(), &content);
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_selector() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let mut messages = vec![];
+        for (data, region, priority) in [("msg1", "us", 5), ("msg2", "eu", 1), ("msg3", "eu", 5)] {
+            let req = CreateMessageRequest::new(data.to_string(), None, None)
+                .with_priority(priority)
+                .with_headers(BTreeMap::from([("region".to_string(), region.to_string())]));
+            let message = store
+                .create_message("queue1".to_string().try_into().unwrap(), &req)
+                .await
+                .unwrap();
+            messages.push(message);
+        }
+
+        let gmo = gmo!(
+            r#"{{"action":"browse","queue_name":"queue1","selector":"region = 'eu'","max_messages":"5"}}"#
+        );
+        let browsed = store.get_messages(gmo).await.unwrap();
+        assert_eq!(browsed, vec![messages[1].clone(), messages[2].clone()]);
+        let gmo = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10","selector":"region = 'eu' AND priority > 3"}}"#
+        );
+        let reserved = store.get_message(gmo.clone()).await.unwrap();
+        assert_eq!(reserved.mid(), messages[2].mid());
+        assert!(matches!(
+            store.get_message(gmo).await,
+            Err(GetMessageError::NoMessage(_))
+        ));
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1","selector":"region IN ('eu')"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), messages[1]);
+        assert_eq!(depth(&store, "queue1").await, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_expired_reservation_and_message() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let msg1 = put(&store, "queue1", "msg1", None, None).await;
+        let _msg2 = put(&store, "queue1", "msg2", None, Some(10)).await;
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","mid":"{}","reservation_seconds":"10"}}"#,
+            msg1.mid()
+        );
+        assert!(store.get_message(reserve.clone()).await.is_ok());
+        assert!(store.get_message(reserve.clone()).await.is_err());
+
+        MockClock::advance(Duration::from_secs(15));
+        assert!(store.get_message(reserve).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 2);
+        let _msg3 = put(&store, "queue1", "msg3", None, None).await;
+        assert_eq!(depth(&store, "queue1").await, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dead_letter() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let dlq: QueueName = "dlq".to_string().try_into().unwrap();
+        let msg1 = put(&store, "queue1", "msg1", None, None).await;
+        store
+            .set_dead_letter_policy(
+                "queue1".to_string().try_into().unwrap(),
+                Some(DeadLetterPolicy::new(1, dlq)),
+            )
+            .await
+            .unwrap();
+        let msg2 = put(&store, "queue1", "msg2", None, None).await;
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","mid":"{}","reservation_seconds":"1000"}}"#,
+            msg1.mid()
+        );
+        let reserved = store.get_message(reserve).await.unwrap();
+        assert_eq!(reserved.deliveries(), 1);
+        let gmo = gmo!(
+            r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        assert!(store.get_message(gmo).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 1);
+        assert_eq!(depth(&store, "dlq").await, 1);
+
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","mid":"{}","reservation_seconds":"10"}}"#,
+            msg2.mid()
+        );
+        assert!(store.get_message(reserve).await.is_ok());
+        MockClock::advance(Duration::from_secs(15));
+        let _msg3 = put(&store, "queue1", "msg3", None, None).await;
+        assert_eq!(depth(&store, "queue1").await, 1);
+        assert_eq!(depth(&store, "dlq").await, 2);
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"dlq"}}"#);
+        let dead = store.get_message(gmo).await.unwrap();
+        assert_eq!(dead.mid(), msg1.mid());
+        assert_eq!(dead.cursor(), 1);
+        assert!(!dead.is_reserved());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_redrive() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let dlq: QueueName = "dlq".to_string().try_into().unwrap();
+        let source: QueueName = "queue1".to_string().try_into().unwrap();
+        let msg1 = put(&store, "queue1", "msg1", None, None).await;
+        store
+            .set_dead_letter_policy(source.clone(), Some(DeadLetterPolicy::new(1, dlq.clone())))
+            .await
+            .unwrap();
+        let _msg2 = put(&store, "queue1", "msg2", None, None).await;
+        let reserve = gmo!(
+            r#"{{"action":"reserve","queue_name":"queue1","mid":"{}","reservation_seconds":"1000"}}"#,
+            msg1.mid()
+        );
+        let reserved = store.get_message(reserve).await.unwrap();
+        let gmo = gmo!(
+            r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        assert!(store.get_message(gmo).await.is_ok());
+        assert_eq!(depth(&store, "dlq").await, 1);
+
+        let req = RedriveRequest::new(source.clone());
+        let moved = store.redrive(dlq.clone(), &req).await.unwrap();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].mid(), msg1.mid());
+        assert_eq!(moved[0].content(), msg1.content());
+        assert_eq!(moved[0].cursor(), 3);
+        assert_eq!(moved[0].deliveries(), 0);
+        assert_eq!(depth(&store, "dlq").await, 0);
+        assert_eq!(depth(&store, "queue1").await, 2);
+
+        let req = RedriveRequest::new(dlq).with_cursor_range(Some(2), None);
+        let moved = store.redrive(source, &req).await.unwrap();
+        assert_eq!(moved[0].mid(), msg1.mid());
+        assert_eq!(moved[0].cursor(), 2);
+        assert_eq!(depth(&store, "queue1").await, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_publish() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let req = CreateMessageRequest::new("order1".to_string(), None, None);
+        assert!(store
+            .publish(name("orders"), &req)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            store.subscribe(name("orders"), name("billing")).await,
+            Err(SubscriptionError::NoQueue(_))
+        ));
+        for queue_name in ["shipping", "billing"] {
+            store
+                .create_queue(name(queue_name), &QueueSettings::default())
+                .await
+                .unwrap();
+        }
+        for queue_name in ["shipping", "billing", "shipping"] {
+            store
+                .subscribe(name("orders"), name(queue_name))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            store.subscriptions(name("orders")).await.unwrap(),
+            vec![name("billing"), name("shipping")]
+        );
+        assert!(store
+            .subscriptions(name("queue1"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let published = store.publish(name("orders"), &req).await.unwrap();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].0, name("billing"));
+        assert_eq!(published[1].0, name("shipping"));
+        assert_ne!(published[0].1.mid(), published[1].1.mid());
+        let _order0 = put(&store, "orders", "order0", None, None).await;
+        assert_eq!(depth(&store, "orders").await, 1);
+
+        store
+            .unsubscribe(name("orders"), name("billing"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.unsubscribe(name("orders"), name("billing")).await,
+            Err(SubscriptionError::NoSubscription(_))
+        ));
+        let req = CreateMessageRequest::new("order2".to_string(), None, None);
+        store.publish(name("orders"), &req).await.unwrap();
+        assert_eq!(depth(&store, "billing").await, 1);
+        assert_eq!(depth(&store, "shipping").await, 2);
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"billing"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), published[0].1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_consumer_groups() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let mut messages = vec![];
+        for data in ["msg1", "msg2", "msg3"] {
+            messages.push(put(&store, "queue1", data, None, None).await);
+        }
+        let read = |group: &str| {
+            gmo!(
+                r#"{{"action":"browse","queue_name":"queue1","group":"{}","max_messages":"2"}}"#,
+                group
+            )
+        };
+        assert_eq!(
+            store.get_messages(read("group1")).await.unwrap(),
+            messages[..2]
+        );
+        assert_eq!(
+            store.get_messages(read("group1")).await.unwrap(),
+            messages[2..]
+        );
+        assert!(matches!(
+            store.get_messages(read("group1")).await,
+            Err(GetMessageError::NoMessage(_))
+        ));
+        assert_eq!(
+            store.get_messages(read("group2")).await.unwrap(),
+            messages[..2]
+        );
+        assert_eq!(depth(&store, "queue1").await, 3);
+
+        let group = store
+            .consumer_group(name("queue1"), name("group1"))
+            .await
+            .unwrap();
+        assert_eq!((group.position(), group.committed()), (3, 0));
+        let group = store
+            .commit_group(name("queue1"), name("group2"), None)
+            .await
+            .unwrap();
+        assert_eq!((group.position(), group.committed()), (2, 2));
+        let group = store
+            .commit_group(name("queue1"), name("group1"), Some(1))
+            .await
+            .unwrap();
+        assert_eq!((group.position(), group.committed()), (1, 1));
+        assert_eq!(
+            store.get_messages(read("group1")).await.unwrap(),
+            messages[1..]
+        );
+        assert!(matches!(
+            store
+                .commit_group(name("queue1"), name("group1"), Some(4))
+                .await,
+            Err(ConsumerGroupError::InvalidCursor(_))
+        ));
+        assert!(matches!(
+            store
+                .commit_group(name("queue1"), name("group3"), None)
+                .await,
+            Err(ConsumerGroupError::NoGroup(_))
+        ));
+
+        store
+            .delete_group(name("queue1"), name("group1"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.consumer_group(name("queue1"), name("group1")).await,
+            Err(ConsumerGroupError::NoGroup(_))
+        ));
+        assert_eq!(
+            store.get_messages(read("group1")).await.unwrap(),
+            messages[..2]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retention() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let policy = RetentionPolicy::new(Some(Duration::from_secs(60)), Some(3));
+        assert!(matches!(
+            store
+                .set_retention_policy(name("queue1"), Some(policy.clone()))
+                .await,
+            Err(RetentionPolicyError::NoQueue(_))
+        ));
+        store
+            .create_queue(name("queue1"), &QueueSettings::default())
+            .await
+            .unwrap();
+        store
+            .set_retention_policy(name("queue1"), Some(policy))
+            .await
+            .unwrap();
+        let mut messages = vec![];
+        for data in ["msg1", "msg2", "msg3"] {
+            messages.push(put(&store, "queue1", data, None, None).await);
+        }
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        let got = store.get_message(gmo.clone()).await.unwrap();
+        assert_eq!(got.mid(), messages[0].mid());
+        assert!(got.is_consumed());
+        let gmo2 =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let reserved = store.get_message(gmo2).await.unwrap();
+        assert_eq!(reserved.mid(), messages[1].mid());
+        let gmo3 = gmo!(
+            r#"{{"action":"confirm","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        assert!(store.get_message(gmo3).await.unwrap().is_consumed());
+        assert_eq!(depth(&store, "queue1").await, 1);
+        assert_eq!(
+            store.get_message(gmo.clone()).await.unwrap().mid(),
+            messages[2].mid()
+        );
+        assert!(matches!(
+            store.get_message(gmo).await,
+            Err(GetMessageError::NoMessage(_))
+        ));
+
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1","max_messages":"5"}}"#);
+        let browsed = store.get_messages(gmo.clone()).await.unwrap();
+        assert_eq!(
+            browsed.iter().map(Message::mid).collect::<Vec<_>>(),
+            messages.iter().map(Message::mid).collect::<Vec<_>>()
+        );
+        let _msg4 = put(&store, "queue1", "msg4", None, None).await;
+        let browsed = store.get_messages(gmo.clone()).await.unwrap();
+        assert_eq!(browsed.len(), 3);
+        assert_eq!(browsed[0].mid(), messages[1].mid());
+
+        MockClock::advance(Duration::from_secs(61));
+        let msg5 = put(&store, "queue1", "msg5", None, None).await;
+        assert_eq!(store.get_messages(gmo).await.unwrap(), vec![msg5]);
+
+        store
+            .set_retention_policy(name("queue1"), None)
+            .await
+            .unwrap();
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert!(store.get_message(gmo).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_queue_lifecycle() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let settings = QueueSettings::default()
+            .with_dead_letter(Some(DeadLetterPolicy::new(1, name("dlq"))))
+            .with_retention(Some(RetentionPolicy::new(None, Some(2))));
+        assert!(store.create_queue(name("queue1"), &settings).await.unwrap());
+        assert!(!store.create_queue(name("queue1"), &settings).await.unwrap());
+        assert_eq!(depth(&store, "queue1").await, 0);
+        for data in ["msg1", "msg2", "msg3"] {
+            put(&store, "queue1", data, None, None).await;
+        }
+        assert_eq!(depth(&store, "queue1").await, 2);
+        let gmo =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10"}}"#);
+        let reserved = store.get_message(gmo).await.unwrap();
+        let gmo = gmo!(
+            r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+            reserved.receipt().unwrap()
+        );
+        store.get_message(gmo).await.unwrap();
+        assert_eq!(depth(&store, "dlq").await, 1);
+
+        assert_eq!(store.purge_queue(name("queue1")).await.unwrap(), 1);
+        assert_eq!(depth(&store, "queue1").await, 0);
+        store.delete_queue(name("queue1")).await.unwrap();
+        assert!(!store
+            .queue_list()
+            .await
+            .unwrap()
+            .0
+            .contains(&"queue1".to_string()));
+        assert!(matches!(
+            store.delete_queue(name("queue1")).await,
+            Err(QueueError::NoQueue(_))
+        ));
+        assert!(matches!(
+            store.purge_queue(name("queue1")).await,
+            Err(QueueError::NoQueue(_))
+        ));
+
+        for data in ["msg1", "msg2", "msg3"] {
+            put(&store, "queue1", data, None, None).await;
+        }
+        assert_eq!(depth(&store, "queue1").await, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_queue_config() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let send = |queue: &str, data: &str| {
+            let req = CreateMessageRequest::new(data.to_string(), None, None);
+            let store = store.clone();
+            let queue_name = name(queue);
+            async move { store.create_message(queue_name, &req).await }
+        };
+        let config = QueueConfig::default()
+            .with_expiry(Some(Duration::from_secs(10)))
+            .with_reservation(Some(Duration::from_secs(30)))
+            .with_max_depth(Some(2))
+            .with_max_content_size(Some(4));
+        let settings = QueueSettings::default().with_config(config);
+        store.create_queue(name("queue1"), &settings).await.unwrap();
+        assert!(send("queue1", "msg1").await.is_ok());
+        assert!(matches!(
+            send("queue1", "msg12").await,
+            Err(CreateMessageError::TooLarge(_))
+        ));
+        assert!(send("queue1", "msg2").await.is_ok());
+        assert!(matches!(
+            send("queue1", "msg3").await,
+            Err(CreateMessageError::QueueFull(_))
+        ));
+        let gmo = gmo!(r#"{{"action":"reserve","queue_name":"queue1"}}"#);
+        let reserved = store.get_message(gmo).await.unwrap();
+        assert!(reserved.is_reserved());
+
+        MockClock::advance(Duration::from_secs(11));
+        assert!(send("queue1", "msg3").await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 1);
+
+        assert!(send("queue2", "msg1").await.is_ok());
+        let gmo = gmo!(r#"{{"action":"reserve","queue_name":"queue2"}}"#);
+        assert!(matches!(
+            store.get_message(gmo).await,
+            Err(GetMessageError::NoReservation(_))
+        ));
+
+        let store = store.with_auto_create(false);
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None);
+        assert!(matches!(
+            store.create_message(name("queue3"), &req).await,
+            Err(CreateMessageError::NoQueue(_))
+        ));
+        assert!(store.create_message(name("queue2"), &req).await.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_no_auto_create() {
+        let store = Sqlite::new(":memory:")
+            .await
+            .unwrap()
+            .with_auto_create(false);
+        let name = |s: &str| -> QueueName { s.to_string().try_into().unwrap() };
+        let policy = DeadLetterPolicy::new(1, name("dlq"));
+        assert!(matches!(
+            store
+                .set_dead_letter_policy(name("queue1"), Some(policy.clone()))
+                .await,
+            Err(DeadLetterPolicyError::NoQueue(_))
+        ));
+        assert!(matches!(
+            store.set_retention_policy(name("queue1"), None).await,
+            Err(RetentionPolicyError::NoQueue(_))
+        ));
+        assert!(matches!(
+            store.subscribe(name("orders"), name("queue1")).await,
+            Err(SubscriptionError::NoQueue(_))
+        ));
+        assert!(store.queue_list().await.unwrap().0.is_empty());
+
+        let settings = QueueSettings::default().with_dead_letter(Some(policy));
+        store.create_queue(name("queue1"), &settings).await.unwrap();
+        let req = CreateMessageRequest::new("msg1".to_string(), None, None);
+        let msg1 = store.create_message(name("queue1"), &req).await.unwrap();
+        let reserve =
+            gmo!(r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"1000"}}"#);
+        let ret = |msg: &Message| {
+            gmo!(
+                r#"{{"action":"return","queue_name":"queue1","receipt":"{}"}}"#,
+                msg.receipt().unwrap()
+            )
+        };
+        // Without the dead-letter queue, the message stays where it is.
+        let reserved = store.get_message(reserve.clone()).await.unwrap();
+        assert!(store.get_message(ret(&reserved)).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 1);
+        let req = RedriveRequest::new(name("queue2"));
+        assert!(matches!(
+            store.redrive(name("queue1"), &req).await,
+            Err(RedriveError::NoQueue(_))
+        ));
+
+        store
+            .create_queue(name("dlq"), &QueueSettings::default())
+            .await
+            .unwrap();
+        let reserved = store.get_message(reserve).await.unwrap();
+        assert!(store.get_message(ret(&reserved)).await.is_ok());
+        assert_eq!(depth(&store, "queue1").await, 0);
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"dlq"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap().mid(), msg1.mid());
+        assert_eq!(store.queue_list().await.unwrap().0.len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_long_poll() {
+        let store = Sqlite::new(":memory:").await.unwrap();
+        let waiter = store.clone();
+        let handle = tokio::spawn(async move {
+            let gmo = gmo!(
+                r#"{{"action":"reserve","queue_name":"queue1","reservation_seconds":"10","wait_seconds":"10"}}"#
+            );
+            waiter.get_message(gmo).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+        let msg1 = put(&store, "queue1", "msg1", None, None).await;
+        let reserved = handle.await.unwrap().unwrap();
+        assert_eq!(reserved.mid(), msg1.mid());
+        assert!(reserved.is_reserved());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_messages_survive_reopen() {
+        let path = std::env::temp_dir().join(format!("msg_q-{}.db", Uuid::new_v4()));
+        let store = Sqlite::new(&path).await.unwrap();
+        let _msg1 = put(&store, "queue1", "msg1", None, None).await;
+        let msg2 = put(&store, "queue1", "msg2", None, None).await;
+        let gmo = gmo!(r#"{{"action":"get","queue_name":"queue1"}}"#);
+        assert!(store.get_message(gmo).await.is_ok());
+        drop(store);
+
+        let store = Sqlite::new(&path).await.unwrap();
+        let gmo = gmo!(r#"{{"action":"browse","queue_name":"queue1"}}"#);
+        assert_eq!(store.get_message(gmo).await.unwrap(), msg2);
+        let msg3 = put(&store, "queue1", "msg3", None, None).await;
+        assert_eq!(msg3.cursor(), 3);
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
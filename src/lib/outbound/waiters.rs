@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::domain::messages::models::message::{GetMessageError, QueueName};
+
+/// Reservations lapse and delayed messages become visible without anything
+/// waking the waiters, so a parked request looks at its queue again at least
+/// this often.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Requests parked until a message may have become available in a queue.  A
+/// queue only has an entry while something is waiting on it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Waiters {
+    queues: Arc<Mutex<HashMap<QueueName, Arc<Notify>>>>,
+}
+
+/// A request's place among the waiters on a queue, given up when it is
+/// dropped, whether the request finished or was abandoned.
+struct Registration<'a> {
+    waiters: &'a Waiters,
+    queue_name: &'a QueueName,
+    notify: Arc<Notify>,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut queues = self.waiters.queues.lock().unwrap();
+        // One reference is the map's and the other is this registration's.
+        if Arc::strong_count(&self.notify) == 2 {
+            queues.remove(self.queue_name);
+        }
+    }
+}
+
+impl Waiters {
+    fn register<'a>(&'a self, queue_name: &'a QueueName) -> Registration<'a> {
+        let notify = self
+            .queues
+            .lock()
+            .unwrap()
+            .entry(queue_name.clone())
+            .or_default()
+            .clone();
+        Registration {
+            waiters: self,
+            queue_name,
+            notify,
+        }
+    }
+
+    /// Wakes every request waiting on `queue_name`.
+    pub(crate) fn wake(&self, queue_name: &QueueName) {
+        if let Some(notify) = self.queues.lock().unwrap().get(queue_name) {
+            notify.notify_waiters();
+        }
+    }
+
+    /// Calls `attempt` until it finds a message or `wait` has passed, trying
+    /// again whenever `queue_name` is woken.  Without a `wait` the attempt is
+    /// made once.
+    pub(crate) async fn wait_for<T>(
+        &self,
+        queue_name: &QueueName,
+        wait: Option<Duration>,
+        mut attempt: impl FnMut() -> Result<T, GetMessageError>,
+    ) -> Result<T, GetMessageError> {
+        let Some(wait) = wait else {
+            return attempt();
+        };
+        let deadline = Instant::now() + wait;
+        let registration = self.register(queue_name);
+        loop {
+            // Register before looking so that a wake between the attempt and
+            // the wait is not lost.
+            let notified = registration.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            let result = attempt();
+            let now = Instant::now();
+            match result {
+                Err(GetMessageError::NoMessage(_)) if now < deadline => {}
+                result => return result,
+            }
+            let _ = tokio::time::timeout_at(deadline.min(now + RECHECK_INTERVAL), notified).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_entries_are_removed() {
+        let waiters = Waiters::default();
+        let queue_name: QueueName = "queue1".to_string().try_into().unwrap();
+        let wait = Some(Duration::from_millis(50));
+        let none = || Err::<(), _>(GetMessageError::NoMessage("queue1".to_string()));
+        let result = waiters.wait_for(&queue_name, wait, none).await;
+        assert!(result.is_err());
+        assert!(waiters.queues.lock().unwrap().is_empty());
+
+        let abandoned = waiters.wait_for(&queue_name, Some(Duration::from_secs(10)), none);
+        let _ = tokio::time::timeout(Duration::from_millis(50), abandoned).await;
+        assert!(waiters.queues.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,11 @@
+#!/bin/bash
+set -euo pipefail
+
+if [[ $# -ge 2 ]] ; then
+  action="?action=${2}"
+else
+  action=""
+fi
+
+
+curl -N "http://localhost:8000/api/$1/stream${action}"
@@ -0,0 +1,4 @@
+#!/bin/bash
+set -euo pipefail
+
+curl -X PUT http://localhost:8000/api/$1/subscriptions/$2